@@ -0,0 +1,202 @@
+use crate::wtf_profile::{Profile, StackId, StringId, EMPTY_STRING, NO_STACK};
+use std::cmp::Reverse;
+use std::collections::HashMap;
+
+pub type NodeId = u32;
+
+// The root node has no function, and holds the weight of samples with an empty callchain
+pub const ROOT_NODE: NodeId = 0;
+
+const NO_NODE: NodeId = u32::MAX;
+
+// Samples aggregated by their path of functions, starting from the outermost frame
+// Frames with different addresses in the same function are merged into one node
+pub struct AggregatedTree {
+    pub nodes: Vec<TreeNode>,
+}
+
+pub struct TreeNode {
+    pub function: StringId,
+    pub module: StringId,
+    pub parent: NodeId,
+    // Sorted by total weight, heaviest first
+    pub children: Vec<NodeId>,
+    // Weight of samples whose innermost frame is this node
+    pub self_weight: u64,
+    // Weight of samples passing through this node, including its children
+    pub total_weight: u64,
+}
+
+impl AggregatedTree {
+    // Aggregate every sample with start <= time <= end
+    pub fn top_down(profile: &Profile, start: u64, end: u64) -> Self {
+        let mut builder = TreeBuilder::new(profile);
+        for &tid in profile.threads.tid.iter() {
+            for sample in profile.thread_samples_in_range(tid, start, end) {
+                let node = builder.node_for_stack(profile.samples.stack[sample]);
+                builder.nodes[node as usize].self_weight += profile.samples.weight[sample] as u64;
+            }
+        }
+        builder.sum_totals();
+        builder.link_children()
+    }
+
+    // Aggregate every sample with start <= time <= end, by their path of functions starting from
+    // the innermost frame, so that the top level nodes are the functions samples were taken in
+    pub fn bottom_up(profile: &Profile, start: u64, end: u64) -> Self {
+        let mut builder = TreeBuilder::new(profile);
+        for (stack, weight) in weighted_stacks(profile, start, end) {
+            builder.nodes[ROOT_NODE as usize].total_weight += weight;
+
+            // Each node's path is a distinct suffix of the stack, so nothing is counted twice
+            let mut node = ROOT_NODE;
+            for frame in profile.stack_frames(stack) {
+                let frame = frame as usize;
+                let function = profile.frames.function[frame];
+                let module = profile.frames.module[frame];
+                node = builder.child(node, function, module);
+                builder.nodes[node as usize].total_weight += weight;
+            }
+
+            // Only the innermost frame is self time, and it's always at the top level
+            if stack == NO_STACK {
+                builder.nodes[ROOT_NODE as usize].self_weight += weight;
+            } else {
+                let leaf_frame = profile.stacks.frame[stack as usize] as usize;
+                let leaf = (
+                    ROOT_NODE,
+                    profile.frames.function[leaf_frame],
+                    profile.frames.module[leaf_frame],
+                );
+                builder.nodes[builder.children[&leaf] as usize].self_weight += weight;
+            }
+        }
+        builder.link_children()
+    }
+
+    pub fn node(&self, node: NodeId) -> &TreeNode {
+        &self.nodes[node as usize]
+    }
+
+    pub fn total_weight(&self) -> u64 {
+        self.node(ROOT_NODE).total_weight
+    }
+}
+
+// The total weight of each distinct stack sampled with start <= time <= end
+// Most samples share a few stacks, so analyses that walk whole stacks work from these instead
+pub(super) fn weighted_stacks(profile: &Profile, start: u64, end: u64) -> Vec<(StackId, u64)> {
+    let mut stack_weights = HashMap::new();
+    for &tid in profile.threads.tid.iter() {
+        for sample in profile.thread_samples_in_range(tid, start, end) {
+            *stack_weights
+                .entry(profile.samples.stack[sample])
+                .or_insert(0) += profile.samples.weight[sample] as u64;
+        }
+    }
+    // Sorted so that results don't depend on the hash map's iteration order
+    let mut stack_weights = stack_weights.into_iter().collect::<Vec<_>>();
+    stack_weights.sort_unstable();
+    stack_weights
+}
+
+struct TreeBuilder<'a> {
+    profile: &'a Profile,
+    nodes: Vec<TreeNode>,
+    // (parent, function, module) -> child
+    children: HashMap<(NodeId, StringId, StringId), NodeId>,
+    // Memoized node of each profile stack, as most samples share a few stacks
+    stack_nodes: Vec<NodeId>,
+}
+
+impl<'a> TreeBuilder<'a> {
+    fn new(profile: &'a Profile) -> Self {
+        Self {
+            profile,
+            nodes: vec![TreeNode::new(EMPTY_STRING, EMPTY_STRING, NO_NODE)],
+            children: HashMap::new(),
+            stack_nodes: vec![NO_NODE; profile.stacks.len()],
+        }
+    }
+
+    fn node_for_stack(&mut self, stack: StackId) -> NodeId {
+        // Walk towards the root until reaching a stack that already has a node...
+        let mut unresolved = Vec::new();
+        let mut node = ROOT_NODE;
+        let mut current = stack;
+        while current != NO_STACK {
+            let known_node = self.stack_nodes[current as usize];
+            if known_node != NO_NODE {
+                node = known_node;
+                break;
+            }
+            unresolved.push(current);
+            current = self.profile.stacks.parent[current as usize];
+        }
+
+        // ...then create nodes back down towards the leaf
+        for &stack in unresolved.iter().rev() {
+            let frame = self.profile.stacks.frame[stack as usize] as usize;
+            let function = self.profile.frames.function[frame];
+            let module = self.profile.frames.module[frame];
+            node = self.child(node, function, module);
+            self.stack_nodes[stack as usize] = node;
+        }
+        node
+    }
+
+    fn child(&mut self, parent: NodeId, function: StringId, module: StringId) -> NodeId {
+        let nodes = &mut self.nodes;
+        *self
+            .children
+            .entry((parent, function, module))
+            .or_insert_with(|| {
+                nodes.push(TreeNode::new(function, module, parent));
+                (nodes.len() - 1) as NodeId
+            })
+    }
+
+    fn sum_totals(&mut self) {
+        // Children are always created after their parents, so one reverse pass sums every total
+        for node in (0..self.nodes.len()).rev() {
+            let tree_node = &mut self.nodes[node];
+            tree_node.total_weight += tree_node.self_weight;
+            let (parent, total_weight) = (tree_node.parent, tree_node.total_weight);
+            if parent != NO_NODE {
+                self.nodes[parent as usize].total_weight += total_weight;
+            }
+        }
+    }
+
+    fn link_children(mut self) -> AggregatedTree {
+        for node in 1..self.nodes.len() {
+            let parent = self.nodes[node].parent;
+            self.nodes[parent as usize].children.push(node as NodeId);
+        }
+
+        let total_weights = self
+            .nodes
+            .iter()
+            .map(|n| n.total_weight)
+            .collect::<Vec<_>>();
+        for node in &mut self.nodes {
+            node.children
+                .sort_by_key(|&child| Reverse(total_weights[child as usize]));
+        }
+
+        AggregatedTree { nodes: self.nodes }
+    }
+}
+
+impl TreeNode {
+    fn new(function: StringId, module: StringId, parent: NodeId) -> Self {
+        Self {
+            function,
+            module,
+            parent,
+            children: Vec::new(),
+            self_weight: 0,
+            total_weight: 0,
+        }
+    }
+}
@@ -0,0 +1,80 @@
+use super::aggregated_tree::weighted_stacks;
+use crate::wtf_profile::{Profile, StringId};
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+
+// A function's callers and callees, and how much time each accounts for
+// Samples are counted at most once per function, so recursive calls don't inflate any totals
+pub struct Butterfly {
+    // (function, module)
+    pub function: (StringId, StringId),
+    pub self_weight: u64,
+    pub total_weight: u64,
+    // Sorted by weight, heaviest first
+    pub callers: Vec<((StringId, StringId), u64)>,
+    pub callees: Vec<((StringId, StringId), u64)>,
+    // Weight of every sample in the range, for computing percentages
+    pub profile_weight: u64,
+}
+
+impl Butterfly {
+    // Aggregate every sample with start <= time <= end that passes through function
+    pub fn new(profile: &Profile, start: u64, end: u64, function: (StringId, StringId)) -> Self {
+        let mut butterfly = Self {
+            function,
+            self_weight: 0,
+            total_weight: 0,
+            callers: Vec::new(),
+            callees: Vec::new(),
+            profile_weight: 0,
+        };
+        let mut callers = HashMap::new();
+        let mut callees = HashMap::new();
+        let mut stack_functions = Vec::new();
+        let mut seen_callers = HashSet::new();
+        let mut seen_callees = HashSet::new();
+
+        for (stack, weight) in weighted_stacks(profile, start, end) {
+            butterfly.profile_weight += weight;
+
+            // Innermost function first
+            stack_functions.clear();
+            stack_functions.extend(profile.stack_frames(stack).map(|frame| {
+                let frame = frame as usize;
+                (profile.frames.function[frame], profile.frames.module[frame])
+            }));
+            if !stack_functions.contains(&function) {
+                continue;
+            }
+
+            butterfly.total_weight += weight;
+            if stack_functions[0] == function {
+                butterfly.self_weight += weight;
+            }
+
+            seen_callers.clear();
+            seen_callees.clear();
+            let calls = stack_functions.iter().enumerate();
+            for (i, _) in calls.filter(|&(_, &called)| called == function) {
+                if let Some(&caller) = stack_functions.get(i + 1) {
+                    if seen_callers.insert(caller) {
+                        *callers.entry(caller).or_insert(0) += weight;
+                    }
+                }
+                if i > 0 && seen_callees.insert(stack_functions[i - 1]) {
+                    *callees.entry(stack_functions[i - 1]).or_insert(0) += weight;
+                }
+            }
+        }
+
+        butterfly.callers = callers.into_iter().collect();
+        butterfly
+            .callers
+            .sort_by_key(|&(_, weight)| Reverse(weight));
+        butterfly.callees = callees.into_iter().collect();
+        butterfly
+            .callees
+            .sort_by_key(|&(_, weight)| Reverse(weight));
+        butterfly
+    }
+}
@@ -0,0 +1,117 @@
+use super::aggregated_tree::weighted_stacks;
+use crate::wtf_profile::{FrameId, Profile};
+use std::cmp::Reverse;
+use std::collections::HashMap;
+
+// Crates shipped with the Rust toolchain
+const STD_CRATES: &[&str] = &[
+    "std",
+    "core",
+    "alloc",
+    "proc_macro",
+    "test",
+    "panic_unwind",
+    "panic_abort",
+    "compiler_builtins",
+];
+
+// What a frame's code belongs to, used to group and color frames
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Category<'a> {
+    // Part of the Rust standard library
+    Std(&'a str),
+    // A Rust crate, by name
+    Crate(&'a str),
+    // Code without a crate path (e.g. C code, or the main binary), by the file name of its module (DSO)
+    Module(&'a str),
+    Unknown,
+}
+
+impl<'a> Category<'a> {
+    pub fn of(function: &'a str, module: &'a str) -> Self {
+        match crate_of(function) {
+            Some(crate_name) if STD_CRATES.contains(&crate_name) => Category::Std(crate_name),
+            Some(crate_name) => Category::Crate(crate_name),
+            None if !module.is_empty() => Category::Module(module_file_name(module)),
+            None => Category::Unknown,
+        }
+    }
+
+    pub fn of_frame(profile: &'a Profile, frame: FrameId) -> Self {
+        let frame = frame as usize;
+        Self::of(
+            profile.strings.get(profile.frames.function[frame]),
+            profile.strings.get(profile.frames.module[frame]),
+        )
+    }
+
+    pub fn name(&self) -> &'a str {
+        match self {
+            Category::Std(name) | Category::Crate(name) | Category::Module(name) => name,
+            Category::Unknown => "Unknown",
+        }
+    }
+}
+
+pub struct CategoryTime<'a> {
+    pub category: Category<'a>,
+    // Weight of samples whose innermost frame is in the category
+    pub self_weight: u64,
+    // Weight of samples with any frame in the category
+    pub total_weight: u64,
+}
+
+// Time spent in each category by samples with start <= time <= end, sorted by self time, heaviest first
+pub fn time_by_category(profile: &Profile, start: u64, end: u64) -> Vec<CategoryTime<'_>> {
+    let mut times: HashMap<Category, CategoryTime> = HashMap::new();
+    let mut stack_categories = Vec::new();
+    for (stack, weight) in weighted_stacks(profile, start, end) {
+        stack_categories.clear();
+        for frame in profile.stack_frames(stack) {
+            let category = Category::of_frame(profile, frame);
+            if !stack_categories.contains(&category) {
+                stack_categories.push(category);
+            }
+        }
+
+        // Frames are walked from the innermost one, so the first category is the self time's
+        for (i, &category) in stack_categories.iter().enumerate() {
+            let time = times.entry(category).or_insert(CategoryTime {
+                category,
+                self_weight: 0,
+                total_weight: 0,
+            });
+            time.total_weight += weight;
+            if i == 0 {
+                time.self_weight += weight;
+            }
+        }
+    }
+
+    let mut times = times.into_values().collect::<Vec<_>>();
+    times.sort_by_key(|time| {
+        (
+            Reverse(time.self_weight),
+            Reverse(time.total_weight),
+            time.category.name(),
+        )
+    });
+    times
+}
+
+// The crate a demangled Rust function belongs to, e.g. "tokio" for
+// "tokio::runtime::task::raw::poll" or "<tokio::net::TcpStream as std::io::Read>::read"
+pub fn crate_of(function: &str) -> Option<&str> {
+    let path = function.trim_start_matches(['<', '&']);
+    let path = path.strip_prefix("mut ").unwrap_or(path);
+    let (crate_name, _) = path.split_once("::")?;
+    let is_identifier = !crate_name.is_empty()
+        && crate_name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_');
+    is_identifier.then_some(crate_name)
+}
+
+pub fn module_file_name(module: &str) -> &str {
+    module.rsplit('/').next().unwrap_or(module)
+}
@@ -0,0 +1,99 @@
+use crate::wtf_profile::Profile;
+use std::collections::BTreeMap;
+
+// Sample gaps measured to estimate the sampling interval, enough for a stable median
+const MAX_INTERVALS_MEASURED: usize = 100_000;
+
+// Every thread's samples, grouped by the CPU they were taken on
+pub struct CpuSamples {
+    // Every CPU samples were taken on, ascending
+    pub cpus: Vec<u32>,
+    // Typical time between samples of a running thread, or 0 if there were too few samples to tell
+    pub sample_interval: u64,
+    // For each of cpus, indices into samples sorted by time
+    samples: Vec<Vec<usize>>,
+}
+
+impl CpuSamples {
+    pub fn new(profile: &Profile) -> Self {
+        let mut samples_by_cpu: BTreeMap<u32, Vec<usize>> = BTreeMap::new();
+        for sample in 0..profile.samples.len() {
+            samples_by_cpu
+                .entry(profile.samples.cpu[sample])
+                .or_default()
+                .push(sample);
+        }
+        for samples in samples_by_cpu.values_mut() {
+            samples.sort_by_key(|&sample| profile.samples.time[sample]);
+        }
+
+        Self {
+            cpus: samples_by_cpu.keys().copied().collect(),
+            sample_interval: sample_interval(profile),
+            samples: samples_by_cpu.into_values().collect(),
+        }
+    }
+
+    // Samples taken on cpus[cpu_index] with start <= time <= end
+    pub fn samples_in_range(
+        &self,
+        profile: &Profile,
+        cpu_index: usize,
+        start: u64,
+        end: u64,
+    ) -> &[usize] {
+        let samples = &self.samples[cpu_index];
+        let first = samples.partition_point(|&sample| profile.samples.time[sample] < start);
+        let last = samples.partition_point(|&sample| profile.samples.time[sample] <= end);
+        &samples[first..last.max(first)]
+    }
+
+    // The average number of busy CPUs in each of bucket_count equal parts of start..=end
+    pub fn usage(&self, profile: &Profile, start: u64, end: u64, bucket_count: usize) -> Vec<f64> {
+        if bucket_count == 0 || end < start {
+            return Vec::new();
+        }
+
+        let duration = end - start + 1;
+        let mut weights = vec![0u64; bucket_count];
+        for cpu_index in 0..self.cpus.len() {
+            for &sample in self.samples_in_range(profile, cpu_index, start, end) {
+                let offset = (profile.samples.time[sample] - start) as u128;
+                let bucket = (offset * bucket_count as u128 / duration as u128) as usize;
+                weights[bucket] += profile.samples.weight[sample] as u64;
+            }
+        }
+
+        // Each sample stands for a sample interval of one CPU being busy
+        let bucket_duration = duration as f64 / bucket_count as f64;
+        weights
+            .into_iter()
+            .map(|weight| weight as f64 * self.sample_interval as f64 / bucket_duration)
+            .collect()
+    }
+}
+
+// The median gap between consecutive samples of the same thread
+// Threads are sampled at a fixed interval while running, and gaps from sleeping are rarer
+fn sample_interval(profile: &Profile) -> u64 {
+    let mut intervals = Vec::new();
+    for &tid in profile.threads.tid.iter() {
+        let samples = profile.thread_samples_in_range(tid, 0, u64::MAX);
+        let times = &profile.samples.time[samples];
+        intervals.extend(
+            times
+                .windows(2)
+                .map(|times| times[1] - times[0])
+                .filter(|&interval| interval > 0),
+        );
+        if intervals.len() >= MAX_INTERVALS_MEASURED {
+            break;
+        }
+    }
+
+    if intervals.is_empty() {
+        return 0;
+    }
+    let middle = intervals.len() / 2;
+    *intervals.select_nth_unstable(middle).1
+}
@@ -0,0 +1,120 @@
+use super::aggregated_tree::{AggregatedTree, NodeId, ROOT_NODE};
+use crate::wtf_profile::Profile;
+use std::collections::HashMap;
+
+pub const BEFORE: usize = 0;
+pub const AFTER: usize = 1;
+
+// How weights of two profiles are made comparable
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Normalization {
+    // Fraction of all samples in the profile
+    PerSample,
+    // Samples per second of the profile's duration
+    PerSecond,
+}
+
+// Two profiles aggregated into one top-down tree, with nodes matched by function name
+// rather than by address or module, so that different builds of a program line up
+pub struct DiffTree {
+    pub nodes: Vec<DiffNode>,
+    // Multiplies weights of each profile into normalized values, indexed by BEFORE and AFTER
+    scales: [f64; 2],
+}
+
+pub struct DiffNode {
+    pub name: String,
+    pub parent: NodeId,
+    // Sorted by the larger of their normalized total values, heaviest first
+    pub children: Vec<NodeId>,
+    // Indexed by BEFORE and AFTER
+    pub self_weight: [u64; 2],
+    pub total_weight: [u64; 2],
+}
+
+impl DiffTree {
+    pub fn new(before: &Profile, after: &Profile, normalization: Normalization) -> Self {
+        let mut nodes = vec![DiffNode::new(String::new(), ROOT_NODE)];
+        // (parent, name) -> child
+        let mut children: HashMap<(NodeId, String), NodeId> = HashMap::new();
+        let mut scales = [0.0; 2];
+
+        for (side, profile) in [(BEFORE, before), (AFTER, after)] {
+            let (start, end) = profile.time_range().unwrap_or_default();
+            let tree = AggregatedTree::top_down(profile, start, end);
+            scales[side] = match normalization {
+                Normalization::PerSample => 1.0 / tree.total_weight().max(1) as f64,
+                Normalization::PerSecond => 1_000_000_000.0 / (end - start).max(1) as f64,
+            };
+
+            // Parents are always before their children, so their diff nodes already exist
+            let mut diff_nodes = vec![ROOT_NODE; tree.nodes.len()];
+            for (node, tree_node) in tree.nodes.iter().enumerate() {
+                let diff_node = if node == ROOT_NODE as usize {
+                    ROOT_NODE
+                } else {
+                    let parent = diff_nodes[tree_node.parent as usize];
+                    let name = profile.strings.get(tree_node.function).to_owned();
+                    *children.entry((parent, name.clone())).or_insert_with(|| {
+                        nodes.push(DiffNode::new(name, parent));
+                        (nodes.len() - 1) as NodeId
+                    })
+                };
+                diff_nodes[node] = diff_node;
+                nodes[diff_node as usize].self_weight[side] += tree_node.self_weight;
+                nodes[diff_node as usize].total_weight[side] += tree_node.total_weight;
+            }
+        }
+
+        for node in 1..nodes.len() {
+            let parent = nodes[node].parent;
+            nodes[parent as usize].children.push(node as NodeId);
+        }
+        let mut diff_tree = Self { nodes, scales };
+        let sort_keys = (0..diff_tree.nodes.len())
+            .map(|node| {
+                let (before, after) = diff_tree.total(node as NodeId);
+                before.max(after)
+            })
+            .collect::<Vec<_>>();
+        for node in &mut diff_tree.nodes {
+            node.children
+                .sort_by(|&a, &b| sort_keys[b as usize].total_cmp(&sort_keys[a as usize]));
+        }
+        diff_tree
+    }
+
+    pub fn node(&self, node: NodeId) -> &DiffNode {
+        &self.nodes[node as usize]
+    }
+
+    // Normalized total value of a node, before and after
+    pub fn total(&self, node: NodeId) -> (f64, f64) {
+        let weights = self.node(node).total_weight;
+        (
+            weights[BEFORE] as f64 * self.scales[BEFORE],
+            weights[AFTER] as f64 * self.scales[AFTER],
+        )
+    }
+
+    // Normalized self value of a node, before and after
+    pub fn self_value(&self, node: NodeId) -> (f64, f64) {
+        let weights = self.node(node).self_weight;
+        (
+            weights[BEFORE] as f64 * self.scales[BEFORE],
+            weights[AFTER] as f64 * self.scales[AFTER],
+        )
+    }
+}
+
+impl DiffNode {
+    fn new(name: String, parent: NodeId) -> Self {
+        Self {
+            name,
+            parent,
+            children: Vec::new(),
+            self_weight: [0; 2],
+            total_weight: [0; 2],
+        }
+    }
+}
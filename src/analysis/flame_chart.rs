@@ -0,0 +1,78 @@
+use crate::wtf_profile::{Profile, StringId};
+
+// A function staying on a thread's stack across consecutive samples
+pub struct Span {
+    pub function: StringId,
+    pub module: StringId,
+    // 0 for the outermost frame
+    pub depth: u32,
+    pub start: u64,
+    pub end: u64,
+}
+
+// Spans of a thread's samples with start <= time <= end, outermost first
+// Samples closer together than min_interval (e.g. within the same pixel) are skipped,
+// so that the number of samples walked is bounded when zoomed out
+pub fn thread_spans(
+    profile: &Profile,
+    tid: u32,
+    start: u64,
+    end: u64,
+    min_interval: u64,
+) -> Vec<Span> {
+    let mut spans: Vec<Span> = Vec::new();
+    // Spans still on the stack, indices into spans, outermost first
+    let mut open_spans: Vec<usize> = Vec::new();
+    let mut functions = Vec::new();
+    let mut last_time = None;
+
+    for sample in profile.thread_samples_in_range(tid, start, end) {
+        let time = profile.samples.time[sample];
+        if matches!(last_time, Some(last_time) if time < last_time + min_interval) {
+            continue;
+        }
+        last_time = Some(time);
+
+        // Outermost function first
+        functions.clear();
+        functions.extend(
+            profile
+                .stack_frames(profile.samples.stack[sample])
+                .map(|frame| {
+                    let frame = frame as usize;
+                    (profile.frames.function[frame], profile.frames.module[frame])
+                }),
+        );
+        functions.reverse();
+
+        // Spans are only continued while every span outside of them is continued too
+        let common_depth = open_spans
+            .iter()
+            .zip(&functions)
+            .take_while(|(&span, &(function, module))| {
+                (spans[span].function, spans[span].module) == (function, module)
+            })
+            .count();
+        for span in open_spans.drain(common_depth..) {
+            spans[span].end = time;
+        }
+        for (depth, &(function, module)) in functions.iter().enumerate().skip(common_depth) {
+            open_spans.push(spans.len());
+            spans.push(Span {
+                function,
+                module,
+                depth: depth as u32,
+                start: time,
+                end: time,
+            });
+        }
+    }
+
+    // Spans still open at the end extend to the last sample
+    if let Some(last_time) = last_time {
+        for span in open_spans {
+            spans[span].end = last_time;
+        }
+    }
+    spans
+}
@@ -0,0 +1,32 @@
+use super::aggregated_tree::weighted_stacks;
+use super::stack_transforms::Function;
+use crate::wtf_profile::Profile;
+use std::collections::HashMap;
+use std::ops::Range;
+
+// Weight of samples with start <= time <= end whose instruction pointer was at each address in addresses
+pub fn instruction_weights(
+    profile: &Profile,
+    start: u64,
+    end: u64,
+    addresses: Range<u64>,
+) -> HashMap<u64, u64> {
+    let mut weights = HashMap::new();
+    for (stack, weight) in weighted_stacks(profile, start, end) {
+        // The innermost frame's address is where the sample was taken
+        if let Some(frame) = profile.stack_frames(stack).next() {
+            let address = profile.frames.address[frame as usize];
+            if addresses.contains(&address) {
+                *weights.entry(address).or_default() += weight;
+            }
+        }
+    }
+    weights
+}
+
+// The address of any instruction a function's samples were taken in or called from
+pub fn function_address(profile: &Profile, function: Function) -> Option<u64> {
+    (0..profile.frames.len())
+        .find(|&frame| (profile.frames.function[frame], profile.frames.module[frame]) == function)
+        .map(|frame| profile.frames.address[frame])
+}
@@ -0,0 +1,27 @@
+mod aggregated_tree;
+mod butterfly;
+mod categories;
+mod cpu_samples;
+mod diff;
+mod flame_chart;
+mod instruction_weights;
+mod sample_groups;
+mod sample_pyramid;
+mod search;
+mod source_lines;
+mod stack_transforms;
+mod threads;
+
+pub use aggregated_tree::{AggregatedTree, NodeId, TreeNode, ROOT_NODE};
+pub use butterfly::Butterfly;
+pub use categories::{crate_of, module_file_name, time_by_category, Category, CategoryTime};
+pub use cpu_samples::CpuSamples;
+pub use diff::{DiffNode, DiffTree, Normalization, AFTER, BEFORE};
+pub use flame_chart::{thread_spans, Span};
+pub use instruction_weights::{function_address, instruction_weights};
+pub use sample_groups::SampleGroup;
+pub use sample_pyramid::{sample_columns, SampleBucket, SamplePyramid};
+pub use search::SearchMatches;
+pub use source_lines::{function_source_lines, LineWeight, SourceFile};
+pub use stack_transforms::{apply_stack_transforms, Function, StackTransform};
+pub use threads::{filter_threads, sort_threads, thread_summaries, ThreadOrder, ThreadSummary};
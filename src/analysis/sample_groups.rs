@@ -0,0 +1,52 @@
+use crate::wtf_profile::{Profile, StackId};
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::ops::Range;
+
+// Consecutive samples of a thread, drawn too close together to tell apart, inspected as one
+#[derive(Clone)]
+pub struct SampleGroup {
+    pub tid: u32,
+    // Indices into samples
+    pub samples: Range<usize>,
+    // The stack sampled most often in the group, standing in for the rest
+    pub stack: StackId,
+    // How many of the group's samples had that stack
+    pub stack_count: usize,
+}
+
+impl SampleGroup {
+    // The thread's samples with start <= time <= end, or None if there are none
+    pub fn new(profile: &Profile, tid: u32, start: u64, end: u64) -> Option<Self> {
+        let samples = profile.thread_samples_in_range(tid, start, end);
+        let mut stack_counts: HashMap<StackId, usize> = HashMap::new();
+        for sample in samples.clone() {
+            *stack_counts
+                .entry(profile.samples.stack[sample])
+                .or_default() += 1;
+        }
+        // Ties go to the lowest stack id, so a group always stands for the same stack
+        let (stack, stack_count) = stack_counts
+            .into_iter()
+            .max_by_key(|&(stack, count)| (count, Reverse(stack)))?;
+
+        Some(Self {
+            tid,
+            samples,
+            stack,
+            stack_count,
+        })
+    }
+
+    pub fn sample_count(&self) -> usize {
+        self.samples.len()
+    }
+
+    pub fn start_time(&self, profile: &Profile) -> u64 {
+        profile.samples.time[self.samples.start]
+    }
+
+    pub fn end_time(&self, profile: &Profile) -> u64 {
+        profile.samples.time[self.samples.end - 1]
+    }
+}
@@ -0,0 +1,170 @@
+use crate::wtf_profile::{Profile, StackId, NO_STACK};
+
+// Level 0 of a pyramid has at most this many buckets, bounding its memory for long profiles
+const MAX_FINEST_BUCKETS: u64 = 1 << 16;
+
+// The samples of a thread over some span of time
+#[derive(Clone, Copy)]
+pub struct SampleBucket {
+    pub count: u32,
+    pub min_depth: u32,
+    pub max_depth: u32,
+    // Stack of the deepest sample, standing in for the rest
+    pub deepest_stack: StackId,
+}
+
+impl Default for SampleBucket {
+    fn default() -> Self {
+        Self {
+            count: 0,
+            min_depth: u32::MAX,
+            max_depth: 0,
+            deepest_stack: NO_STACK,
+        }
+    }
+}
+
+impl SampleBucket {
+    pub fn add(&mut self, depth: u32, stack: StackId) {
+        self.count += 1;
+        self.min_depth = self.min_depth.min(depth);
+        if self.count == 1 || depth > self.max_depth {
+            self.max_depth = depth;
+            self.deepest_stack = stack;
+        }
+    }
+
+    pub fn merge(&mut self, other: &SampleBucket) {
+        if other.count == 0 {
+            return;
+        }
+        if self.count == 0 || other.max_depth > self.max_depth {
+            self.max_depth = other.max_depth;
+            self.deepest_stack = other.deepest_stack;
+        }
+        self.count += other.count;
+        self.min_depth = self.min_depth.min(other.min_depth);
+    }
+}
+
+// A thread's samples bucketed at every power of two multiple of a finest bucket duration, so any
+// span of time can be summarized from a handful of buckets
+pub struct SamplePyramid {
+    // Start of the first bucket of every level
+    start: u64,
+    // Time of the thread's last sample
+    end: u64,
+    finest_duration: u64,
+    // levels[n] has buckets of finest_duration << n, the last level has a single bucket
+    levels: Vec<Vec<SampleBucket>>,
+}
+
+impl SamplePyramid {
+    pub fn new(profile: &Profile, tid: u32) -> Self {
+        let samples = profile.thread_samples_in_range(tid, 0, u64::MAX);
+        let (start, end) = match samples.len() {
+            0 => (0, 0),
+            _ => (
+                profile.samples.time[samples.start],
+                profile.samples.time[samples.end - 1],
+            ),
+        };
+
+        // The finest buckets hold about one sample each, unless there are too many samples
+        let finest_count = (samples.len() as u64).clamp(1, MAX_FINEST_BUCKETS);
+        let finest_duration = ((end - start) / finest_count).max(1);
+        let mut finest =
+            vec![SampleBucket::default(); ((end - start) / finest_duration + 1) as usize];
+        for sample in samples {
+            let bucket = (profile.samples.time[sample] - start) / finest_duration;
+            let stack = profile.samples.stack[sample];
+            finest[bucket as usize].add(profile.stack_depth(stack) as u32, stack);
+        }
+
+        let mut levels = vec![finest];
+        while levels.last().unwrap().len() > 1 {
+            let coarser = levels
+                .last()
+                .unwrap()
+                .chunks(2)
+                .map(|pair| {
+                    let mut bucket = pair[0];
+                    if let Some(second) = pair.get(1) {
+                        bucket.merge(second);
+                    }
+                    bucket
+                })
+                .collect();
+            levels.push(coarser);
+        }
+
+        Self {
+            start,
+            end,
+            finest_duration,
+            levels,
+        }
+    }
+
+    // Samples with start <= time <= end, summarized into column_count equal columns
+    // The coarsest buckets no longer than a column are used, so the cost depends on column_count
+    // rather than the number of samples
+    // None if columns are shorter than the finest buckets, where sample_columns should be used
+    pub fn columns(&self, start: u64, end: u64, column_count: usize) -> Option<Vec<SampleBucket>> {
+        if column_count == 0 || end <= start {
+            return Some(Vec::new());
+        }
+        let column_duration = (end - start) as f64 / column_count as f64;
+        if column_duration < self.finest_duration as f64 {
+            return None;
+        }
+        // Clamping to the first or last bucket would show samples from outside the range
+        if end < self.start || start > self.end {
+            return Some(vec![SampleBucket::default(); column_count]);
+        }
+
+        let level = ((column_duration / self.finest_duration as f64)
+            .log2()
+            .floor() as usize)
+            .min(self.levels.len() - 1);
+        let bucket_duration = self.finest_duration << level;
+        let buckets = &self.levels[level];
+        let first = (start.saturating_sub(self.start) / bucket_duration) as usize;
+        let last =
+            ((end.saturating_sub(self.start) / bucket_duration) as usize).min(buckets.len() - 1);
+
+        let mut columns = vec![SampleBucket::default(); column_count];
+        for (index, bucket) in buckets.iter().enumerate().take(last + 1).skip(first) {
+            // Buckets go in the column their start is drawn in, or the first if they start before it
+            let bucket_start = self.start + index as u64 * bucket_duration;
+            if bucket_start > end {
+                break;
+            }
+            let column = (bucket_start.saturating_sub(start) as f64 / column_duration) as usize;
+            columns[column.min(column_count - 1)].merge(bucket);
+        }
+        Some(columns)
+    }
+}
+
+// Samples of a thread with start <= time <= end, summarized into column_count equal columns
+// Every sample in range is visited, so this is for spans too short for a SamplePyramid
+pub fn sample_columns(
+    profile: &Profile,
+    tid: u32,
+    start: u64,
+    end: u64,
+    column_count: usize,
+) -> Vec<SampleBucket> {
+    let mut columns = vec![SampleBucket::default(); column_count];
+    if column_count == 0 || end <= start {
+        return columns;
+    }
+    let column_duration = (end - start) as f64 / column_count as f64;
+    for sample in profile.thread_samples_in_range(tid, start, end) {
+        let column = ((profile.samples.time[sample] - start) as f64 / column_duration) as usize;
+        let stack = profile.samples.stack[sample];
+        columns[column.min(column_count - 1)].add(profile.stack_depth(stack) as u32, stack);
+    }
+    columns
+}
@@ -0,0 +1,78 @@
+use super::aggregated_tree::weighted_stacks;
+use super::stack_transforms::Function;
+use crate::wtf_profile::{Profile, StackId, NO_STACK};
+use regex::{escape, Error as RegexError, RegexBuilder};
+use std::collections::HashSet;
+
+// The frames and stacks of a profile matching a search of function names, file paths, and modules
+pub struct SearchMatches {
+    // Indexed by StackId, true if any frame of the stack matches
+    stacks: Vec<bool>,
+    functions: HashSet<Function>,
+}
+
+impl SearchMatches {
+    // Searches are case insensitive, and match substrings unless use_regex is set
+    pub fn new(profile: &Profile, query: &str, use_regex: bool) -> Result<Self, RegexError> {
+        let pattern = if use_regex {
+            query.to_owned()
+        } else {
+            escape(query)
+        };
+        let regex = RegexBuilder::new(&pattern).case_insensitive(true).build()?;
+
+        // Most strings are shared by many frames, so each one is only matched once
+        let strings = (0..profile.strings.len())
+            .map(|string| regex.is_match(profile.strings.get(string as u32)))
+            .collect::<Vec<_>>();
+
+        let mut functions = HashSet::new();
+        let frames = (0..profile.frames.len())
+            .map(|frame| {
+                let function = profile.frames.function[frame];
+                let module = profile.frames.module[frame];
+                let file = profile.frames.file[frame];
+                let matches = [function, module, file]
+                    .iter()
+                    .any(|&string| strings[string as usize]);
+                if matches {
+                    functions.insert((function, module));
+                }
+                matches
+            })
+            .collect::<Vec<_>>();
+
+        // Parents always come before their children, so one pass covers every stack
+        let mut stacks: Vec<bool> = Vec::with_capacity(profile.stacks.len());
+        for stack in 0..profile.stacks.len() {
+            let parent = profile.stacks.parent[stack];
+            let frame = profile.stacks.frame[stack];
+            let parent_matches = parent != NO_STACK && stacks[parent as usize];
+            stacks.push(parent_matches || frames[frame as usize]);
+        }
+
+        Ok(Self { stacks, functions })
+    }
+
+    pub fn matches_function(&self, function: Function) -> bool {
+        self.functions.contains(&function)
+    }
+
+    pub fn matches_stack(&self, stack: StackId) -> bool {
+        stack != NO_STACK && self.stacks[stack as usize]
+    }
+
+    // Weight of samples with start <= time <= end with a matching frame on their stack,
+    // and the weight of every sample in that range
+    pub fn matched_weight(&self, profile: &Profile, start: u64, end: u64) -> (u64, u64) {
+        let mut matched_weight = 0;
+        let mut total_weight = 0;
+        for (stack, weight) in weighted_stacks(profile, start, end) {
+            total_weight += weight;
+            if self.matches_stack(stack) {
+                matched_weight += weight;
+            }
+        }
+        (matched_weight, total_weight)
+    }
+}
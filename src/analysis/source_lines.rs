@@ -0,0 +1,72 @@
+use super::aggregated_tree::weighted_stacks;
+use super::stack_transforms::Function;
+use crate::wtf_profile::{Profile, StringId, EMPTY_STRING};
+use std::cmp::Reverse;
+use std::collections::HashMap;
+
+#[derive(Clone, Copy, Default)]
+pub struct LineWeight {
+    // Weight of samples taken on the line itself
+    pub self_weight: u64,
+    // Weight of samples on the line or in anything it called
+    pub total_weight: u64,
+}
+
+// The samples of a function attributed to the lines of one of its source files
+pub struct SourceFile {
+    pub file: StringId,
+    pub lines: HashMap<u32, LineWeight>,
+    pub total_weight: u64,
+}
+
+// Where a function's samples with start <= time <= end were in its source files, heaviest file first.
+// Time in functions inlined into it is attributed to the lines they were inlined at, as those are
+// the locations the symbolicator gives the function's own frames.
+// Samples are counted at most once per line, so recursive calls don't inflate any totals.
+pub fn function_source_lines(
+    profile: &Profile,
+    function: Function,
+    start: u64,
+    end: u64,
+) -> Vec<SourceFile> {
+    let mut files: HashMap<StringId, SourceFile> = HashMap::new();
+    let mut sample_lines = Vec::new();
+    for (stack, weight) in weighted_stacks(profile, start, end) {
+        // Innermost frame first
+        sample_lines.clear();
+        for (i, frame) in profile.stack_frames(stack).enumerate() {
+            let frame = frame as usize;
+            let file = profile.frames.file[frame];
+            let frame_function = (profile.frames.function[frame], profile.frames.module[frame]);
+            if frame_function != function || file == EMPTY_STRING {
+                continue;
+            }
+
+            let line = (file, profile.frames.line[frame]);
+            if !sample_lines.iter().any(|&(seen, _)| seen == line) {
+                sample_lines.push((line, i == 0));
+            }
+        }
+
+        for (i, &((file, line), is_self)) in sample_lines.iter().enumerate() {
+            let source_file = files.entry(file).or_insert_with(|| SourceFile {
+                file,
+                lines: HashMap::new(),
+                total_weight: 0,
+            });
+            if !sample_lines[..i].iter().any(|&((seen, _), _)| seen == file) {
+                source_file.total_weight += weight;
+            }
+
+            let line_weight = source_file.lines.entry(line).or_default();
+            line_weight.total_weight += weight;
+            if is_self {
+                line_weight.self_weight += weight;
+            }
+        }
+    }
+
+    let mut files = files.into_values().collect::<Vec<_>>();
+    files.sort_by_key(|file| Reverse(file.total_weight));
+    files
+}
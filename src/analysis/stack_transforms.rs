@@ -0,0 +1,223 @@
+use super::categories::{crate_of, module_file_name};
+use crate::wtf_profile::{
+    Frame, Profile, ProfileBuilder, Sample, StackId, StringId, EMPTY_STRING, NO_STACK,
+};
+
+// (function, module)
+pub type Function = (StringId, StringId);
+
+// A change to the stacks of every sample, applied before any aggregation
+#[derive(Clone, PartialEq, Eq)]
+pub enum StackTransform {
+    // Only keep samples passing through the function, starting their stacks at its outermost call
+    FocusFunction(Function),
+    // Only keep samples passing through this path of functions (outermost first),
+    // starting their stacks at its last function
+    FocusSubtree(Vec<Function>),
+    // Remove the function's frames, so that its self time is charged to its callers
+    MergeFunction(Function),
+    // Remove every sample with the function on its stack
+    DropFunction(Function),
+    // Remove every frame between the function's outermost and innermost calls
+    CollapseRecursion(Function),
+    // Remove the function's frames when it directly calls itself
+    CollapseDirectRecursion(Function),
+    // Replace consecutive frames in the module (e.g. a shared library) with one frame named after it
+    CollapseModule(StringId),
+    // Replace consecutive frames of functions in the crate with one frame named after it
+    CollapseCrate(String),
+}
+
+impl StackTransform {
+    pub fn description(&self, profile: &Profile) -> String {
+        let name = |(function, _): &Function| profile.strings.get(*function).to_owned();
+        match self {
+            StackTransform::FocusFunction(function) => format!("Focus: {}", name(function)),
+            StackTransform::FocusSubtree(path) => {
+                format!(
+                    "Focus subtree: {}",
+                    path.last().map(name).unwrap_or_default()
+                )
+            }
+            StackTransform::MergeFunction(function) => format!("Merge: {}", name(function)),
+            StackTransform::DropFunction(function) => format!("Drop: {}", name(function)),
+            StackTransform::CollapseRecursion(function) => {
+                format!("Collapse recursion: {}", name(function))
+            }
+            StackTransform::CollapseDirectRecursion(function) => {
+                format!("Collapse direct recursion: {}", name(function))
+            }
+            StackTransform::CollapseModule(module) => {
+                format!(
+                    "Collapse: {}",
+                    module_file_name(profile.strings.get(*module))
+                )
+            }
+            StackTransform::CollapseCrate(crate_name) => format!("Collapse: {crate_name}"),
+        }
+    }
+
+    // Transform a stack's frames, ordered from the outermost frame, or return false to drop it
+    fn apply(
+        &self,
+        profile: &Profile,
+        frames: &mut Vec<Frame>,
+        builder: &mut ProfileBuilder,
+    ) -> bool {
+        let is = |frame: &Frame, (function, module): &Function| {
+            frame.function == *function && frame.module == *module
+        };
+        match self {
+            StackTransform::FocusFunction(function) => {
+                match frames.iter().position(|frame| is(frame, function)) {
+                    Some(outermost) => {
+                        frames.drain(..outermost);
+                        true
+                    }
+                    None => false,
+                }
+            }
+            StackTransform::FocusSubtree(path) => {
+                let on_path = path.len() <= frames.len()
+                    && frames
+                        .iter()
+                        .zip(path)
+                        .all(|(frame, function)| is(frame, function));
+                if on_path {
+                    frames.drain(..path.len().saturating_sub(1));
+                }
+                on_path
+            }
+            StackTransform::MergeFunction(function) => {
+                frames.retain(|frame| !is(frame, function));
+                true
+            }
+            StackTransform::DropFunction(function) => {
+                !frames.iter().any(|frame| is(frame, function))
+            }
+            StackTransform::CollapseRecursion(function) => {
+                let outermost = frames.iter().position(|frame| is(frame, function));
+                let innermost = frames.iter().rposition(|frame| is(frame, function));
+                if let (Some(outermost), Some(innermost)) = (outermost, innermost) {
+                    frames.drain(outermost..innermost);
+                }
+                true
+            }
+            StackTransform::CollapseDirectRecursion(function) => {
+                frames.dedup_by(|frame, caller| is(frame, function) && is(caller, function));
+                true
+            }
+            StackTransform::CollapseModule(module) => {
+                let module_function =
+                    builder.intern_string(module_file_name(profile.strings.get(*module)));
+                collapse_runs(frames, |frame| {
+                    (frame.module == *module).then_some((module_function, *module))
+                });
+                true
+            }
+            StackTransform::CollapseCrate(crate_name) => {
+                let crate_function = builder.intern_string(crate_name);
+                collapse_runs(frames, |frame| {
+                    // Functions named by earlier transforms aren't in the original profile
+                    let in_crate = (frame.function as usize) < profile.strings.len()
+                        && crate_of(profile.strings.get(frame.function)) == Some(crate_name);
+                    in_crate.then_some((crate_function, frame.module))
+                });
+                true
+            }
+        }
+    }
+}
+
+// Apply transforms in order to every sample's stack, producing a new profile
+// Strings, frames, and stacks of the original profile keep their ids in the new profile, so that
+// transforms referring to functions of a transformed profile can be applied to the original one
+pub fn apply_stack_transforms(profile: &Profile, transforms: &[StackTransform]) -> Profile {
+    let mut builder = ProfileBuilder::from_tables(profile);
+    for thread in 0..profile.threads.len() {
+        builder.set_thread(
+            profile.threads.tid[thread],
+            profile.threads.pid[thread],
+            profile.threads.name[thread],
+        );
+    }
+
+    // Most samples share a few stacks, so each stack is only transformed once
+    let mut transformed_stacks: Vec<Option<Option<StackId>>> = vec![None; profile.stacks.len()];
+    let mut frames = Vec::new();
+    for sample in 0..profile.samples.len() {
+        let stack = profile.samples.stack[sample];
+        let memoized = match stack {
+            NO_STACK => None,
+            stack => transformed_stacks[stack as usize],
+        };
+        let transformed_stack = match memoized {
+            Some(transformed_stack) => transformed_stack,
+            None => {
+                frames.clear();
+                frames.extend(profile.stack_frames(stack).map(|frame| {
+                    let frame = frame as usize;
+                    Frame {
+                        address: profile.frames.address[frame],
+                        function: profile.frames.function[frame],
+                        module: profile.frames.module[frame],
+                        file: profile.frames.file[frame],
+                        line: profile.frames.line[frame],
+                    }
+                }));
+                frames.reverse();
+
+                let kept = transforms
+                    .iter()
+                    .all(|transform| transform.apply(profile, &mut frames, &mut builder));
+                let transformed_stack = kept.then(|| {
+                    frames.iter().fold(NO_STACK, |parent, &frame| {
+                        let frame = builder.intern_frame(frame);
+                        builder.intern_stack(parent, frame)
+                    })
+                });
+                if stack != NO_STACK {
+                    transformed_stacks[stack as usize] = Some(transformed_stack);
+                }
+                transformed_stack
+            }
+        };
+
+        if let Some(stack) = transformed_stack {
+            builder.add_sample(Sample {
+                time: profile.samples.time[sample],
+                tid: profile.samples.tid[sample],
+                cpu: profile.samples.cpu[sample],
+                stack,
+                weight: profile.samples.weight[sample],
+            });
+        }
+    }
+    builder.finish()
+}
+
+// Replace each run of consecutive frames mapping to the same (function, module) with one frame
+fn collapse_runs<F: Fn(&Frame) -> Option<Function>>(frames: &mut Vec<Frame>, collapsed: F) {
+    let mut collapsed_frames: Vec<Frame> = Vec::with_capacity(frames.len());
+    let mut last_collapsed = None;
+    for frame in frames.drain(..) {
+        match collapsed(&frame) {
+            Some(function) if last_collapsed == Some(function) => {}
+            Some((function, module)) => {
+                collapsed_frames.push(Frame {
+                    address: 0,
+                    function,
+                    module,
+                    file: EMPTY_STRING,
+                    line: 0,
+                });
+                last_collapsed = Some((function, module));
+            }
+            None => {
+                collapsed_frames.push(frame);
+                last_collapsed = None;
+            }
+        }
+    }
+    *frames = collapsed_frames;
+}
@@ -0,0 +1,98 @@
+use crate::wtf_profile::{Profile, ProfileBuilder, Sample, StringId, StringTable};
+use std::collections::BTreeSet;
+
+#[derive(Clone)]
+pub struct ThreadSummary {
+    pub tid: u32,
+    pub pid: u32,
+    pub name: StringId,
+    // Total weight of the thread's samples
+    pub weight: u64,
+    // Time of the thread's first sample, or u64::MAX if it has none
+    pub start_time: u64,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ThreadOrder {
+    // Heaviest first
+    CpuTime,
+    // Earliest first
+    StartTime,
+    Name,
+    Tid,
+}
+
+impl ThreadOrder {
+    // In the order they're listed in the UI
+    pub const ALL: [ThreadOrder; 4] = [
+        ThreadOrder::CpuTime,
+        ThreadOrder::StartTime,
+        ThreadOrder::Name,
+        ThreadOrder::Tid,
+    ];
+}
+
+// Every thread in the profile, in tid order
+pub fn thread_summaries(profile: &Profile) -> Vec<ThreadSummary> {
+    (0..profile.threads.len())
+        .map(|thread| {
+            let tid = profile.threads.tid[thread];
+            let samples = profile.thread_samples_in_range(tid, 0, u64::MAX);
+            ThreadSummary {
+                tid,
+                pid: profile.threads.pid[thread],
+                name: profile.threads.name[thread],
+                weight: profile.samples.weight[samples.clone()]
+                    .iter()
+                    .map(|&weight| weight as u64)
+                    .sum(),
+                start_time: if samples.is_empty() {
+                    u64::MAX
+                } else {
+                    profile.samples.time[samples.start]
+                },
+            }
+        })
+        .collect()
+}
+
+// Ties are broken by tid, so the order is the same every time
+pub fn sort_threads(threads: &mut [ThreadSummary], order: ThreadOrder, strings: &StringTable) {
+    match order {
+        ThreadOrder::CpuTime => {
+            threads.sort_by_key(|thread| (std::cmp::Reverse(thread.weight), thread.tid))
+        }
+        ThreadOrder::StartTime => threads.sort_by_key(|thread| (thread.start_time, thread.tid)),
+        ThreadOrder::Name => {
+            threads.sort_by(|a, b| (strings.get(a.name), a.tid).cmp(&(strings.get(b.name), b.tid)))
+        }
+        ThreadOrder::Tid => threads.sort_by_key(|thread| thread.tid),
+    }
+}
+
+// A copy of the profile with only the samples of some threads
+// Strings, frames, and stacks keep their ids, so anything found in the full profile still applies
+pub fn filter_threads(profile: &Profile, tids: &BTreeSet<u32>) -> Profile {
+    let mut builder = ProfileBuilder::from_tables(profile);
+    for thread in 0..profile.threads.len() {
+        let tid = profile.threads.tid[thread];
+        if !tids.contains(&tid) {
+            continue;
+        }
+        builder.set_thread(
+            tid,
+            profile.threads.pid[thread],
+            profile.threads.name[thread],
+        );
+        for sample in profile.thread_samples_in_range(tid, 0, u64::MAX) {
+            builder.add_sample(Sample {
+                time: profile.samples.time[sample],
+                tid,
+                cpu: profile.samples.cpu[sample],
+                stack: profile.samples.stack[sample],
+                weight: profile.samples.weight[sample],
+            });
+        }
+    }
+    builder.finish()
+}
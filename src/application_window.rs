@@ -3,13 +3,17 @@ use crate::profile_page_view::{
 };
 use adw::subclass::prelude::AdwApplicationWindowImpl;
 use adw::{TabBar, TabPage, TabView, WindowTitle};
-use gio::{ActionGroup, ActionMap};
+use gio::traits::FileExt;
+use gio::{ActionGroup, ActionMap, File};
 use glib::subclass::prelude::{
     DerivedObjectProperties, ObjectImpl, ObjectImplExt, ObjectSubclass, ObjectSubclassExt,
     ObjectSubclassIsExt,
 };
 use glib::subclass::InitializingObject;
-use glib::{clone, object_subclass, IsA, Object, ObjectExt, ParamSpec, Properties, Value};
+use glib::{
+    clone, object_subclass, Cast, IsA, Object, ObjectExt, ParamSpec, Properties, StaticType, Value,
+};
+use gtk::gdk::DragAction;
 use gtk::prelude::{GObjectPropertyExpressionExt, InitializingWidgetExt};
 use gtk::subclass::prelude::{
     ApplicationWindowImpl, CompositeTemplateCallbacksClass, CompositeTemplateClass, TemplateChild,
@@ -48,6 +52,8 @@ pub struct ApplicationWindowPrivate {
     #[template_child]
     title: TemplateChild<WindowTitle>,
     #[template_child]
+    tab_bar: TemplateChild<TabBar>,
+    #[template_child]
     tab_view: TemplateChild<TabView>,
     #[template_child]
     new_tab_button: TemplateChild<Button>,
@@ -119,7 +125,7 @@ fn set_tab_properties(tab: &TabPage, page_view: &ProfilePageView) {
         }
         (ProfilePageViewState::Profile, Some(profile_name)) => {
             tab.set_title(&profile_name);
-            tab.set_loading(true);
+            tab.set_loading(false);
             tab.set_needs_attention(!tab.is_selected());
         }
         _ => unreachable!(),
@@ -153,6 +159,17 @@ impl ObjectImpl for ApplicationWindowPrivate {
             .chain_property::<TabPage>("title")
             .bind(&self.title.get(), "subtitle", Widget::NONE);
 
+        // Dropping a profile onto a tab opens it there, or compares it with the tab's profile
+        self.tab_bar
+            .setup_extra_drop_target(DragAction::COPY, &[File::static_type()]);
+        self.tab_bar.connect_extra_drag_drop(|_, tab, value| {
+            let page_view = tab.child().downcast::<ProfilePageView>().unwrap();
+            match value.get::<File>().ok().and_then(|file| file.path()) {
+                Some(profile_path) => page_view.open_dropped_profile(&profile_path),
+                None => false,
+            }
+        });
+
         if this.create_initial_tab() {
             self.add_new_tab();
         }
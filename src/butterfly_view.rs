@@ -0,0 +1,248 @@
+use crate::analysis::Butterfly;
+use crate::timeline_view::TimelineView;
+use crate::wtf_profile::{Profile, StringId};
+use adw::subclass::prelude::BinImpl;
+use adw::traits::BinExt;
+use adw::Bin;
+use glib::once_cell::sync::OnceCell;
+use glib::subclass::prelude::{
+    DerivedObjectProperties, ObjectImpl, ObjectImplExt, ObjectSubclass, ObjectSubclassExt,
+    ObjectSubclassIsExt,
+};
+use glib::{
+    clone, idle_add_local_once, object_subclass, Object, ObjectExt, ParamSpec, Properties,
+    StaticType, Value,
+};
+use gtk::pango::EllipsizeMode;
+use gtk::subclass::prelude::{WidgetClassSubclassExt, WidgetImpl};
+use gtk::traits::{BoxExt, ListBoxRowExt, WidgetExt};
+use gtk::{
+    Accessible, Buildable, ConstraintTarget, Label, ListBox, Orientation, ScrolledWindow,
+    SelectionMode, Widget,
+};
+use std::cell::{Cell, RefCell};
+
+glib::wrapper! {
+    pub struct ButterflyView(ObjectSubclass<ButterflyViewPrivate>)
+    @extends Bin, Widget,
+    @implements Accessible, Buildable, ConstraintTarget;
+}
+
+impl ButterflyView {
+    pub fn new() -> Self {
+        Object::new(&[]).unwrap()
+    }
+
+    pub fn show_function(&self, function: (StringId, StringId)) {
+        self.imp().function.set(Some(function));
+        self.queue_rebuild();
+    }
+
+    // Selection changes arrive on every drag update, so coalesce them into one rebuild
+    fn queue_rebuild(&self) {
+        if !self.imp().rebuild_queued.replace(true) {
+            idle_add_local_once(clone!(@weak self as this => move || {
+                this.imp().rebuild_queued.set(false);
+                this.rebuild();
+            }));
+        }
+    }
+
+    fn rebuild(&self) {
+        let imp = self.imp();
+        let widgets = imp.widgets.get().unwrap();
+        for list in [&widgets.callers, &widgets.callees] {
+            while let Some(row) = list.first_child() {
+                list.remove(&row);
+            }
+        }
+        imp.callers.borrow_mut().clear();
+        imp.callees.borrow_mut().clear();
+
+        let butterfly = self.timeline().and_then(|timeline| {
+            let function = imp.function.get()?;
+            let (start, end) = timeline.analysis_time_range()?;
+            let profile = timeline.analysis_profile()?;
+            Some((Butterfly::new(&profile, start, end, function), profile))
+        });
+        let (butterfly, profile) = match butterfly {
+            Some(butterfly) => butterfly,
+            None => {
+                widgets
+                    .title
+                    .set_label("Select a function to see its callers and callees");
+                widgets.summary.set_label("");
+                return;
+            }
+        };
+
+        let percent = |weight: u64| weight as f64 * 100.0 / butterfly.profile_weight.max(1) as f64;
+        widgets
+            .title
+            .set_label(profile.strings.get(butterfly.function.0));
+        widgets.summary.set_label(&format!(
+            "{:.1}% total, {:.1}% self, {} samples",
+            percent(butterfly.total_weight),
+            percent(butterfly.self_weight),
+            butterfly.total_weight,
+        ));
+
+        for (list, functions, calls) in [
+            (&widgets.callers, &imp.callers, &butterfly.callers),
+            (&widgets.callees, &imp.callees, &butterfly.callees),
+        ] {
+            for &(function, weight) in calls {
+                list.append(&new_row(&profile, function.0, percent(weight)));
+                functions.borrow_mut().push(function);
+            }
+        }
+    }
+}
+
+fn new_row(profile: &Profile, function: StringId, percent: f64) -> gtk::Box {
+    let name = Label::new(Some(profile.strings.get(function)));
+    name.set_xalign(0.0);
+    name.set_hexpand(true);
+    name.set_ellipsize(EllipsizeMode::End);
+    let percent = Label::new(Some(&format!("{percent:.1}%")));
+    percent.add_css_class("numeric");
+
+    let row = gtk::Box::new(Orientation::Horizontal, 12);
+    row.append(&name);
+    row.append(&percent);
+    row
+}
+
+fn new_list(title: &str) -> (gtk::Box, ListBox) {
+    let list = ListBox::new();
+    list.set_selection_mode(SelectionMode::None);
+    list.add_css_class("boxed-list");
+
+    let scrolled_window = ScrolledWindow::new();
+    scrolled_window.set_child(Some(&list));
+    scrolled_window.set_vexpand(true);
+
+    let title = Label::new(Some(title));
+    title.set_xalign(0.0);
+    title.add_css_class("heading");
+
+    let column = gtk::Box::new(Orientation::Vertical, 6);
+    column.append(&title);
+    column.append(&scrolled_window);
+    (column, list)
+}
+
+#[derive(Debug)]
+struct ButterflyWidgets {
+    title: Label,
+    summary: Label,
+    callers: ListBox,
+    callees: ListBox,
+}
+
+// ------------------------------------------------------------------------------
+
+#[derive(Properties, Default)]
+pub struct ButterflyViewPrivate {
+    #[property(get, set = Self::set_timeline, builder(TimelineView::static_type()))]
+    timeline: RefCell<Option<TimelineView>>,
+
+    // (function, module)
+    function: Cell<Option<(StringId, StringId)>>,
+    // The functions listed in each list, by row index
+    callers: RefCell<Vec<(StringId, StringId)>>,
+    callees: RefCell<Vec<(StringId, StringId)>>,
+    widgets: OnceCell<ButterflyWidgets>,
+    rebuild_queued: Cell<bool>,
+}
+
+impl ButterflyViewPrivate {
+    pub fn set_timeline(&self, value: Option<TimelineView>) {
+        let this = self.instance();
+        if let Some(timeline) = &value {
+            for property in [
+                "profile",
+                "selected-time-range",
+                "transform-count",
+                "selected-thread-count",
+            ] {
+                timeline.connect_notify_local(
+                    Some(property),
+                    clone!(@weak this => move |_, _| this.queue_rebuild()),
+                );
+            }
+        }
+
+        *self.timeline.borrow_mut() = value;
+        this.queue_rebuild();
+    }
+}
+
+#[object_subclass]
+impl ObjectSubclass for ButterflyViewPrivate {
+    const NAME: &'static str = "WtfButterflyView";
+    type Type = ButterflyView;
+    type ParentType = Bin;
+
+    fn class_init(klass: &mut Self::Class) {
+        klass.set_css_name("butterfly-view");
+    }
+}
+
+impl ObjectImpl for ButterflyViewPrivate {
+    fn constructed(&self, this: &Self::Type) {
+        self.parent_constructed(this);
+
+        let title = Label::new(None);
+        title.set_xalign(0.0);
+        title.set_ellipsize(EllipsizeMode::End);
+        title.add_css_class("title-4");
+        let summary = Label::new(None);
+        summary.set_xalign(0.0);
+        summary.add_css_class("dim-label");
+
+        // Activating a caller or callee moves the view to it
+        let (callers_column, callers) = new_list("Callers");
+        let (callees_column, callees) = new_list("Callees");
+        callers.connect_row_activated(clone!(@weak this => move |_, row| {
+            let caller = this.imp().callers.borrow()[row.index() as usize];
+            this.show_function(caller);
+        }));
+        callees.connect_row_activated(clone!(@weak this => move |_, row| {
+            let callee = this.imp().callees.borrow()[row.index() as usize];
+            this.show_function(callee);
+        }));
+
+        let lists = gtk::Box::new(Orientation::Horizontal, 12);
+        lists.set_homogeneous(true);
+        lists.append(&callers_column);
+        lists.append(&callees_column);
+
+        let content = gtk::Box::new(Orientation::Vertical, 6);
+        content.append(&title);
+        content.append(&summary);
+        content.append(&lists);
+        this.set_child(Some(&content));
+
+        let widgets = ButterflyWidgets {
+            title,
+            summary,
+            callers,
+            callees,
+        };
+        self.widgets.set(widgets).unwrap();
+    }
+
+    fn properties() -> &'static [ParamSpec] {
+        Self::derived_properties()
+    }
+    fn set_property(&self, this: &Self::Type, id: usize, value: &Value, pspec: &ParamSpec) {
+        Self::derived_set_property(self, this, id, value, pspec).unwrap();
+    }
+    fn property(&self, this: &Self::Type, id: usize, pspec: &ParamSpec) -> Value {
+        Self::derived_property(self, this, id, pspec).unwrap()
+    }
+}
+
+impl WidgetImpl for ButterflyViewPrivate {}
+impl BinImpl for ButterflyViewPrivate {}
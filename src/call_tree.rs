@@ -0,0 +1,459 @@
+use crate::analysis::{AggregatedTree, Category, Function, NodeId, SearchMatches, ROOT_NODE};
+use crate::butterfly_view::{ButterflyView, ButterflyViewPrivatePropertiesExt};
+use crate::disassembly_view::{DisassemblyView, DisassemblyViewPrivatePropertiesExt};
+use crate::source_view::{SourceView, SourceViewPrivatePropertiesExt};
+use crate::timeline_view::TimelineView;
+use crate::transform_menu::popup_transform_menu;
+use crate::wtf_profile::Profile;
+use adw::subclass::prelude::BinImpl;
+use adw::traits::BinExt;
+use adw::Bin;
+use gio::traits::ListModelExt;
+use gio::{ListModel, ListStore};
+use glib::once_cell::sync::OnceCell;
+use glib::subclass::prelude::{
+    DerivedObjectProperties, ObjectImpl, ObjectImplExt, ObjectSubclass, ObjectSubclassExt,
+    ObjectSubclassIsExt,
+};
+use glib::{
+    clone, idle_add_local_once, object_subclass, BindingFlags, BoxedAnyObject, Cast, ObjectExt,
+    ParamSpec, Properties, StaticType, Value,
+};
+use gtk::pango::EllipsizeMode;
+use gtk::subclass::prelude::{WidgetClassSubclassExt, WidgetImpl};
+use gtk::traits::{BoxExt, EventControllerExt, GestureSingleExt, WidgetExt};
+use gtk::{
+    Accessible, Align, Buildable, ColumnView, ColumnViewColumn, ConstraintTarget, GestureClick,
+    Label, ListItem, Orientation, Paned, ScrolledWindow, SignalListItemFactory, SingleSelection,
+    Stack, StackSwitcher, ToggleButton, TreeExpander, TreeListModel, TreeListRow, Widget,
+};
+use std::cell::{Cell, RefCell};
+use std::path::Path;
+use std::rc::Rc;
+
+// Rows visited when expanding the tree to show search matches
+const MAX_EXPANDED_ROWS: u32 = 1000;
+
+glib::wrapper! {
+    pub struct CallTree(ObjectSubclass<CallTreePrivate>)
+    @extends Bin, Widget,
+    @implements Accessible, Buildable, ConstraintTarget;
+}
+
+impl CallTree {
+    // Selection changes arrive on every drag update, so coalesce them into one rebuild
+    fn queue_rebuild(&self) {
+        if !self.imp().rebuild_queued.replace(true) {
+            idle_add_local_once(clone!(@weak self as this => move || {
+                this.imp().rebuild_queued.set(false);
+                this.rebuild();
+            }));
+        }
+    }
+
+    // Re-aggregate the timeline's profile, limited to its selection if there is one
+    fn rebuild(&self) {
+        let column_view = self.imp().column_view.get().unwrap();
+        let profile = self.timeline().and_then(|timeline| {
+            let (start, end) = timeline.analysis_time_range()?;
+            Some((timeline.analysis_profile()?, start, end))
+        });
+        let (profile, start, end) = match profile {
+            Some(profile) => profile,
+            None => {
+                column_view.set_model(None::<&SingleSelection>);
+                return;
+            }
+        };
+
+        let tree = if self.inverted() {
+            AggregatedTree::bottom_up(&profile, start, end)
+        } else {
+            AggregatedTree::top_down(&profile, start, end)
+        };
+        let search = self
+            .timeline()
+            .and_then(|timeline| timeline.search_matches());
+        let root = CallTreeItem {
+            tree: Rc::new(tree),
+            profile,
+            node: ROOT_NODE,
+            inverted: self.inverted(),
+            search: search.clone(),
+        };
+        let root_model = root
+            .children_model()
+            .unwrap_or_else(|| ListStore::new(BoxedAnyObject::static_type()).upcast());
+
+        // Child models are only created once a row is expanded
+        let tree_model = TreeListModel::new(&root_model, false, false, |item| {
+            let item = item.downcast_ref::<BoxedAnyObject>().unwrap();
+            let item = item.borrow::<CallTreeItem>();
+            item.children_model()
+        });
+        if let Some(search) = &search {
+            expand_to_matches(&tree_model, &root.tree, search);
+        }
+
+        let selection = SingleSelection::new(Some(&tree_model));
+        selection.connect_selected_item_notify(clone!(@weak self as this => move |selection| {
+            this.show_selection_in_details(selection);
+        }));
+        self.show_selection_in_details(&selection);
+        column_view.set_model(Some(&selection));
+    }
+
+    fn show_selection_in_details(&self, selection: &SingleSelection) {
+        let item = selection
+            .selected_item()
+            .and_then(|row| row.downcast::<TreeListRow>().unwrap().item());
+        if let Some(item) = item {
+            let item = item.downcast::<BoxedAnyObject>().unwrap();
+            let item = item.borrow::<CallTreeItem>();
+            let imp = self.imp();
+            imp.butterfly_view
+                .get()
+                .unwrap()
+                .show_function(item.function());
+            imp.source_view
+                .get()
+                .unwrap()
+                .show_function(item.function());
+            imp.disassembly_view
+                .get()
+                .unwrap()
+                .show_function(item.function());
+        }
+    }
+}
+
+// Expand every row with a function matching the search below it, within reason
+fn expand_to_matches(tree_model: &TreeListModel, tree: &AggregatedTree, search: &SearchMatches) {
+    // Children are always created after their parents, so one reverse pass covers every node
+    let mut leads_to_match = vec![false; tree.nodes.len()];
+    for node in (1..tree.nodes.len()).rev() {
+        let tree_node = &tree.nodes[node];
+        if leads_to_match[node] || search.matches_function((tree_node.function, tree_node.module)) {
+            leads_to_match[tree_node.parent as usize] = true;
+        }
+    }
+
+    let mut row_index = 0;
+    while row_index < tree_model.n_items().min(MAX_EXPANDED_ROWS) {
+        let row = tree_model.row(row_index).unwrap();
+        let item = row.item().unwrap().downcast::<BoxedAnyObject>().unwrap();
+        if leads_to_match[item.borrow::<CallTreeItem>().node as usize] {
+            row.set_expanded(true);
+        }
+        row_index += 1;
+    }
+}
+
+// A node of an AggregatedTree, as displayed by a row
+struct CallTreeItem {
+    profile: Rc<Profile>,
+    tree: Rc<AggregatedTree>,
+    node: NodeId,
+    inverted: bool,
+    search: Option<Rc<SearchMatches>>,
+}
+
+impl CallTreeItem {
+    fn children_model(&self) -> Option<ListModel> {
+        let children = &self.tree.node(self.node).children;
+        if children.is_empty() {
+            return None;
+        }
+
+        let model = ListStore::new(BoxedAnyObject::static_type());
+        for &child in children {
+            model.append(&BoxedAnyObject::new(CallTreeItem {
+                profile: self.profile.clone(),
+                tree: self.tree.clone(),
+                node: child,
+                inverted: self.inverted,
+                search: self.search.clone(),
+            }));
+        }
+        Some(model.upcast())
+    }
+
+    fn function(&self) -> Function {
+        let node = self.tree.node(self.node);
+        (node.function, node.module)
+    }
+
+    // Functions from the outermost caller to this node, only known in top-down trees
+    fn path(&self) -> Option<Vec<Function>> {
+        if self.inverted {
+            return None;
+        }
+        let mut path = Vec::new();
+        let mut node = self.node;
+        while node != ROOT_NODE {
+            let tree_node = self.tree.node(node);
+            path.push((tree_node.function, tree_node.module));
+            node = tree_node.parent;
+        }
+        path.reverse();
+        Some(path)
+    }
+
+    fn matches_search(&self) -> bool {
+        match &self.search {
+            Some(search) => search.matches_function(self.function()),
+            None => false,
+        }
+    }
+
+    fn function_name(&self) -> &str {
+        self.profile.strings.get(self.tree.node(self.node).function)
+    }
+
+    fn category_name(&self) -> String {
+        let node = self.tree.node(self.node);
+        let function = self.profile.strings.get(node.function);
+        let module = self.profile.strings.get(node.module);
+        Category::of(function, module).name().to_owned()
+    }
+
+    fn module_name(&self) -> String {
+        let module = self.profile.strings.get(self.tree.node(self.node).module);
+        Path::new(module)
+            .file_name()
+            .map(|name| name.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    }
+
+    fn percent_of_total(&self, weight: u64) -> String {
+        let total_weight = self.tree.total_weight().max(1);
+        format!("{:.1}%", (weight as f64 * 100.0) / total_weight as f64)
+    }
+}
+
+fn list_item_row_item(list_item: &ListItem) -> BoxedAnyObject {
+    let row = list_item.item().unwrap().downcast::<TreeListRow>().unwrap();
+    row.item().unwrap().downcast::<BoxedAnyObject>().unwrap()
+}
+
+fn new_function_column() -> ColumnViewColumn {
+    let factory = SignalListItemFactory::new();
+    factory.connect_setup(|_, list_item| {
+        let label = Label::new(None);
+        label.set_xalign(0.0);
+        label.set_ellipsize(EllipsizeMode::End);
+        let expander = TreeExpander::new();
+        expander.set_child(Some(&label));
+        list_item.set_child(Some(&expander));
+
+        // Right clicking a function offers transforms of every stack it's in
+        let menu_controller = GestureClick::new();
+        menu_controller.set_button(3);
+        menu_controller.connect_pressed(clone!(@weak list_item => move |gesture, _, x, y| {
+            if list_item.item().is_none() {
+                return;
+            }
+            let item = list_item_row_item(&list_item);
+            let item = item.borrow::<CallTreeItem>();
+            popup_transform_menu(
+                &gesture.widget(),
+                x,
+                y,
+                &item.profile,
+                item.function(),
+                item.path(),
+            );
+        }));
+        expander.add_controller(&menu_controller);
+    });
+    factory.connect_bind(|_, list_item| {
+        let expander = list_item
+            .child()
+            .unwrap()
+            .downcast::<TreeExpander>()
+            .unwrap();
+        let label = expander.child().unwrap().downcast::<Label>().unwrap();
+        let row = list_item.item().unwrap().downcast::<TreeListRow>().unwrap();
+        expander.set_list_row(Some(&row));
+
+        let item = list_item_row_item(list_item);
+        let item = item.borrow::<CallTreeItem>();
+        label.set_label(item.function_name());
+        if item.matches_search() {
+            label.add_css_class("search-match");
+        } else {
+            label.remove_css_class("search-match");
+        }
+    });
+
+    let column = ColumnViewColumn::new(Some("Function"), Some(&factory));
+    column.set_expand(true);
+    column.set_resizable(true);
+    column
+}
+
+fn new_text_column(title: &str, text: fn(&CallTreeItem) -> String) -> ColumnViewColumn {
+    let factory = SignalListItemFactory::new();
+    factory.connect_setup(|_, list_item| {
+        let label = Label::new(None);
+        label.set_xalign(1.0);
+        label.add_css_class("numeric");
+        list_item.set_child(Some(&label));
+    });
+    factory.connect_bind(move |_, list_item| {
+        let label = list_item.child().unwrap().downcast::<Label>().unwrap();
+        let item = list_item_row_item(list_item);
+        label.set_label(&text(&item.borrow::<CallTreeItem>()));
+    });
+
+    let column = ColumnViewColumn::new(Some(title), Some(&factory));
+    column.set_resizable(true);
+    column
+}
+
+// ------------------------------------------------------------------------------
+
+#[derive(Properties, Default)]
+pub struct CallTreePrivate {
+    #[property(get, set = Self::set_timeline, builder(TimelineView::static_type()))]
+    timeline: RefCell<Option<TimelineView>>,
+
+    // Whether the tree starts from the innermost frames (bottom-up) instead of the outermost (top-down)
+    #[property(get, set = Self::set_inverted)]
+    inverted: Cell<bool>,
+
+    column_view: OnceCell<ColumnView>,
+    butterfly_view: OnceCell<ButterflyView>,
+    source_view: OnceCell<SourceView>,
+    disassembly_view: OnceCell<DisassemblyView>,
+    rebuild_queued: Cell<bool>,
+}
+
+impl CallTreePrivate {
+    pub fn set_timeline(&self, value: Option<TimelineView>) {
+        let this = self.instance();
+        if let Some(timeline) = &value {
+            let properties = [
+                "profile",
+                "selected-time-range",
+                "transform-count",
+                "selected-thread-count",
+                "search-query",
+            ];
+            for property in properties {
+                timeline.connect_notify_local(
+                    Some(property),
+                    clone!(@weak this => move |_, _| this.queue_rebuild()),
+                );
+            }
+        }
+
+        if let Some(butterfly_view) = self.butterfly_view.get() {
+            butterfly_view.set_timeline(value.clone());
+        }
+        if let Some(source_view) = self.source_view.get() {
+            source_view.set_timeline(value.clone());
+        }
+        if let Some(disassembly_view) = self.disassembly_view.get() {
+            disassembly_view.set_timeline(value.clone());
+        }
+        *self.timeline.borrow_mut() = value;
+        this.queue_rebuild();
+    }
+
+    pub fn set_inverted(&self, value: bool) {
+        self.inverted.set(value);
+        self.instance().queue_rebuild();
+    }
+}
+
+#[object_subclass]
+impl ObjectSubclass for CallTreePrivate {
+    const NAME: &'static str = "WtfCallTree";
+    type Type = CallTree;
+    type ParentType = Bin;
+
+    fn class_init(klass: &mut Self::Class) {
+        klass.set_css_name("call-tree");
+    }
+}
+
+impl ObjectImpl for CallTreePrivate {
+    fn constructed(&self, this: &Self::Type) {
+        self.parent_constructed(this);
+
+        let column_view = ColumnView::new(None::<&SingleSelection>);
+        column_view.add_css_class("data-table");
+        column_view.append_column(&new_function_column());
+        column_view.append_column(&new_text_column("Self", |item| {
+            item.percent_of_total(item.tree.node(item.node).self_weight)
+        }));
+        column_view.append_column(&new_text_column("Total", |item| {
+            item.percent_of_total(item.tree.node(item.node).total_weight)
+        }));
+        column_view.append_column(&new_text_column("Samples", |item| {
+            item.tree.node(item.node).total_weight.to_string()
+        }));
+        column_view.append_column(&new_text_column("Crate", CallTreeItem::category_name));
+        column_view.append_column(&new_text_column("Module", CallTreeItem::module_name));
+
+        let scrolled_window = ScrolledWindow::new();
+        scrolled_window.set_child(Some(&column_view));
+        scrolled_window.set_vexpand(true);
+
+        let inverted_toggle = ToggleButton::with_label("Inverted");
+        inverted_toggle.set_halign(Align::Start);
+        inverted_toggle.set_tooltip_text(Some("Start from the functions samples were taken in"));
+        inverted_toggle
+            .bind_property("active", this, "inverted")
+            .flags(BindingFlags::BIDIRECTIONAL | BindingFlags::SYNC_CREATE)
+            .build();
+
+        let tree = gtk::Box::new(Orientation::Vertical, 6);
+        tree.append(&inverted_toggle);
+        tree.append(&scrolled_window);
+
+        // Shows the callers and callees, source, or disassembly of the selected function
+        let butterfly_view = ButterflyView::new();
+        let source_view = SourceView::new();
+        let disassembly_view = DisassemblyView::new();
+        let details = Stack::new();
+        details.add_titled(&butterfly_view, None, "Callers & Callees");
+        details.add_titled(&source_view, None, "Source");
+        details.add_titled(&disassembly_view, None, "Disassembly");
+        let details_switcher = StackSwitcher::new();
+        details_switcher.set_stack(Some(&details));
+        details_switcher.set_halign(Align::Start);
+
+        let details_box = gtk::Box::new(Orientation::Vertical, 6);
+        details_box.set_margin_top(6);
+        details_box.append(&details_switcher);
+        details_box.append(&details);
+
+        let paned = Paned::new(Orientation::Vertical);
+        paned.set_start_child(Some(&tree));
+        paned.set_end_child(Some(&details_box));
+        this.set_child(Some(&paned));
+
+        self.column_view.set(column_view).unwrap();
+        self.butterfly_view.set(butterfly_view).unwrap();
+        self.source_view.set(source_view).unwrap();
+        self.disassembly_view.set(disassembly_view).unwrap();
+
+        this.set_hexpand(true);
+        this.set_vexpand(true);
+    }
+
+    fn properties() -> &'static [ParamSpec] {
+        Self::derived_properties()
+    }
+    fn set_property(&self, this: &Self::Type, id: usize, value: &Value, pspec: &ParamSpec) {
+        Self::derived_set_property(self, this, id, value, pspec).unwrap();
+    }
+    fn property(&self, this: &Self::Type, id: usize, pspec: &ParamSpec) -> Value {
+        Self::derived_property(self, this, id, pspec).unwrap()
+    }
+}
+
+impl WidgetImpl for CallTreePrivate {}
+impl BinImpl for CallTreePrivate {}
@@ -0,0 +1,192 @@
+use crate::analysis::{time_by_category, Category};
+use crate::flame_graph::category_color;
+use crate::timeline_view::TimelineView;
+use adw::subclass::prelude::BinImpl;
+use adw::traits::BinExt;
+use adw::Bin;
+use glib::once_cell::sync::OnceCell;
+use glib::subclass::prelude::{
+    DerivedObjectProperties, ObjectImpl, ObjectImplExt, ObjectSubclass, ObjectSubclassExt,
+    ObjectSubclassIsExt,
+};
+use glib::{
+    clone, idle_add_local_once, object_subclass, ObjectExt, ParamSpec, Properties, StaticType,
+    Value,
+};
+use gtk::pango::EllipsizeMode;
+use gtk::subclass::prelude::{WidgetClassSubclassExt, WidgetImpl};
+use gtk::traits::{BoxExt, WidgetExt};
+use gtk::{
+    Accessible, Buildable, ConstraintTarget, Label, ListBox, Orientation, ScrolledWindow,
+    SelectionMode, Widget,
+};
+use std::cell::{Cell, RefCell};
+
+glib::wrapper! {
+    pub struct CategorySummary(ObjectSubclass<CategorySummaryPrivate>)
+    @extends Bin, Widget,
+    @implements Accessible, Buildable, ConstraintTarget;
+}
+
+impl CategorySummary {
+    // Selection changes arrive on every drag update, so coalesce them into one rebuild
+    fn queue_rebuild(&self) {
+        if !self.imp().rebuild_queued.replace(true) {
+            idle_add_local_once(clone!(@weak self as this => move || {
+                this.imp().rebuild_queued.set(false);
+                this.rebuild();
+            }));
+        }
+    }
+
+    fn rebuild(&self) {
+        let list = self.imp().list.get().unwrap();
+        while let Some(row) = list.first_child() {
+            list.remove(&row);
+        }
+
+        let timeline = match self.timeline() {
+            Some(timeline) => timeline,
+            None => return,
+        };
+        let (profile, (start, end)) =
+            match (timeline.analysis_profile(), timeline.analysis_time_range()) {
+                (Some(profile), Some(time_range)) => (profile, time_range),
+                _ => return,
+            };
+
+        let times = time_by_category(&profile, start, end);
+        let total_weight = times
+            .iter()
+            .map(|time| time.self_weight)
+            .sum::<u64>()
+            .max(1) as f64;
+        for time in times {
+            list.append(&new_row(
+                time.category,
+                time.self_weight as f64 * 100.0 / total_weight,
+                time.total_weight as f64 * 100.0 / total_weight,
+            ));
+        }
+    }
+}
+
+fn new_row(category: Category, self_percent: f64, total_percent: f64) -> gtk::Box {
+    let color = category_color(category);
+    let swatch = Label::new(None);
+    swatch.set_markup(&format!(
+        "<span foreground=\"#{:02x}{:02x}{:02x}\">●</span>",
+        (color.red() * 255.0) as u8,
+        (color.green() * 255.0) as u8,
+        (color.blue() * 255.0) as u8,
+    ));
+
+    let name = Label::new(Some(category.name()));
+    name.set_xalign(0.0);
+    name.set_hexpand(true);
+    name.set_ellipsize(EllipsizeMode::End);
+
+    let kind = Label::new(Some(match category {
+        Category::Std(_) => "std",
+        Category::Crate(_) => "crate",
+        Category::Module(_) => "module",
+        Category::Unknown => "",
+    }));
+    kind.add_css_class("dim-label");
+
+    let self_percent = Label::new(Some(&format!("{self_percent:.1}% self")));
+    self_percent.add_css_class("numeric");
+    let total_percent = Label::new(Some(&format!("{total_percent:.1}% total")));
+    total_percent.add_css_class("numeric");
+    total_percent.add_css_class("dim-label");
+
+    let row = gtk::Box::new(Orientation::Horizontal, 6);
+    row.append(&swatch);
+    row.append(&name);
+    row.append(&kind);
+    row.append(&self_percent);
+    row.append(&total_percent);
+    row
+}
+
+// ------------------------------------------------------------------------------
+
+#[derive(Properties, Default)]
+pub struct CategorySummaryPrivate {
+    #[property(get, set = Self::set_timeline, builder(TimelineView::static_type()))]
+    timeline: RefCell<Option<TimelineView>>,
+
+    list: OnceCell<ListBox>,
+    rebuild_queued: Cell<bool>,
+}
+
+impl CategorySummaryPrivate {
+    pub fn set_timeline(&self, value: Option<TimelineView>) {
+        let this = self.instance();
+        if let Some(timeline) = &value {
+            for property in [
+                "profile",
+                "selected-time-range",
+                "transform-count",
+                "selected-thread-count",
+            ] {
+                timeline.connect_notify_local(
+                    Some(property),
+                    clone!(@weak this => move |_, _| this.queue_rebuild()),
+                );
+            }
+        }
+
+        *self.timeline.borrow_mut() = value;
+        this.queue_rebuild();
+    }
+}
+
+#[object_subclass]
+impl ObjectSubclass for CategorySummaryPrivate {
+    const NAME: &'static str = "WtfCategorySummary";
+    type Type = CategorySummary;
+    type ParentType = Bin;
+
+    fn class_init(klass: &mut Self::Class) {
+        klass.set_css_name("category-summary");
+    }
+}
+
+impl ObjectImpl for CategorySummaryPrivate {
+    fn constructed(&self, this: &Self::Type) {
+        self.parent_constructed(this);
+
+        let title = Label::new(Some("Time by Crate"));
+        title.set_xalign(0.0);
+        title.add_css_class("heading");
+
+        let list = ListBox::new();
+        list.set_selection_mode(SelectionMode::None);
+        list.add_css_class("boxed-list");
+
+        let scrolled_window = ScrolledWindow::new();
+        scrolled_window.set_child(Some(&list));
+        scrolled_window.set_min_content_height(160);
+
+        let content = gtk::Box::new(Orientation::Vertical, 6);
+        content.append(&title);
+        content.append(&scrolled_window);
+        this.set_child(Some(&content));
+
+        self.list.set(list).unwrap();
+    }
+
+    fn properties() -> &'static [ParamSpec] {
+        Self::derived_properties()
+    }
+    fn set_property(&self, this: &Self::Type, id: usize, value: &Value, pspec: &ParamSpec) {
+        Self::derived_set_property(self, this, id, value, pspec).unwrap();
+    }
+    fn property(&self, this: &Self::Type, id: usize, pspec: &ParamSpec) -> Value {
+        Self::derived_property(self, this, id, pspec).unwrap()
+    }
+}
+
+impl WidgetImpl for CategorySummaryPrivate {}
+impl BinImpl for CategorySummaryPrivate {}
@@ -0,0 +1,136 @@
+use crate::analysis::CpuSamples;
+use crate::timeline_range::TimelineRangePrivatePropertiesExt;
+use crate::timeline_row::append_row_header;
+use crate::timeline_view::{TimelineView, TimelineViewPrivatePropertiesExt};
+use crate::wtf_profile::Profile;
+use glib::once_cell::sync::OnceCell;
+use glib::subclass::prelude::{DerivedObjectProperties, ObjectImpl, ObjectImplExt, ObjectSubclass};
+use glib::{
+    object_subclass, BoxedAnyObject, Cast, Object, ObjectExt, ParamSpec, Properties, StaticType,
+    Value,
+};
+use gtk::gdk::RGBA;
+use gtk::graphene::Rect;
+use gtk::subclass::prelude::{WidgetClassSubclassExt, WidgetImpl, WidgetImplExt};
+use gtk::traits::WidgetExt;
+use gtk::{Accessible, Buildable, ConstraintTarget, Orientation, Snapshot, Widget};
+use std::cell::{Cell, Ref};
+use std::rc::Rc;
+
+glib::wrapper! {
+    pub struct CpuLane(ObjectSubclass<CpuLanePrivate>)
+    @extends Widget,
+    @implements Accessible, Buildable, ConstraintTarget;
+}
+
+impl CpuLane {
+    pub fn new(profile: Rc<Profile>, cpu_samples: Rc<CpuSamples>, cpu_index: u32) -> Self {
+        Object::new(&[
+            ("profile", &BoxedAnyObject::new(profile)),
+            ("cpu-samples", &BoxedAnyObject::new(cpu_samples)),
+            ("cpu-index", &cpu_index),
+        ])
+        .unwrap()
+    }
+}
+
+// A distinct color per thread, so migrations between CPUs stand out
+pub fn thread_color(tid: u32) -> RGBA {
+    // Multiples of the golden ratio spread consecutive tids around the color wheel
+    let hue = (tid as f32 * 0.618_034).fract();
+    let (red, green, blue) = gtk::hsv_to_rgb(hue, 0.6, 0.85);
+    RGBA::new(red, green, blue, 0.8)
+}
+
+// ------------------------------------------------------------------------------
+
+#[derive(Properties, Default)]
+pub struct CpuLanePrivate {
+    #[property(get, set, construct_only, builder(BoxedAnyObject::static_type()))]
+    profile: OnceCell<BoxedAnyObject>,
+    #[property(get, set, construct_only, builder(BoxedAnyObject::static_type()))]
+    cpu_samples: OnceCell<BoxedAnyObject>,
+    // Index into the CPUs of cpu_samples
+    #[property(get, set, construct_only)]
+    cpu_index: Cell<u32>,
+}
+
+#[object_subclass]
+impl ObjectSubclass for CpuLanePrivate {
+    const NAME: &'static str = "WtfCpuLane";
+    type Type = CpuLane;
+    type ParentType = Widget;
+
+    fn class_init(klass: &mut Self::Class) {
+        klass.set_css_name("cpu-lane");
+    }
+}
+
+impl ObjectImpl for CpuLanePrivate {
+    fn constructed(&self, this: &Self::Type) {
+        self.parent_constructed(this);
+
+        this.set_hexpand(true);
+    }
+
+    fn properties() -> &'static [ParamSpec] {
+        Self::derived_properties()
+    }
+    fn set_property(&self, this: &Self::Type, id: usize, value: &Value, pspec: &ParamSpec) {
+        Self::derived_set_property(self, this, id, value, pspec).unwrap();
+    }
+    fn property(&self, this: &Self::Type, id: usize, pspec: &ParamSpec) -> Value {
+        Self::derived_property(self, this, id, pspec).unwrap()
+    }
+}
+
+impl WidgetImpl for CpuLanePrivate {
+    fn measure(&self, _: &Self::Type, orientation: Orientation, _: i32) -> (i32, i32, i32, i32) {
+        match orientation {
+            Orientation::Horizontal => (0, 0, -1, -1),
+            Orientation::Vertical => (20, 20, -1, -1),
+            _ => unreachable!(),
+        }
+    }
+
+    // A bar for each sample taken on the CPU, colored by the thread that was running
+    fn snapshot(&self, this: &Self::Type, snapshot: &Snapshot) {
+        self.parent_snapshot(this, snapshot);
+
+        let timeline_view = this.parent().unwrap().downcast::<TimelineView>().unwrap();
+        let profile: Ref<Rc<Profile>> = self.profile.get().unwrap().borrow();
+        let cpu_samples: Ref<Rc<CpuSamples>> = self.cpu_samples.get().unwrap().borrow();
+        let display_time_range = timeline_view.display_time_range();
+        let search_matches = timeline_view.search_matches();
+
+        let samples = cpu_samples.samples_in_range(
+            &profile,
+            self.cpu_index.get() as usize,
+            display_time_range.start(),
+            display_time_range.end(),
+        );
+        // Only the first sample of each thread in a pixel is drawn
+        let mut last_drawn = None;
+        for &sample in samples {
+            let tid = profile.samples.tid[sample];
+            let x = timeline_view
+                .time_to_widget_point(profile.samples.time[sample])
+                .floor() as f32;
+            if last_drawn == Some((x, tid)) {
+                continue;
+            }
+            last_drawn = Some((x, tid));
+
+            let mut color = thread_color(tid);
+            if let Some(search_matches) = &search_matches {
+                if !search_matches.matches_stack(profile.samples.stack[sample]) {
+                    color.set_alpha(0.2);
+                }
+            }
+            snapshot.append_color(&color, &Rect::new(x, 0.0, 1.0, this.height() as f32));
+        }
+
+        let cpu = cpu_samples.cpus[self.cpu_index.get() as usize];
+        append_row_header(this, snapshot, &format!("CPU {cpu}"));
+    }
+}
@@ -0,0 +1,98 @@
+use crate::timeline_range::TimelineRangePrivatePropertiesExt;
+use crate::timeline_view::{TimelineView, TimelineViewPrivatePropertiesExt};
+use glib::subclass::prelude::{ObjectImpl, ObjectImplExt, ObjectSubclass};
+use glib::{object_subclass, Cast, Object, Properties};
+use gtk::gdk::RGBA;
+use gtk::graphene::Rect;
+use gtk::subclass::prelude::{WidgetClassSubclassExt, WidgetImpl, WidgetImplExt};
+use gtk::traits::{StyleContextExt, WidgetExt};
+use gtk::{Accessible, Buildable, ConstraintTarget, Orientation, Snapshot, Widget};
+
+const GRAPH_HEIGHT: i32 = 40;
+
+glib::wrapper! {
+    pub struct CpuUsageGraph(ObjectSubclass<CpuUsageGraphPrivate>)
+    @extends Widget,
+    @implements Accessible, Buildable, ConstraintTarget;
+}
+
+impl CpuUsageGraph {
+    pub fn new() -> Self {
+        Object::new(&[]).unwrap()
+    }
+}
+
+// ------------------------------------------------------------------------------
+
+#[derive(Properties, Default)]
+pub struct CpuUsageGraphPrivate {}
+
+#[object_subclass]
+impl ObjectSubclass for CpuUsageGraphPrivate {
+    const NAME: &'static str = "WtfCpuUsageGraph";
+    type Type = CpuUsageGraph;
+    type ParentType = Widget;
+
+    fn class_init(klass: &mut Self::Class) {
+        klass.set_css_name("cpu-usage-graph");
+    }
+}
+
+impl ObjectImpl for CpuUsageGraphPrivate {
+    fn constructed(&self, this: &Self::Type) {
+        self.parent_constructed(this);
+
+        this.set_hexpand(true);
+        this.set_css_classes(&["caption", "dim-label"]);
+    }
+}
+
+impl WidgetImpl for CpuUsageGraphPrivate {
+    fn measure(&self, _: &Self::Type, orientation: Orientation, _: i32) -> (i32, i32, i32, i32) {
+        match orientation {
+            Orientation::Horizontal => (0, 0, -1, -1),
+            Orientation::Vertical => (GRAPH_HEIGHT, GRAPH_HEIGHT, -1, -1),
+            _ => unreachable!(),
+        }
+    }
+
+    // One bar per pixel, as tall as the share of CPUs busy during that pixel's time
+    fn snapshot(&self, this: &Self::Type, snapshot: &Snapshot) {
+        self.parent_snapshot(this, snapshot);
+
+        let timeline_view = this.parent().unwrap().downcast::<TimelineView>().unwrap();
+        let (profile, cpu_samples) =
+            match (timeline_view.profile_data(), timeline_view.cpu_samples()) {
+                (Some(profile), Some(cpu_samples)) => (profile, cpu_samples),
+                _ => return,
+            };
+        let display_time_range = timeline_view.display_time_range();
+        let usage = cpu_samples.usage(
+            &profile,
+            display_time_range.start(),
+            display_time_range.end(),
+            this.width().max(0) as usize,
+        );
+
+        let height = this.height() as f32;
+        let cpu_count = cpu_samples.cpus.len().max(1) as f64;
+        let mut color = this
+            .style_context()
+            .lookup_color("accent_color")
+            .unwrap_or(RGBA::new(0.21, 0.52, 0.89, 1.0));
+        color.set_alpha(0.6);
+        for (x, busy_cpus) in usage.into_iter().enumerate() {
+            let bar_height = ((busy_cpus / cpu_count).min(1.0) as f32 * height).round();
+            if bar_height > 0.0 {
+                snapshot.append_color(
+                    &color,
+                    &Rect::new(x as f32, height - bar_height, 1.0, bar_height),
+                );
+            }
+        }
+
+        let label = format!("CPU Usage ({} CPUs)", cpu_samples.cpus.len());
+        let label = this.create_pango_layout(Some(&label));
+        snapshot.render_layout(&this.style_context(), 4.0, 2.0, &label);
+    }
+}
@@ -0,0 +1,207 @@
+use crate::analysis::{DiffTree, NodeId, ROOT_NODE};
+use crate::diff_page::format_value;
+use crate::flame_graph::append_frame;
+use glib::subclass::prelude::{ObjectImpl, ObjectImplExt, ObjectSubclass, ObjectSubclassIsExt};
+use glib::{object_subclass, Cast, Object};
+use gtk::gdk::RGBA;
+use gtk::graphene::{Point, Rect};
+use gtk::subclass::prelude::{WidgetClassSubclassExt, WidgetImpl, WidgetImplExt};
+use gtk::traits::{EventControllerExt, GestureSingleExt, WidgetExt};
+use gtk::{Accessible, Buildable, ConstraintTarget, GestureClick, Overflow, Snapshot, Widget};
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+const FRAME_HEIGHT: f32 = 18.0;
+// Frames narrower than this aren't drawn
+const MIN_FRAME_WIDTH: f32 = 1.0;
+
+glib::wrapper! {
+    pub struct DiffFlameGraph(ObjectSubclass<DiffFlameGraphPrivate>)
+    @extends Widget,
+    @implements Accessible, Buildable, ConstraintTarget;
+}
+
+impl DiffFlameGraph {
+    pub fn new() -> Self {
+        Object::new(&[]).unwrap()
+    }
+
+    pub fn set_tree(&self, tree: Rc<DiffTree>, per_second: bool) {
+        let imp = self.imp();
+        imp.tree.replace(Some(tree));
+        imp.per_second.set(per_second);
+        imp.zoom_node.set(ROOT_NODE);
+        self.queue_draw();
+    }
+
+    fn drawn_frame_at(&self, x: f32, y: f32) -> Option<NodeId> {
+        let drawn_frames = self.imp().drawn_frames.borrow();
+        drawn_frames
+            .iter()
+            .find(|(rect, _)| rect.contains_point(&Point::new(x, y)))
+            .map(|&(_, node)| node)
+    }
+
+    fn tooltip_text_at(&self, x: f32, y: f32) -> Option<String> {
+        let node = self.drawn_frame_at(x, y)?;
+        let tree = self.imp().tree.borrow();
+        let tree = tree.as_ref()?;
+        let per_second = self.imp().per_second.get();
+
+        let (before, after) = tree.total(node);
+        let (self_before, self_after) = tree.self_value(node);
+        Some(format!(
+            "{}\nTotal: {} → {} ({})\nSelf: {} → {} ({})",
+            node_name(tree, node),
+            format_value(before, per_second, false),
+            format_value(after, per_second, false),
+            format_value(after - before, per_second, true),
+            format_value(self_before, per_second, false),
+            format_value(self_after, per_second, false),
+            format_value(self_after - self_before, per_second, true),
+        ))
+    }
+}
+
+fn node_name(tree: &DiffTree, node: NodeId) -> &str {
+    match node {
+        ROOT_NODE => "All",
+        node => &tree.node(node).name,
+    }
+}
+
+// Red for functions that take more time after, blue for ones that take less, and white for unchanged ones
+fn delta_color(before: f64, after: f64) -> RGBA {
+    let change = ((after - before) / before.max(after).max(f64::MIN_POSITIVE)) as f32;
+    let fade = 0.95 - 0.6 * change.abs();
+    if change >= 0.0 {
+        RGBA::new(0.95, fade, fade, 1.0)
+    } else {
+        RGBA::new(fade, fade, 0.95, 1.0)
+    }
+}
+
+// Clicking a frame zooms in on it, and clicking the zoomed in frame zooms back out to its parent
+fn zoom_to_clicked_frame(gesture: &GestureClick, _: i32, x: f64, y: f64) {
+    let flame_graph = gesture.widget().downcast::<DiffFlameGraph>().unwrap();
+    let imp = flame_graph.imp();
+    let node = match flame_graph.drawn_frame_at(x as f32, y as f32) {
+        Some(node) => node,
+        None => return,
+    };
+    if node == imp.zoom_node.get() {
+        if let Some(tree) = &*imp.tree.borrow() {
+            imp.zoom_node.set(tree.node(node).parent);
+        }
+    } else {
+        imp.zoom_node.set(node);
+    }
+    flame_graph.queue_draw();
+}
+
+// ------------------------------------------------------------------------------
+
+#[derive(Default)]
+pub struct DiffFlameGraphPrivate {
+    tree: RefCell<Option<Rc<DiffTree>>>,
+    per_second: Cell<bool>,
+    zoom_node: Cell<NodeId>,
+    drawn_frames: RefCell<Vec<(Rect, NodeId)>>,
+}
+
+#[object_subclass]
+impl ObjectSubclass for DiffFlameGraphPrivate {
+    const NAME: &'static str = "WtfDiffFlameGraph";
+    type Type = DiffFlameGraph;
+    type ParentType = Widget;
+
+    fn class_init(klass: &mut Self::Class) {
+        klass.set_css_name("diff-flame-graph");
+    }
+}
+
+impl ObjectImpl for DiffFlameGraphPrivate {
+    fn constructed(&self, this: &Self::Type) {
+        self.parent_constructed(this);
+
+        this.set_hexpand(true);
+        this.set_vexpand(true);
+        this.set_overflow(Overflow::Hidden);
+
+        let click_controller = GestureClick::new();
+        click_controller.set_button(1);
+        click_controller.connect_released(zoom_to_clicked_frame);
+        this.add_controller(&click_controller);
+
+        this.set_has_tooltip(true);
+        this.connect_query_tooltip(|this, x, y, _, tooltip| {
+            let text = this.tooltip_text_at(x as f32, y as f32);
+            tooltip.set_text(text.as_deref());
+            text.is_some()
+        });
+    }
+}
+
+impl WidgetImpl for DiffFlameGraphPrivate {
+    fn snapshot(&self, this: &Self::Type, snapshot: &Snapshot) {
+        self.parent_snapshot(this, snapshot);
+
+        let mut drawn_frames = self.drawn_frames.borrow_mut();
+        drawn_frames.clear();
+
+        let tree = self.tree.borrow();
+        let tree = match &*tree {
+            Some(tree) => tree,
+            None => return,
+        };
+
+        // Frames are as wide as the larger of their before and after values, so that
+        // functions which only exist in one of the profiles are still visible
+        let height = this.height() as f32;
+        let mut pending = vec![(self.zoom_node.get(), 0.0, this.width() as f32, 0)];
+        while let Some((node, x, width, depth)) = pending.pop() {
+            let y = depth as f32 * FRAME_HEIGHT;
+            if y > height {
+                continue;
+            }
+
+            let (before, after) = tree.total(node);
+            let rect = Rect::new(x, y, width - 1.0, FRAME_HEIGHT - 1.0);
+            append_frame(
+                this,
+                snapshot,
+                node_name(tree, node),
+                &delta_color(before, after),
+                &rect,
+            );
+            drawn_frames.push((rect, node));
+
+            // The larger sides of the children can add up to more than the larger side of their parent
+            let children = &tree.node(node).children;
+            let (self_before, self_after) = tree.self_value(node);
+            let children_value = children
+                .iter()
+                .map(|&child| {
+                    let (before, after) = tree.total(child);
+                    before.max(after)
+                })
+                .sum::<f64>();
+            let value = before
+                .max(after)
+                .max(children_value + self_before.max(self_after));
+            let value_to_width = width as f64 / value.max(f64::MIN_POSITIVE);
+
+            // Children are sorted heaviest first, so once one is too narrow the rest are too
+            let mut child_x = x;
+            for &child in children {
+                let (before, after) = tree.total(child);
+                let child_width = (before.max(after) * value_to_width) as f32;
+                if child_width < MIN_FRAME_WIDTH {
+                    break;
+                }
+                pending.push((child, child_x, child_width, depth + 1));
+                child_x += child_width;
+            }
+        }
+    }
+}
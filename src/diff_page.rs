@@ -0,0 +1,309 @@
+use crate::analysis::{DiffTree, NodeId, Normalization, ROOT_NODE};
+use crate::diff_flame_graph::DiffFlameGraph;
+use crate::wtf_profile::Profile;
+use adw::subclass::prelude::BinImpl;
+use adw::traits::BinExt;
+use adw::Bin;
+use gio::{ListModel, ListStore};
+use glib::once_cell::sync::OnceCell;
+use glib::subclass::prelude::{
+    DerivedObjectProperties, ObjectImpl, ObjectImplExt, ObjectSubclass, ObjectSubclassExt,
+    ObjectSubclassIsExt,
+};
+use glib::{
+    object_subclass, BindingFlags, BoxedAnyObject, Cast, Object, ObjectExt, ParamSpec, Properties,
+    StaticType, Value,
+};
+use gtk::pango::EllipsizeMode;
+use gtk::subclass::prelude::{WidgetClassSubclassExt, WidgetImpl};
+use gtk::traits::{BoxExt, WidgetExt};
+use gtk::{
+    Accessible, Align, Buildable, ColumnView, ColumnViewColumn, ConstraintTarget, Label, ListItem,
+    Orientation, Paned, ScrolledWindow, SignalListItemFactory, SingleSelection, ToggleButton,
+    TreeExpander, TreeListModel, TreeListRow, Widget,
+};
+use std::cell::Cell;
+use std::rc::Rc;
+
+glib::wrapper! {
+    pub struct DiffPage(ObjectSubclass<DiffPagePrivate>)
+    @extends Bin, Widget,
+    @implements Accessible, Buildable, ConstraintTarget;
+}
+
+impl DiffPage {
+    pub fn new(before: (Rc<Profile>, &str), after: (Rc<Profile>, &str)) -> Self {
+        let this: Self = Object::new(&[]).unwrap();
+        let widgets = this.imp().widgets.get().unwrap();
+        widgets
+            .before_name
+            .set_label(&format!("Before: {}", before.1));
+        widgets.after_name.set_label(&format!("After: {}", after.1));
+        this.imp().profiles.set((before.0, after.0)).ok().unwrap();
+        this.rebuild();
+        this
+    }
+
+    // Re-aggregate both profiles with the current normalization
+    fn rebuild(&self) {
+        let imp = self.imp();
+        let (before, after) = match imp.profiles.get() {
+            Some(profiles) => profiles,
+            None => return,
+        };
+        let normalization = match self.per_second() {
+            true => Normalization::PerSecond,
+            false => Normalization::PerSample,
+        };
+        let tree = Rc::new(DiffTree::new(before, after, normalization));
+
+        let widgets = imp.widgets.get().unwrap();
+        widgets
+            .flame_graph
+            .set_tree(tree.clone(), self.per_second());
+
+        let root = DiffTreeItem {
+            tree,
+            node: ROOT_NODE,
+            per_second: self.per_second(),
+        };
+        let root_model = root
+            .children_model()
+            .unwrap_or_else(|| ListStore::new(BoxedAnyObject::static_type()).upcast());
+        let tree_model = TreeListModel::new(&root_model, false, false, |item| {
+            let item = item.downcast_ref::<BoxedAnyObject>().unwrap();
+            let item = item.borrow::<DiffTreeItem>();
+            item.children_model()
+        });
+        widgets
+            .column_view
+            .set_model(Some(&SingleSelection::new(Some(&tree_model))));
+    }
+}
+
+// A normalized value as a percent of samples, or as samples per second, optionally with a sign
+pub fn format_value(value: f64, per_second: bool, signed: bool) -> String {
+    match (per_second, signed) {
+        (false, false) => format!("{:.1}%", value * 100.0),
+        (false, true) => format!("{:+.1}%", value * 100.0),
+        (true, false) => format!("{value:.1}/s"),
+        (true, true) => format!("{value:+.1}/s"),
+    }
+}
+
+// A node of a DiffTree, as displayed by a row
+struct DiffTreeItem {
+    tree: Rc<DiffTree>,
+    node: NodeId,
+    per_second: bool,
+}
+
+impl DiffTreeItem {
+    fn children_model(&self) -> Option<ListModel> {
+        let children = &self.tree.node(self.node).children;
+        if children.is_empty() {
+            return None;
+        }
+
+        let model = ListStore::new(BoxedAnyObject::static_type());
+        for &child in children {
+            model.append(&BoxedAnyObject::new(DiffTreeItem {
+                tree: self.tree.clone(),
+                node: child,
+                per_second: self.per_second,
+            }));
+        }
+        Some(model.upcast())
+    }
+}
+
+fn list_item_row_item(list_item: &ListItem) -> BoxedAnyObject {
+    let row = list_item.item().unwrap().downcast::<TreeListRow>().unwrap();
+    row.item().unwrap().downcast::<BoxedAnyObject>().unwrap()
+}
+
+fn new_function_column() -> ColumnViewColumn {
+    let factory = SignalListItemFactory::new();
+    factory.connect_setup(|_, list_item| {
+        let label = Label::new(None);
+        label.set_xalign(0.0);
+        label.set_ellipsize(EllipsizeMode::End);
+        let expander = TreeExpander::new();
+        expander.set_child(Some(&label));
+        list_item.set_child(Some(&expander));
+    });
+    factory.connect_bind(|_, list_item| {
+        let expander = list_item
+            .child()
+            .unwrap()
+            .downcast::<TreeExpander>()
+            .unwrap();
+        let label = expander.child().unwrap().downcast::<Label>().unwrap();
+        let row = list_item.item().unwrap().downcast::<TreeListRow>().unwrap();
+        expander.set_list_row(Some(&row));
+
+        let item = list_item_row_item(list_item);
+        let item = item.borrow::<DiffTreeItem>();
+        label.set_label(&item.tree.node(item.node).name);
+    });
+
+    let column = ColumnViewColumn::new(Some("Function"), Some(&factory));
+    column.set_expand(true);
+    column.set_resizable(true);
+    column
+}
+
+// value gives the before and after values of a node, and shown picks what's shown of them.
+// Signed values are changes, colored by whether they're increases or decreases.
+fn new_value_column(
+    title: &str,
+    value: fn(&DiffTree, NodeId) -> (f64, f64),
+    shown: fn(f64, f64) -> f64,
+    signed: bool,
+) -> ColumnViewColumn {
+    let factory = SignalListItemFactory::new();
+    factory.connect_setup(|_, list_item| {
+        let label = Label::new(None);
+        label.set_xalign(1.0);
+        label.add_css_class("numeric");
+        list_item.set_child(Some(&label));
+    });
+    factory.connect_bind(move |_, list_item| {
+        let label = list_item.child().unwrap().downcast::<Label>().unwrap();
+        let item = list_item_row_item(list_item);
+        let item = item.borrow::<DiffTreeItem>();
+        let (before, after) = value(&item.tree, item.node);
+        let shown = shown(before, after);
+        label.set_label(&format_value(shown, item.per_second, signed));
+
+        label.remove_css_class("diff-increase");
+        label.remove_css_class("diff-decrease");
+        if signed && shown > 0.0 {
+            label.add_css_class("diff-increase");
+        } else if signed && shown < 0.0 {
+            label.add_css_class("diff-decrease");
+        }
+    });
+
+    let column = ColumnViewColumn::new(Some(title), Some(&factory));
+    column.set_resizable(true);
+    column
+}
+
+struct DiffWidgets {
+    before_name: Label,
+    after_name: Label,
+    column_view: ColumnView,
+    flame_graph: DiffFlameGraph,
+}
+
+// ------------------------------------------------------------------------------
+
+#[derive(Properties, Default)]
+pub struct DiffPagePrivate {
+    // Whether values are normalized by each profile's duration instead of its total samples
+    #[property(get, set = Self::set_per_second)]
+    per_second: Cell<bool>,
+
+    // The profiles recorded before and after the change being compared
+    profiles: OnceCell<(Rc<Profile>, Rc<Profile>)>,
+    widgets: OnceCell<DiffWidgets>,
+}
+
+impl DiffPagePrivate {
+    pub fn set_per_second(&self, value: bool) {
+        self.per_second.set(value);
+        self.instance().rebuild();
+    }
+}
+
+#[object_subclass]
+impl ObjectSubclass for DiffPagePrivate {
+    const NAME: &'static str = "WtfDiffPage";
+    type Type = DiffPage;
+    type ParentType = Bin;
+
+    fn class_init(klass: &mut Self::Class) {
+        klass.set_css_name("diff-page");
+    }
+}
+
+impl ObjectImpl for DiffPagePrivate {
+    fn constructed(&self, this: &Self::Type) {
+        self.parent_constructed(this);
+
+        let before_name = Label::new(None);
+        before_name.add_css_class("heading");
+        let after_name = Label::new(None);
+        after_name.add_css_class("heading");
+        after_name.set_hexpand(true);
+        after_name.set_xalign(0.0);
+
+        let per_second_toggle = ToggleButton::with_label("Per Second");
+        per_second_toggle.set_tooltip_text(Some(
+            "Normalize by each profile's duration instead of its number of samples",
+        ));
+        per_second_toggle
+            .bind_property("active", this, "per-second")
+            .flags(BindingFlags::BIDIRECTIONAL | BindingFlags::SYNC_CREATE)
+            .build();
+
+        let header = gtk::Box::new(Orientation::Horizontal, 18);
+        header.append(&before_name);
+        header.append(&after_name);
+        header.append(&per_second_toggle);
+
+        let column_view = ColumnView::new(None::<&SingleSelection>);
+        column_view.add_css_class("data-table");
+        column_view.append_column(&new_function_column());
+        let before = |before: f64, _: f64| before;
+        let after = |_: f64, after: f64| after;
+        let change = |before: f64, after: f64| after - before;
+        column_view.append_column(&new_value_column("Before", DiffTree::total, before, false));
+        column_view.append_column(&new_value_column("After", DiffTree::total, after, false));
+        column_view.append_column(&new_value_column("Delta", DiffTree::total, change, true));
+        column_view.append_column(&new_value_column(
+            "Self Delta",
+            DiffTree::self_value,
+            change,
+            true,
+        ));
+
+        let scrolled_window = ScrolledWindow::new();
+        scrolled_window.set_child(Some(&column_view));
+
+        let flame_graph = DiffFlameGraph::new();
+        flame_graph.set_valign(Align::Fill);
+
+        let paned = Paned::new(Orientation::Horizontal);
+        paned.set_start_child(Some(&scrolled_window));
+        paned.set_end_child(Some(&flame_graph));
+        paned.set_vexpand(true);
+
+        let content = gtk::Box::new(Orientation::Vertical, 12);
+        content.append(&header);
+        content.append(&paned);
+        this.set_child(Some(&content));
+
+        let widgets = DiffWidgets {
+            before_name,
+            after_name,
+            column_view,
+            flame_graph,
+        };
+        self.widgets.set(widgets).ok().unwrap();
+    }
+
+    fn properties() -> &'static [ParamSpec] {
+        Self::derived_properties()
+    }
+    fn set_property(&self, this: &Self::Type, id: usize, value: &Value, pspec: &ParamSpec) {
+        Self::derived_set_property(self, this, id, value, pspec).unwrap();
+    }
+    fn property(&self, this: &Self::Type, id: usize, pspec: &ParamSpec) -> Value {
+        Self::derived_property(self, this, id, pspec).unwrap()
+    }
+}
+
+impl WidgetImpl for DiffPagePrivate {}
+impl BinImpl for DiffPagePrivate {}
@@ -0,0 +1,345 @@
+use crate::analysis::{function_address, instruction_weights, Function};
+use crate::perf_data_parser::{Disassembly, Symbolicator};
+use crate::source_view::{heat_markup, list_item_labels, read_source_file};
+use crate::timeline_view::TimelineView;
+use crate::wtf_profile::Profile;
+use adw::subclass::prelude::BinImpl;
+use adw::traits::BinExt;
+use adw::Bin;
+use gio::ListStore;
+use glib::once_cell::sync::OnceCell;
+use glib::subclass::prelude::{
+    DerivedObjectProperties, ObjectImpl, ObjectImplExt, ObjectSubclass, ObjectSubclassExt,
+    ObjectSubclassIsExt,
+};
+use glib::{
+    clone, idle_add_local_once, markup_escape_text, object_subclass, BoxedAnyObject, Cast, Object,
+    ObjectExt, ParamSpec, Properties, StaticType, Value,
+};
+use gtk::pango::EllipsizeMode;
+use gtk::subclass::prelude::{WidgetClassSubclassExt, WidgetImpl};
+use gtk::traits::{BoxExt, WidgetExt};
+use gtk::{
+    Accessible, Buildable, ConstraintTarget, Label, ListView, NoSelection, Orientation,
+    ScrolledWindow, SignalListItemFactory, Stack, Widget,
+};
+use std::cell::{Cell, RefCell};
+use std::collections::HashMap;
+
+glib::wrapper! {
+    pub struct DisassemblyView(ObjectSubclass<DisassemblyViewPrivate>)
+    @extends Bin, Widget,
+    @implements Accessible, Buildable, ConstraintTarget;
+}
+
+impl DisassemblyView {
+    pub fn new() -> Self {
+        Object::new(&[]).unwrap()
+    }
+
+    pub fn show_function(&self, function: Function) {
+        self.imp().function.set(Some(function));
+        self.queue_rebuild();
+    }
+
+    // Selection changes arrive on every drag update, so coalesce them into one rebuild
+    fn queue_rebuild(&self) {
+        if !self.imp().rebuild_queued.replace(true) {
+            idle_add_local_once(clone!(@weak self as this => move || {
+                this.imp().rebuild_queued.set(false);
+                this.rebuild();
+            }));
+        }
+    }
+
+    fn rebuild(&self) {
+        let imp = self.imp();
+        let widgets = imp.widgets.get().unwrap();
+        let analysis = self.timeline().and_then(|timeline| {
+            let function = imp.function.get()?;
+            let (start, end) = timeline.analysis_time_range()?;
+            Some((timeline.analysis_profile()?, function, start, end))
+        });
+        let (profile, function, start, end) = match analysis {
+            Some(analysis) => analysis,
+            None => {
+                widgets
+                    .message
+                    .set_label("Select a function to see its disassembly");
+                widgets.stack.set_visible_child(&widgets.message);
+                return;
+            }
+        };
+
+        let disassembly = match self.disassemble(&profile, function) {
+            Ok(disassembly) => disassembly,
+            Err(error) => {
+                widgets.message.set_label(&error);
+                widgets.stack.set_visible_child(&widgets.message);
+                return;
+            }
+        };
+
+        let addresses = match (
+            disassembly.instructions.first(),
+            disassembly.instructions.last(),
+        ) {
+            (Some(first), Some(last)) => first.address..last.address + 1,
+            _ => 0..0,
+        };
+        let weights = instruction_weights(&profile, start, end, addresses);
+        let max_weight = weights.values().copied().max().unwrap_or_default().max(1);
+
+        // Each instruction is preceded by its source line whenever that changes
+        let model = ListStore::new(BoxedAnyObject::static_type());
+        let mut source_files: HashMap<String, Option<Vec<String>>> = HashMap::new();
+        let mut previous_location = None;
+        for instruction in disassembly.instructions {
+            if instruction.location.is_some() && instruction.location != previous_location {
+                let (file, line) = instruction.location.clone().unwrap();
+                let source = source_files.entry(file.clone()).or_insert_with(|| {
+                    let text = read_source_file(&file, "").ok()?;
+                    Some(text.lines().map(str::to_owned).collect())
+                });
+                let text = source
+                    .as_ref()
+                    .and_then(|lines| lines.get((line as usize).checked_sub(1)?))
+                    .map(|text| text.trim().to_owned())
+                    .unwrap_or_default();
+                model.append(&BoxedAnyObject::new(DisassemblyLine::Source {
+                    location: format!("{}:{line}", file.rsplit('/').next().unwrap_or(&file)),
+                    text,
+                }));
+                previous_location = instruction.location.clone();
+            }
+
+            let weight = weights
+                .get(&instruction.address)
+                .copied()
+                .unwrap_or_default();
+            let text = match instruction.call_target {
+                Some(call_target) => format!("{}  ; {call_target}", instruction.text),
+                None => instruction.text,
+            };
+            model.append(&BoxedAnyObject::new(DisassemblyLine::Instruction {
+                address: instruction.address,
+                text,
+                weight,
+                heat: weight as f64 / max_weight as f64,
+            }));
+        }
+
+        widgets.title.set_label(&disassembly.function);
+        widgets
+            .list_view
+            .set_model(Some(&NoSelection::new(Some(&model))));
+        widgets.stack.set_visible_child(&widgets.content);
+    }
+
+    // Binaries are only loaded once, as long as the same one is being disassembled
+    fn disassemble(&self, profile: &Profile, function: Function) -> Result<Disassembly, String> {
+        let (binary_path, base_address) = match (
+            profile.metadata("binary-path"),
+            profile.metadata("binary-base-address"),
+        ) {
+            (Some(binary_path), Some(base_address)) => (binary_path, base_address),
+            _ => return Err("Disassembly needs a profile converted from perf.data".to_owned()),
+        };
+        let address = function_address(profile, function)
+            .ok_or_else(|| "No samples were taken in this function".to_owned())?;
+
+        let mut symbolicator = self.imp().symbolicator.borrow_mut();
+        if symbolicator
+            .as_ref()
+            .map_or(true, |(path, _)| path != binary_path)
+        {
+            let base_address = base_address.parse().map_err(|_| "Invalid base address")?;
+            let loaded = Symbolicator::new(binary_path, base_address)
+                .map_err(|error| format!("Failed to load {binary_path}: {error}"))?;
+            *symbolicator = Some((binary_path.to_owned(), loaded));
+        }
+
+        let (_, symbolicator) = symbolicator.as_ref().unwrap();
+        symbolicator
+            .disassemble(address)
+            .map_err(|error| format!("Failed to disassemble: {error}"))
+    }
+}
+
+enum DisassemblyLine {
+    Source {
+        // file name:line
+        location: String,
+        text: String,
+    },
+    Instruction {
+        address: u64,
+        text: String,
+        // Weight of samples taken at the instruction
+        weight: u64,
+        // Weight relative to the function's heaviest instruction
+        heat: f64,
+    },
+}
+
+fn new_line_factory() -> SignalListItemFactory {
+    let factory = SignalListItemFactory::new();
+    factory.connect_setup(|_, list_item| {
+        let weight = Label::new(None);
+        weight.set_xalign(1.0);
+        weight.set_width_chars(6);
+        weight.add_css_class("numeric");
+        weight.add_css_class("dim-label");
+        let address = Label::new(None);
+        address.set_xalign(1.0);
+        address.add_css_class("dim-label");
+        let text = Label::new(None);
+        text.set_xalign(0.0);
+        text.set_hexpand(true);
+
+        let row = gtk::Box::new(Orientation::Horizontal, 12);
+        row.add_css_class("monospace");
+        row.append(&weight);
+        row.append(&address);
+        row.append(&text);
+        list_item.set_child(Some(&row));
+    });
+    factory.connect_bind(|_, list_item| {
+        let labels = list_item_labels(list_item);
+        let line = list_item
+            .item()
+            .unwrap()
+            .downcast::<BoxedAnyObject>()
+            .unwrap();
+
+        match &*line.borrow::<DisassemblyLine>() {
+            DisassemblyLine::Source { location, text } => {
+                labels[0].set_label("");
+                labels[1].set_label(location);
+                labels[2].set_markup(&format!("<i>{}</i>", markup_escape_text(text)));
+                labels[2].add_css_class("dim-label");
+            }
+            DisassemblyLine::Instruction {
+                address,
+                text,
+                weight,
+                heat,
+            } => {
+                labels[0].set_label(&match weight {
+                    0 => String::new(),
+                    weight => weight.to_string(),
+                });
+                labels[1].set_label(&format!("{address:x}"));
+                labels[2].set_markup(&heat_markup(text, *heat));
+                labels[2].remove_css_class("dim-label");
+            }
+        }
+    });
+    factory
+}
+
+struct DisassemblyWidgets {
+    title: Label,
+    stack: Stack,
+    message: Label,
+    content: gtk::Box,
+    list_view: ListView,
+}
+
+// ------------------------------------------------------------------------------
+
+#[derive(Properties, Default)]
+pub struct DisassemblyViewPrivate {
+    #[property(get, set = Self::set_timeline, builder(TimelineView::static_type()))]
+    timeline: RefCell<Option<TimelineView>>,
+
+    function: Cell<Option<Function>>,
+    // The last binary loaded, by path
+    symbolicator: RefCell<Option<(String, Symbolicator)>>,
+    widgets: OnceCell<DisassemblyWidgets>,
+    rebuild_queued: Cell<bool>,
+}
+
+impl DisassemblyViewPrivate {
+    pub fn set_timeline(&self, value: Option<TimelineView>) {
+        let this = self.instance();
+        if let Some(timeline) = &value {
+            for property in [
+                "profile",
+                "selected-time-range",
+                "transform-count",
+                "selected-thread-count",
+            ] {
+                timeline.connect_notify_local(
+                    Some(property),
+                    clone!(@weak this => move |_, _| this.queue_rebuild()),
+                );
+            }
+        }
+
+        *self.timeline.borrow_mut() = value;
+        this.queue_rebuild();
+    }
+}
+
+#[object_subclass]
+impl ObjectSubclass for DisassemblyViewPrivate {
+    const NAME: &'static str = "WtfDisassemblyView";
+    type Type = DisassemblyView;
+    type ParentType = Bin;
+
+    fn class_init(klass: &mut Self::Class) {
+        klass.set_css_name("disassembly-view");
+    }
+}
+
+impl ObjectImpl for DisassemblyViewPrivate {
+    fn constructed(&self, this: &Self::Type) {
+        self.parent_constructed(this);
+
+        let title = Label::new(None);
+        title.set_xalign(0.0);
+        title.set_ellipsize(EllipsizeMode::End);
+        title.add_css_class("heading");
+
+        let list_view = ListView::new(None::<&NoSelection>, Some(&new_line_factory()));
+        let scrolled_window = ScrolledWindow::new();
+        scrolled_window.set_child(Some(&list_view));
+        scrolled_window.set_vexpand(true);
+
+        let content = gtk::Box::new(Orientation::Vertical, 6);
+        content.append(&title);
+        content.append(&scrolled_window);
+
+        let message = Label::new(None);
+        message.add_css_class("dim-label");
+        message.set_wrap(true);
+
+        let stack = Stack::new();
+        stack.add_child(&content);
+        stack.add_child(&message);
+        this.set_child(Some(&stack));
+
+        let widgets = DisassemblyWidgets {
+            title,
+            stack,
+            message,
+            content,
+            list_view,
+        };
+        self.widgets.set(widgets).ok().unwrap();
+    }
+
+    fn properties() -> &'static [ParamSpec] {
+        Self::derived_properties()
+    }
+    fn set_property(&self, this: &Self::Type, id: usize, value: &Value, pspec: &ParamSpec) {
+        Self::derived_set_property(self, this, id, value, pspec).unwrap();
+    }
+    fn property(&self, this: &Self::Type, id: usize, pspec: &ParamSpec) -> Value {
+        Self::derived_property(self, this, id, pspec).unwrap()
+    }
+}
+
+impl WidgetImpl for DisassemblyViewPrivate {}
+impl BinImpl for DisassemblyViewPrivate {}
@@ -0,0 +1,471 @@
+use crate::analysis::{AggregatedTree, Category, NodeId, ROOT_NODE};
+use crate::timeline_view::TimelineView;
+use crate::transform_menu::popup_transform_menu;
+use crate::wtf_profile::{Profile, StringId};
+use glib::once_cell::sync::OnceCell;
+use glib::subclass::prelude::{
+    DerivedObjectProperties, ObjectImpl, ObjectImplExt, ObjectSubclass, ObjectSubclassExt,
+    ObjectSubclassIsExt,
+};
+use glib::{
+    clone, idle_add_local_once, object_subclass, Cast, IsA, ObjectExt, ParamSpec, Properties,
+    StaticType, Value,
+};
+use gtk::gdk::RGBA;
+use gtk::graphene::{Point, Rect};
+use gtk::pango::{EllipsizeMode, SCALE as PANGO_SCALE};
+use gtk::subclass::prelude::{WidgetClassSubclassExt, WidgetImpl, WidgetImplExt};
+use gtk::traits::{
+    BoxExt, ButtonExt, EventControllerExt, GestureSingleExt, OrientableExt, StyleContextExt,
+    WidgetExt,
+};
+use gtk::{
+    Accessible, Align, BoxLayout, Buildable, Button, ConstraintTarget, GestureClick, Label,
+    Orientation, Overflow, Snapshot, Widget,
+};
+use std::cell::{Cell, RefCell};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::rc::Rc;
+
+const FRAME_HEIGHT: f32 = 18.0;
+// Frames narrower than this are merged with their siblings instead of being drawn
+const MIN_FRAME_WIDTH: f32 = 1.0;
+// Frames narrower than this aren't labeled
+const MIN_LABEL_WIDTH: f32 = 24.0;
+
+glib::wrapper! {
+    pub struct FlameGraph(ObjectSubclass<FlameGraphPrivate>)
+    @extends Widget,
+    @implements Accessible, Buildable, ConstraintTarget;
+}
+
+impl FlameGraph {
+    // Selection changes arrive on every drag update, so coalesce them into one rebuild
+    fn queue_rebuild(&self) {
+        if !self.imp().rebuild_queued.replace(true) {
+            idle_add_local_once(clone!(@weak self as this => move || {
+                this.imp().rebuild_queued.set(false);
+                this.rebuild();
+            }));
+        }
+    }
+
+    // Re-aggregate the timeline's profile, staying zoomed in on the same call path if it still exists
+    fn rebuild(&self) {
+        let imp = self.imp();
+        let profile = self.timeline().and_then(|timeline| {
+            let (start, end) = timeline.analysis_time_range()?;
+            let profile = timeline.analysis_profile()?;
+            let tree = AggregatedTree::top_down(&profile, start, end);
+            Some((profile, Rc::new(tree)))
+        });
+
+        let mut zoom_node = ROOT_NODE;
+        if let Some((_, tree)) = &profile {
+            for &(function, module) in imp.zoom_path.borrow().iter() {
+                let child = tree.node(zoom_node).children.iter().find(|&&child| {
+                    let child = tree.node(child);
+                    (child.function, child.module) == (function, module)
+                });
+                match child {
+                    Some(&child) => zoom_node = child,
+                    None => break,
+                }
+            }
+        }
+
+        imp.profile.replace(profile);
+        self.zoom_to(zoom_node);
+    }
+
+    fn zoom_to(&self, node: NodeId) {
+        let imp = self.imp();
+        imp.zoom_node.set(node);
+
+        // Path of nodes from the root to the zoomed in node
+        let mut path = Vec::new();
+        if let Some((_, tree)) = &*imp.profile.borrow() {
+            let mut current = node;
+            while current != ROOT_NODE {
+                path.push(current);
+                current = tree.node(current).parent;
+            }
+            path.reverse();
+            *imp.zoom_path.borrow_mut() = path
+                .iter()
+                .map(|&node| (tree.node(node).function, tree.node(node).module))
+                .collect();
+        }
+
+        self.update_breadcrumbs(&path);
+        self.queue_draw();
+    }
+
+    fn update_breadcrumbs(&self, path: &[NodeId]) {
+        let imp = self.imp();
+        let breadcrumbs = imp.breadcrumbs.get().unwrap();
+        while let Some(child) = breadcrumbs.first_child() {
+            breadcrumbs.remove(&child);
+        }
+
+        let profile = imp.profile.borrow();
+        let (profile, tree) = match &*profile {
+            Some((profile, tree)) => (profile, tree),
+            None => return,
+        };
+
+        for &node in [ROOT_NODE].iter().chain(path) {
+            if node != ROOT_NODE {
+                breadcrumbs.append(&Label::new(Some("›")));
+            }
+
+            let button = Button::with_label(&node_name(profile, tree, node));
+            button.add_css_class("flat");
+            button.connect_clicked(clone!(@weak self as this => move |_| this.zoom_to(node)));
+            breadcrumbs.append(&button);
+        }
+    }
+
+    fn drawn_frame_at(&self, x: f32, y: f32) -> Option<DrawnFrame> {
+        let drawn_frames = self.imp().drawn_frames.borrow();
+        drawn_frames
+            .iter()
+            .find(|frame| frame.rect.contains_point(&Point::new(x, y)))
+            .cloned()
+    }
+
+    fn tooltip_text_at(&self, x: f32, y: f32) -> Option<String> {
+        let drawn_frame = self.drawn_frame_at(x, y)?;
+        let profile = self.imp().profile.borrow();
+        let (profile, tree) = profile.as_ref()?;
+        let total_weight = tree.total_weight().max(1) as f64;
+
+        Some(match drawn_frame.node {
+            Some(node) => {
+                let tree_node = tree.node(node);
+                format!(
+                    "{}\n{}\n{} samples ({:.1}% total, {:.1}% self)",
+                    node_name(profile, tree, node),
+                    profile.strings.get(tree_node.module),
+                    tree_node.total_weight,
+                    tree_node.total_weight as f64 * 100.0 / total_weight,
+                    tree_node.self_weight as f64 * 100.0 / total_weight,
+                )
+            }
+            None => format!(
+                "{} functions too small to display\n{} samples ({:.1}% total)",
+                drawn_frame.merged_count,
+                drawn_frame.weight,
+                drawn_frame.weight as f64 * 100.0 / total_weight,
+            ),
+        })
+    }
+}
+
+fn node_name(profile: &Profile, tree: &AggregatedTree, node: NodeId) -> String {
+    match node {
+        ROOT_NODE => "All".to_owned(),
+        node => profile.strings.get(tree.node(node).function).to_owned(),
+    }
+}
+
+// Colors stable per category: blue for std, warm colors for crates, and cool colors for other modules
+pub fn category_color(category: Category) -> RGBA {
+    let mut hasher = DefaultHasher::new();
+    category.name().hash(&mut hasher);
+    let hash = hasher.finish();
+    let t1 = (hash & 0xFF) as f32 / 255.0;
+    let t2 = ((hash >> 8) & 0xFF) as f32 / 255.0;
+    match category {
+        Category::Std(_) => RGBA::new(0.55, 0.68, 0.85, 1.0),
+        Category::Crate(_) => RGBA::new(0.85 + 0.15 * t1, 0.3 + 0.5 * t2, 0.1 + 0.15 * t1, 1.0),
+        Category::Module(_) => RGBA::new(0.3 + 0.3 * t1, 0.7 + 0.2 * t2, 0.5 + 0.3 * t1, 1.0),
+        Category::Unknown => RGBA::new(0.7, 0.7, 0.7, 1.0),
+    }
+}
+
+// A colored block, labeled with the function's name if there's room for it
+pub fn append_frame(
+    widget: &impl IsA<Widget>,
+    snapshot: &Snapshot,
+    name: &str,
+    color: &RGBA,
+    rect: &Rect,
+) {
+    snapshot.append_color(color, rect);
+    if rect.width() >= MIN_LABEL_WIDTH {
+        let label = widget.create_pango_layout(Some(name));
+        label.set_width(((rect.width() - 5.0) * PANGO_SCALE as f32) as i32);
+        label.set_ellipsize(EllipsizeMode::End);
+        snapshot.save();
+        snapshot.translate(&Point::new(rect.x() + 3.0, rect.y() + 1.0));
+        snapshot.append_layout(&label, &label_color(widget, color));
+        snapshot.restore();
+    }
+}
+
+// Dark or light text, whichever stands out against the fill as it's drawn over the view's background
+fn label_color(widget: &impl IsA<Widget>, fill: &RGBA) -> RGBA {
+    let background = widget
+        .style_context()
+        .lookup_color("view_bg_color")
+        .unwrap_or(RGBA::WHITE);
+    let blend = |fill_channel: f32, background_channel: f32| {
+        fill_channel * fill.alpha() + background_channel * (1.0 - fill.alpha())
+    };
+    let luminance = 0.2126 * blend(fill.red(), background.red())
+        + 0.7152 * blend(fill.green(), background.green())
+        + 0.0722 * blend(fill.blue(), background.blue());
+    if luminance > 0.5 {
+        RGBA::BLACK
+    } else {
+        RGBA::WHITE
+    }
+}
+
+// Where a frame was last drawn, for hit testing clicks and tooltips
+#[derive(Clone)]
+struct DrawnFrame {
+    rect: Rect,
+    // None for a block of merged frames
+    node: Option<NodeId>,
+    merged_count: usize,
+    weight: u64,
+}
+
+fn zoom_to_clicked_frame(gesture: &GestureClick, _: i32, x: f64, y: f64) {
+    let flame_graph = gesture.widget().downcast::<FlameGraph>().unwrap();
+    if let Some(node) = flame_graph
+        .drawn_frame_at(x as f32, y as f32)
+        .and_then(|frame| frame.node)
+    {
+        flame_graph.zoom_to(node);
+    }
+}
+
+fn popup_transform_menu_at_frame(gesture: &GestureClick, _: i32, x: f64, y: f64) {
+    let flame_graph = gesture.widget().downcast::<FlameGraph>().unwrap();
+    let node = match flame_graph
+        .drawn_frame_at(x as f32, y as f32)
+        .and_then(|frame| frame.node)
+    {
+        Some(node) if node != ROOT_NODE => node,
+        _ => return,
+    };
+
+    let profile = flame_graph.imp().profile.borrow();
+    let (profile, tree) = match &*profile {
+        Some((profile, tree)) => (profile, tree),
+        None => return,
+    };
+    let mut path = Vec::new();
+    let mut current = node;
+    while current != ROOT_NODE {
+        path.push((tree.node(current).function, tree.node(current).module));
+        current = tree.node(current).parent;
+    }
+    path.reverse();
+
+    let function = *path.last().unwrap();
+    popup_transform_menu(&flame_graph, x, y, profile, function, Some(path));
+}
+
+// ------------------------------------------------------------------------------
+
+#[derive(Properties, Default)]
+pub struct FlameGraphPrivate {
+    #[property(get, set = Self::set_timeline, builder(TimelineView::static_type()))]
+    timeline: RefCell<Option<TimelineView>>,
+
+    profile: RefCell<Option<(Rc<Profile>, Rc<AggregatedTree>)>>,
+    zoom_node: Cell<NodeId>,
+    // (function, module) of each node from the root to the zoomed in node, used to stay zoomed in across rebuilds
+    zoom_path: RefCell<Vec<(StringId, StringId)>>,
+    breadcrumbs: OnceCell<gtk::Box>,
+    drawn_frames: RefCell<Vec<DrawnFrame>>,
+    rebuild_queued: Cell<bool>,
+}
+
+impl FlameGraphPrivate {
+    pub fn set_timeline(&self, value: Option<TimelineView>) {
+        let this = self.instance();
+        if let Some(timeline) = &value {
+            for property in [
+                "profile",
+                "selected-time-range",
+                "transform-count",
+                "selected-thread-count",
+            ] {
+                timeline.connect_notify_local(
+                    Some(property),
+                    clone!(@weak this => move |_, _| this.queue_rebuild()),
+                );
+            }
+            timeline.connect_notify_local(
+                Some("search-query"),
+                clone!(@weak this => move |_, _| this.queue_draw()),
+            );
+        }
+
+        *self.timeline.borrow_mut() = value;
+        this.queue_rebuild();
+    }
+}
+
+#[object_subclass]
+impl ObjectSubclass for FlameGraphPrivate {
+    const NAME: &'static str = "WtfFlameGraph";
+    type Type = FlameGraph;
+    type ParentType = Widget;
+
+    fn class_init(klass: &mut Self::Class) {
+        klass.set_css_name("flame-graph");
+        klass.set_layout_manager_type::<BoxLayout>();
+    }
+}
+
+impl ObjectImpl for FlameGraphPrivate {
+    fn constructed(&self, this: &Self::Type) {
+        self.parent_constructed(this);
+
+        let layout_manager = this
+            .layout_manager()
+            .unwrap()
+            .downcast::<BoxLayout>()
+            .unwrap();
+        layout_manager.set_orientation(Orientation::Vertical);
+
+        this.set_hexpand(true);
+        this.set_vexpand(true);
+        this.set_overflow(Overflow::Hidden);
+
+        let breadcrumbs = gtk::Box::new(Orientation::Horizontal, 0);
+        breadcrumbs.set_valign(Align::Start);
+        breadcrumbs.set_parent(this);
+        self.breadcrumbs.set(breadcrumbs).unwrap();
+
+        let click_controller = GestureClick::new();
+        click_controller.set_button(1);
+        click_controller.connect_released(zoom_to_clicked_frame);
+        this.add_controller(&click_controller);
+
+        let menu_controller = GestureClick::new();
+        menu_controller.set_button(3);
+        menu_controller.connect_pressed(popup_transform_menu_at_frame);
+        this.add_controller(&menu_controller);
+
+        this.set_has_tooltip(true);
+        this.connect_query_tooltip(|this, x, y, _, tooltip| {
+            let text = this.tooltip_text_at(x as f32, y as f32);
+            tooltip.set_text(text.as_deref());
+            text.is_some()
+        });
+    }
+
+    fn dispose(&self, _: &Self::Type) {
+        if let Some(breadcrumbs) = self.breadcrumbs.get() {
+            breadcrumbs.unparent();
+        }
+    }
+
+    fn properties() -> &'static [ParamSpec] {
+        Self::derived_properties()
+    }
+    fn set_property(&self, this: &Self::Type, id: usize, value: &Value, pspec: &ParamSpec) {
+        Self::derived_set_property(self, this, id, value, pspec).unwrap();
+    }
+    fn property(&self, this: &Self::Type, id: usize, pspec: &ParamSpec) -> Value {
+        Self::derived_property(self, this, id, pspec).unwrap()
+    }
+}
+
+impl WidgetImpl for FlameGraphPrivate {
+    fn snapshot(&self, this: &Self::Type, snapshot: &Snapshot) {
+        self.parent_snapshot(this, snapshot);
+
+        let mut drawn_frames = self.drawn_frames.borrow_mut();
+        drawn_frames.clear();
+
+        let profile = self.profile.borrow();
+        let (profile, tree) = match &*profile {
+            Some((profile, tree)) => (profile, tree),
+            None => return,
+        };
+
+        let top = self.breadcrumbs.get().unwrap().height() as f32 + 6.0;
+        let height = this.height() as f32;
+        let merged_color = RGBA::new(0.5, 0.5, 0.5, 0.5);
+        // While searching, frames of functions that don't match are faded out
+        let search_matches = self
+            .timeline
+            .borrow()
+            .as_ref()
+            .and_then(|timeline| timeline.search_matches());
+
+        // Only frames at least MIN_FRAME_WIDTH wide are visited, so the work done is bounded
+        // by the widget's size rather than by the number of unique stacks
+        let mut pending = vec![(self.zoom_node.get(), 0.0, this.width() as f32, 0)];
+        while let Some((node, x, width, depth)) = pending.pop() {
+            let y = top + depth as f32 * FRAME_HEIGHT;
+            if y > height {
+                continue;
+            }
+
+            let tree_node = tree.node(node);
+            let name = node_name(profile, tree, node);
+            let category = match node {
+                ROOT_NODE => Category::Unknown,
+                _ => Category::of(&name, profile.strings.get(tree_node.module)),
+            };
+            let mut color = category_color(category);
+            if let Some(search_matches) = &search_matches {
+                if !search_matches.matches_function((tree_node.function, tree_node.module)) {
+                    color.set_alpha(0.3);
+                }
+            }
+            let rect = Rect::new(x, y, width - 1.0, FRAME_HEIGHT - 1.0);
+            append_frame(this, snapshot, &name, &color, &rect);
+
+            drawn_frames.push(DrawnFrame {
+                rect,
+                node: Some(node),
+                merged_count: 0,
+                weight: tree_node.total_weight,
+            });
+
+            // Children are sorted heaviest first, so once one is too narrow the rest are too
+            let weight_to_width = width / tree_node.total_weight.max(1) as f32;
+            let mut child_x = x;
+            for (i, &child) in tree_node.children.iter().enumerate() {
+                let child_weight = tree.node(child).total_weight;
+                let child_width = child_weight as f32 * weight_to_width;
+                if child_width >= MIN_FRAME_WIDTH {
+                    pending.push((child, child_x, child_width, depth + 1));
+                    child_x += child_width;
+                    continue;
+                }
+
+                let merged_weight = tree_node.total_weight
+                    - tree_node.self_weight
+                    - tree_node.children[..i]
+                        .iter()
+                        .map(|&child| tree.node(child).total_weight)
+                        .sum::<u64>();
+                let merged_width = merged_weight as f32 * weight_to_width;
+                if merged_width >= MIN_FRAME_WIDTH {
+                    let rect =
+                        Rect::new(child_x, y + FRAME_HEIGHT, merged_width, FRAME_HEIGHT - 1.0);
+                    snapshot.append_color(&merged_color, &rect);
+                    drawn_frames.push(DrawnFrame {
+                        rect,
+                        node: None,
+                        merged_count: tree_node.children.len() - i,
+                        weight: merged_weight,
+                    });
+                }
+                break;
+            }
+        }
+    }
+}
@@ -1,12 +1,33 @@
+mod analysis;
 mod application_window;
+mod butterfly_view;
+mod call_tree;
+mod category_summary;
+mod cpu_lane;
+mod cpu_usage_graph;
+mod diff_flame_graph;
+mod diff_page;
+mod disassembly_view;
+mod flame_graph;
+mod marker_list;
 mod perf_data_parser;
+mod process_row;
+mod profile_loader;
 mod profile_page;
 mod profile_page_view;
+mod profile_search_bar;
 mod profile_setup_page;
+mod sample_inspector;
+mod source_view;
+mod stack_transform_bar;
+mod timeline_minimap;
 mod timeline_range;
 mod timeline_row;
 mod timeline_ticker;
 mod timeline_view;
+mod tracepoint_list;
+mod transform_menu;
+mod wtf_profile;
 
 use crate::application_window::ApplicationWindow;
 use adw::Application;
@@ -0,0 +1,364 @@
+use crate::timeline_range::TimelineRangePrivatePropertiesExt;
+use crate::timeline_ticker::format_time;
+use crate::timeline_view::{marker_color, TimelineView, TimelineViewPrivatePropertiesExt};
+use crate::wtf_profile::{read_marker_sidecar, write_wtf_profile, Marker, Profile};
+use adw::subclass::prelude::BinImpl;
+use adw::traits::BinExt;
+use adw::Bin;
+use glib::once_cell::sync::OnceCell;
+use glib::subclass::prelude::{
+    DerivedObjectProperties, ObjectImpl, ObjectImplExt, ObjectSubclass, ObjectSubclassExt,
+    ObjectSubclassIsExt,
+};
+use glib::{
+    clone, object_subclass, Cast, MainContext, ObjectExt, ParamSpec, Properties, StaticType, Value,
+};
+use gtk::pango::EllipsizeMode;
+use gtk::prelude::NativeDialogExtManual;
+use gtk::subclass::prelude::{WidgetClassSubclassExt, WidgetImpl};
+use gtk::traits::{
+    BoxExt, ButtonExt, EditableExt, EntryExt, FileChooserExt, ListBoxRowExt, NativeDialogExt,
+    WidgetExt,
+};
+use gtk::{
+    Accessible, Buildable, Button, ConstraintTarget, Entry, FileChooserAction, FileChooserNative,
+    FileFilter, Label, ListBox, Orientation, ResponseType, ScrolledWindow, SelectionMode, Widget,
+    Window,
+};
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+glib::wrapper! {
+    pub struct MarkerList(ObjectSubclass<MarkerListPrivate>)
+    @extends Bin, Widget,
+    @implements Accessible, Buildable, ConstraintTarget;
+}
+
+impl MarkerList {
+    fn rebuild(&self) {
+        let list = self.imp().list.get().unwrap();
+        while let Some(row) = list.first_child() {
+            list.remove(&row);
+        }
+
+        let timeline = match self.timeline() {
+            Some(timeline) => timeline,
+            None => return,
+        };
+        let markers = timeline.markers();
+        list.set_visible(!markers.is_empty());
+        for (index, marker) in markers.iter().enumerate() {
+            list.append(&new_row(&timeline, marker, index));
+        }
+    }
+
+    fn update_add_button(&self) {
+        let has_selection = self
+            .timeline()
+            .and_then(|timeline| timeline.selected_time_range())
+            .is_some();
+        self.imp()
+            .add_button
+            .get()
+            .unwrap()
+            .set_sensitive(has_selection);
+    }
+
+    // Named after the entry, or numbered if it's empty
+    fn add_marker(&self) {
+        let timeline = match self.timeline() {
+            Some(timeline) => timeline,
+            None => return,
+        };
+        let entry = self.imp().name_entry.get().unwrap();
+        let name = match entry.text().trim() {
+            "" => format!("Marker {}", timeline.marker_count() + 1),
+            name => name.to_owned(),
+        };
+        if timeline.add_marker_from_selection(&name).is_some() {
+            entry.set_text("");
+        }
+    }
+
+    async fn import_markers(&self) {
+        let timeline = match self.timeline() {
+            Some(timeline) => timeline,
+            None => return,
+        };
+        let path = self
+            .choose_file("Import Markers", FileChooserAction::Open, None)
+            .await;
+        let path = match path {
+            Some(path) => path,
+            None => return,
+        };
+        match read_marker_sidecar(&path) {
+            Ok(markers) => {
+                let marker_count = markers.len();
+                timeline.add_markers(markers);
+                self.show_status(&format!("Imported {marker_count} markers"));
+            }
+            Err(error) => self.show_status(&format!("Failed to import markers: {error}")),
+        }
+    }
+
+    // .wtf profiles are saved in place, profiles converted from other formats are saved as a
+    // new .wtf profile
+    async fn save_markers(&self) {
+        let profile_path = PathBuf::from(self.profile_path());
+        let timeline = match self.timeline() {
+            Some(timeline) => timeline,
+            None => return,
+        };
+        let profile = match timeline.profile() {
+            Some(profile) => profile.borrow::<Rc<Profile>>().clone(),
+            None => return,
+        };
+
+        let save_path = if profile_path.extension().map_or(false, |e| e == "wtf") {
+            profile_path
+        } else {
+            let file_name = profile_path.with_extension("wtf");
+            let file_name = file_name.file_name().and_then(|name| name.to_str());
+            match self
+                .choose_file("Save Profile", FileChooserAction::Save, file_name)
+                .await
+            {
+                Some(save_path) => save_path,
+                None => return,
+            }
+        };
+
+        match write_wtf_profile(&profile, &timeline.markers(), &save_path) {
+            Ok(()) => self.show_status(&format!("Saved to {}", display_name(&save_path))),
+            Err(error) => self.show_status(&format!("Failed to save profile: {error}")),
+        }
+    }
+
+    async fn choose_file(
+        &self,
+        title: &str,
+        action: FileChooserAction,
+        file_name: Option<&str>,
+    ) -> Option<PathBuf> {
+        let parent_window = self.root().and_then(|root| root.downcast::<Window>().ok());
+        let file_chooser =
+            FileChooserNative::new(Some(title), parent_window.as_ref(), action, None, None);
+        file_chooser.set_modal(true);
+
+        let file_filter = FileFilter::new();
+        let pattern = match action {
+            FileChooserAction::Save => "*.wtf",
+            _ => "*.jsonl",
+        };
+        file_filter.set_name(Some(pattern));
+        file_filter.add_pattern(pattern);
+        file_chooser.add_filter(&file_filter);
+        if let Some(file_name) = file_name {
+            file_chooser.set_current_name(file_name);
+        }
+
+        let response = file_chooser.run_future().await;
+        if response == ResponseType::Accept {
+            file_chooser.file().unwrap().path()
+        } else {
+            None
+        }
+    }
+
+    fn show_status(&self, status: &str) {
+        let status_label = self.imp().status.get().unwrap();
+        status_label.set_label(status);
+        status_label.set_visible(true);
+    }
+}
+
+fn new_row(timeline: &TimelineView, marker: &Marker, index: usize) -> gtk::Box {
+    let color = marker_color(timeline);
+    let swatch = Label::new(None);
+    swatch.set_markup(&format!(
+        "<span foreground=\"#{:02x}{:02x}{:02x}\">{}</span>",
+        (color.red() * 255.0) as u8,
+        (color.green() * 255.0) as u8,
+        (color.blue() * 255.0) as u8,
+        if marker.duration > 0 { "▬" } else { "│" },
+    ));
+
+    let name = Label::new(Some(&marker.name));
+    name.set_xalign(0.0);
+    name.set_hexpand(true);
+    name.set_ellipsize(EllipsizeMode::End);
+    name.set_tooltip_text(Some(&marker.name));
+
+    // Precise to a pixel at the current zoom
+    let resolution =
+        (timeline.display_time_range().duration() / timeline.width().max(1) as u64).max(1);
+    let time = timeline.format_time_label(marker.start, resolution);
+    let time = match marker.duration {
+        0 => time,
+        duration => format!("{time}, {}", format_time(duration, resolution)),
+    };
+    let time = Label::new(Some(&time));
+    time.add_css_class("numeric");
+    time.add_css_class("dim-label");
+
+    let remove_button = Button::from_icon_name("user-trash-symbolic");
+    remove_button.set_tooltip_text(Some("Remove Marker"));
+    remove_button.add_css_class("flat");
+    remove_button.connect_clicked(clone!(@weak timeline => move |_| timeline.remove_marker(index)));
+
+    let row = gtk::Box::new(Orientation::Horizontal, 6);
+    row.append(&swatch);
+    row.append(&name);
+    row.append(&time);
+    row.append(&remove_button);
+    row
+}
+
+fn display_name(path: &Path) -> String {
+    path.file_name()
+        .unwrap_or(path.as_os_str())
+        .to_string_lossy()
+        .into_owned()
+}
+
+// ------------------------------------------------------------------------------
+
+#[derive(Properties, Default)]
+pub struct MarkerListPrivate {
+    #[property(get, set = Self::set_timeline, builder(TimelineView::static_type()))]
+    timeline: RefCell<Option<TimelineView>>,
+    // Where the profile was loaded from, for saving markers into it
+    #[property(get, set)]
+    profile_path: RefCell<String>,
+
+    name_entry: OnceCell<Entry>,
+    add_button: OnceCell<Button>,
+    status: OnceCell<Label>,
+    list: OnceCell<ListBox>,
+}
+
+impl MarkerListPrivate {
+    pub fn set_timeline(&self, value: Option<TimelineView>) {
+        let this = self.instance();
+        if let Some(timeline) = &value {
+            // Marker times are labelled the same way as the timeline's
+            for property in ["marker-count", "time-of-day"] {
+                timeline.connect_notify_local(
+                    Some(property),
+                    clone!(@weak this => move |_, _| this.rebuild()),
+                );
+            }
+            timeline.connect_notify_local(
+                Some("selected-time-range"),
+                clone!(@weak this => move |_, _| this.update_add_button()),
+            );
+        }
+
+        *self.timeline.borrow_mut() = value;
+        this.rebuild();
+        this.update_add_button();
+    }
+}
+
+#[object_subclass]
+impl ObjectSubclass for MarkerListPrivate {
+    const NAME: &'static str = "WtfMarkerList";
+    type Type = MarkerList;
+    type ParentType = Bin;
+
+    fn class_init(klass: &mut Self::Class) {
+        klass.set_css_name("marker-list");
+    }
+}
+
+impl ObjectImpl for MarkerListPrivate {
+    fn constructed(&self, this: &Self::Type) {
+        self.parent_constructed(this);
+
+        let title = Label::new(Some("Markers"));
+        title.set_xalign(0.0);
+        title.set_hexpand(true);
+        title.add_css_class("heading");
+
+        let import_button = Button::from_icon_name("document-open-symbolic");
+        import_button.set_tooltip_text(Some("Import Markers from a JSON Lines File"));
+        import_button.add_css_class("flat");
+        import_button.connect_clicked(clone!(@weak this => move |_| {
+            MainContext::default().spawn_local(async move { this.import_markers().await });
+        }));
+
+        let save_button = Button::from_icon_name("document-save-symbolic");
+        save_button.set_tooltip_text(Some("Save Markers with the Profile"));
+        save_button.add_css_class("flat");
+        save_button.connect_clicked(clone!(@weak this => move |_| {
+            MainContext::default().spawn_local(async move { this.save_markers().await });
+        }));
+
+        let header = gtk::Box::new(Orientation::Horizontal, 6);
+        header.append(&title);
+        header.append(&import_button);
+        header.append(&save_button);
+
+        // Markers span the selected time
+        let name_entry = Entry::new();
+        name_entry.set_placeholder_text(Some("Marker Name"));
+        name_entry.set_hexpand(true);
+        name_entry.connect_activate(clone!(@weak this => move |_| this.add_marker()));
+
+        let add_button = Button::with_label("Add Marker");
+        add_button.set_tooltip_text(Some("Mark the selected time"));
+        add_button.connect_clicked(clone!(@weak this => move |_| this.add_marker()));
+
+        let add_row = gtk::Box::new(Orientation::Horizontal, 6);
+        add_row.append(&name_entry);
+        add_row.append(&add_button);
+
+        let status = Label::new(None);
+        status.set_xalign(0.0);
+        status.set_ellipsize(EllipsizeMode::End);
+        status.add_css_class("dim-label");
+        status.set_visible(false);
+
+        // Activating a marker shows it on the timeline
+        let list = ListBox::new();
+        list.set_selection_mode(SelectionMode::None);
+        list.add_css_class("boxed-list");
+        list.connect_row_activated(clone!(@weak this => move |_, row| {
+            if let Some(timeline) = this.timeline() {
+                timeline.show_marker(row.index() as usize);
+            }
+        }));
+
+        let scrolled_window = ScrolledWindow::new();
+        scrolled_window.set_child(Some(&list));
+        scrolled_window.set_max_content_height(200);
+        scrolled_window.set_propagate_natural_height(true);
+
+        let content = gtk::Box::new(Orientation::Vertical, 6);
+        content.append(&header);
+        content.append(&add_row);
+        content.append(&status);
+        content.append(&scrolled_window);
+        this.set_child(Some(&content));
+
+        self.name_entry.set(name_entry).unwrap();
+        self.add_button.set(add_button).unwrap();
+        self.status.set(status).unwrap();
+        self.list.set(list).unwrap();
+    }
+
+    fn properties() -> &'static [ParamSpec] {
+        Self::derived_properties()
+    }
+    fn set_property(&self, this: &Self::Type, id: usize, value: &Value, pspec: &ParamSpec) {
+        Self::derived_set_property(self, this, id, value, pspec).unwrap();
+    }
+    fn property(&self, this: &Self::Type, id: usize, pspec: &ParamSpec) -> Value {
+        Self::derived_property(self, this, id, pspec).unwrap()
+    }
+}
+
+impl WidgetImpl for MarkerListPrivate {}
+impl BinImpl for MarkerListPrivate {}
@@ -1,18 +1,18 @@
 use super::perf_data_parser::ReadExt;
 use bitflags::bitflags;
 use std::io::Error as IOError;
+use std::path::Path;
 
 pub trait ReadMmap2EventExt: ReadExt {
-    fn read_mmap2_event(&mut self, object_path: &str) -> Result<(u64, Option<u64>), IOError> {
+    fn read_mmap2_event(&mut self) -> Result<(u64, Mmap2Event), IOError> {
         let mut bytes_read = 65;
-        let mut object_base_address = None;
 
         // TODO: properly parse some of these
         let _pid = self.read_u32()?;
         let _tid = self.read_u32()?;
         let addr = self.read_u64()?;
         let _len = self.read_u64()?;
-        let _pgoff = self.read_u64()?;
+        let pgoff = self.read_u64()?;
         let _maj = self.read_u32()?;
         let _min = self.read_u32()?;
         let _ino = self.read_u64()?;
@@ -29,10 +29,6 @@ pub trait ReadMmap2EventExt: ReadExt {
                 break;
             }
         }
-        // TODO: should I use the filename, or whole path?
-        if filename.contains(object_path) && prot.contains(MemoryProtection::PROT_EXEC) {
-            object_base_address = Some(addr);
-        }
         // let _sample_id = todo!();
         // struct sample_id {
         //     { u32 pid, tid; }   /* if PERF_SAMPLE_TID set */
@@ -42,7 +38,42 @@ pub trait ReadMmap2EventExt: ReadExt {
         //     { u32 cpu, res; }   /* if PERF_SAMPLE_CPU set */
         //     { u64 id;       }   /* if PERF_SAMPLE_IDENTIFIER set */
         // };
-        Ok((bytes_read, object_base_address))
+        let event = Mmap2Event {
+            address: addr,
+            offset: pgoff,
+            executable: prot.contains(MemoryProtection::PROT_EXEC),
+            filename,
+        };
+        Ok((bytes_read, event))
+    }
+}
+
+// A file mapped into a process
+pub struct Mmap2Event {
+    pub address: u64,
+    // Of the mapping within the file
+    pub offset: u64,
+    pub executable: bool,
+    pub filename: String,
+}
+
+impl Mmap2Event {
+    // Executable code from the file perf record ran, rather than e.g. a shared library, [vdso] or
+    // the kernel's [kernel.kallsyms]_text. Without perf's command line any mapped file counts.
+    pub fn is_program(&self, perf_cmdline: &[String]) -> bool {
+        if !self.executable || self.filename.starts_with('[') {
+            return false;
+        }
+        if perf_cmdline.is_empty() {
+            return true;
+        }
+        // perf's own path comes first, the profiled program's path is somewhere after its options,
+        // as typed, so either the full path or relative to the working directory or $PATH
+        let filename = Path::new(&self.filename);
+        perf_cmdline.iter().skip(1).any(|argument| {
+            let argument = Path::new(argument.strip_prefix("./").unwrap_or(argument));
+            !argument.as_os_str().is_empty() && filename.ends_with(argument)
+        })
     }
 }
 
@@ -1,5 +1,6 @@
 use super::perf_data_parser::{bit, ReadExt};
 use super::symbolicator::Symbolicator;
+use super::tracing_data::{TracepointEvent, TracingData};
 use bitflags::bitflags;
 use std::error::Error;
 
@@ -8,11 +9,14 @@ pub trait ReadSampleEventExt: ReadExt {
         &mut self,
         sample_type: SampleType,
         symbolicator: &mut Symbolicator,
+        tracing_data: Option<&TracingData>,
         mut process_sample: F,
     ) -> Result<u64, Box<dyn Error>> {
         let mut tid = None;
         let mut timestamp = None;
+        let mut cpu = None;
         let mut callchain = None;
+        let mut tracepoint = None;
 
         // TODO: Correctly parse this event (some fields not listed yet, or incomplete)
         let mut bytes_read = 0;
@@ -48,7 +52,7 @@ pub trait ReadSampleEventExt: ReadExt {
         }
         // TODO: Figure out how to enable this via perf record, dosen't seem to be on by default
         if sample_type.contains(SampleType::CPU) {
-            let _cpu = self.read_u32()?;
+            cpu = Some(self.read_u32()?);
             let _res = self.read_u32()?;
             bytes_read += 8;
         }
@@ -57,7 +61,7 @@ pub trait ReadSampleEventExt: ReadExt {
             bytes_read += 8;
         }
         if sample_type.contains(SampleType::READ) {
-            todo!();
+            return Err("Samples with read values (PERF_SAMPLE_READ) aren't supported".into());
         }
         if sample_type.contains(SampleType::CALLCHAIN) {
             let nr = self.read_u64()?;
@@ -70,11 +74,12 @@ pub trait ReadSampleEventExt: ReadExt {
             bytes_read += 8 * (nr + 1);
         }
         if sample_type.contains(SampleType::RAW) {
+            // size already includes the padding needed to keep the event u64 aligned
             let size = self.read_u32()?;
-            let n = ((size as i32 + 7) & (-8)) as u32;
-            for _ in 1..=n {
-                let _data = self.read_u8()?;
-            }
+            let mut raw_data = vec![0; size as usize];
+            self.read_exact(&mut raw_data)?;
+            tracepoint = tracing_data.and_then(|t| t.decode_raw_sample(&raw_data));
+            bytes_read += 4 + size as u64;
         }
         // TODO: Uncomment
         // if sample_type.contains(SampleType::BRANCH_STACK) {
@@ -112,16 +117,18 @@ pub trait ReadSampleEventExt: ReadExt {
         //     todo!();
         // }
 
-        if tid.is_some() && timestamp.is_some() && callchain.is_some() {
-            let stacktrace = callchain
-                .unwrap()
-                .into_iter()
-                .map(|ip| symbolicator.lookup_symbol(ip))
-                .collect::<Result<_, _>>()?;
+        // Tracepoint samples are still useful without a callchain
+        if tid.is_some() && timestamp.is_some() && (callchain.is_some() || tracepoint.is_some()) {
+            let stacktrace = match callchain {
+                Some(callchain) => Some(symbolicate_callchain(callchain, symbolicator)?),
+                None => None,
+            };
             let sample = Sample {
                 tid: tid.unwrap(),
                 timestamp: timestamp.unwrap(),
+                cpu,
                 stacktrace,
+                tracepoint,
             };
             (process_sample)(sample);
         }
@@ -130,10 +137,54 @@ pub trait ReadSampleEventExt: ReadExt {
     }
 }
 
+fn symbolicate_callchain(
+    callchain: Vec<u64>,
+    symbolicator: &mut Symbolicator,
+) -> Result<Box<[StackFrame]>, Box<dyn Error>> {
+    let mut stacktrace = Vec::new();
+    // Each context's part of the callchain starts with the address it was interrupted at, the
+    // rest are return addresses
+    let mut leaf = true;
+    for ip in callchain {
+        // Markers between the kernel and user parts of a callchain aren't addresses
+        if ip >= PERF_CONTEXT_MAX {
+            leaf = true;
+            continue;
+        }
+        // Callers' addresses are return addresses, so look up the call instruction before them instead
+        let call_ip = if leaf { ip } else { ip.saturating_sub(1) };
+        leaf = false;
+        for frame in symbolicator.lookup_frames(call_ip)? {
+            stacktrace.push(StackFrame {
+                address: ip,
+                function: frame.function,
+                file: frame.file,
+                line: frame.line,
+            });
+        }
+    }
+    Ok(stacktrace.into_boxed_slice())
+}
+
+// Callchain entries from here up are PERF_CONTEXT_* markers
+const PERF_CONTEXT_MAX: u64 = -4095i64 as u64;
+
 pub struct Sample {
     pub tid: u32,
     pub timestamp: u64,
-    pub stacktrace: Box<[String]>,
+    pub cpu: Option<u32>,
+    // None if the sample wasn't recorded with a callchain
+    pub stacktrace: Option<Box<[StackFrame]>>,
+    pub tracepoint: Option<TracepointEvent>,
+}
+
+// Functions inlined at an address get a frame each, sharing the address
+pub struct StackFrame {
+    pub address: u64,
+    pub function: String,
+    pub file: Option<String>,
+    // 0 if unknown
+    pub line: u32,
 }
 
 bitflags! {
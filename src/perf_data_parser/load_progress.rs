@@ -0,0 +1,83 @@
+use std::io::{Error as IOError, ErrorKind, Read};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+// Shared between the thread converting a profile and the UI thread displaying its progress
+#[derive(Default)]
+pub struct LoadProgress {
+    bytes_processed: AtomicU64,
+    total_bytes: AtomicU64,
+    records_processed: AtomicU64,
+    cancelled: AtomicBool,
+}
+
+impl LoadProgress {
+    pub fn bytes_processed(&self) -> u64 {
+        self.bytes_processed.load(Ordering::Relaxed)
+    }
+
+    pub fn total_bytes(&self) -> u64 {
+        self.total_bytes.load(Ordering::Relaxed)
+    }
+
+    pub fn records_processed(&self) -> u64 {
+        self.records_processed.load(Ordering::Relaxed)
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    pub(super) fn set_total_bytes(&self, total_bytes: u64) {
+        self.total_bytes.store(total_bytes, Ordering::Relaxed);
+    }
+
+    pub(super) fn set_bytes_processed(&self, bytes_processed: u64) {
+        self.bytes_processed
+            .store(bytes_processed, Ordering::Relaxed);
+    }
+
+    pub(super) fn add_bytes_processed(&self, bytes: u64) {
+        self.bytes_processed.fetch_add(bytes, Ordering::Relaxed);
+    }
+
+    pub(super) fn add_record(&self) {
+        self.records_processed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    // Parsers call this regularly so that they stop soon after being cancelled
+    pub(super) fn check_cancelled(&self) -> Result<(), IOError> {
+        if self.is_cancelled() {
+            Err(IOError::new(
+                ErrorKind::Other,
+                "Profile loading was cancelled",
+            ))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+// Counts bytes as they're read, and fails the read once loading is cancelled
+pub(super) struct ProgressReader<'a, R> {
+    reader: R,
+    progress: &'a LoadProgress,
+}
+
+impl<'a, R: Read> ProgressReader<'a, R> {
+    pub(super) fn new(reader: R, progress: &'a LoadProgress) -> Self {
+        Self { reader, progress }
+    }
+}
+
+impl<R: Read> Read for ProgressReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize, IOError> {
+        self.progress.check_cancelled()?;
+        let bytes_read = self.reader.read(buf)?;
+        self.progress.add_bytes_processed(bytes_read as u64);
+        Ok(bytes_read)
+    }
+}
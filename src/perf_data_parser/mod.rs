@@ -1,8 +1,12 @@
 mod event_mmap2;
 mod event_sample;
+mod load_progress;
 mod perf_data_parser;
 mod perf_json_parser;
 mod symbolicator;
+mod tracing_data;
 
+pub use load_progress::LoadProgress;
 pub use perf_data_parser::convert_perf_data_to_wtf;
-pub use perf_json_parser::*;
+pub use perf_json_parser::convert_perf_json_to_wtf;
+pub use symbolicator::{DisassembledInstruction, Disassembly, Symbolicator};
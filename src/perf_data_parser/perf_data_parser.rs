@@ -1,32 +1,155 @@
-use super::event_mmap2::ReadMmap2EventExt;
+use super::event_mmap2::{Mmap2Event, ReadMmap2EventExt};
 use super::event_sample::{ReadSampleEventExt, Sample, SampleType};
-use super::symbolicator::Symbolicator;
+use super::load_progress::LoadProgress;
+use super::symbolicator::{load_bias, Symbolicator};
+use super::tracing_data::TracingData;
+use crate::wtf_profile::{
+    Frame, Profile, ProfileBuilder, Sample as WtfSample, Tracepoint, EMPTY_STRING, UNKNOWN_CPU,
+};
 use bitflags::bitflags;
 use std::error::Error;
 use std::fs::File;
 use std::io::{BufReader, Error as IOError, ErrorKind, Read, Seek, SeekFrom};
-use std::path::Path;
-
-pub fn convert_perf_data_to_wtf<P1: AsRef<Path>, P2: AsRef<Path> + Copy>(
-    perf_data_path: P1,
-    binary_profiled_path: P2,
-) -> Result<(), Box<dyn Error>> {
-    let mut file = BufReader::new(File::open(perf_data_path)?);
+use std::path::{Path, PathBuf};
+
+// The profiled program is the first mapped executable file that perf record ran, going by the
+// command line it recorded. Only its frames are symbolicated.
+pub fn convert_perf_data_to_wtf<P: AsRef<Path>>(
+    perf_data_path: P,
+    progress: &LoadProgress,
+) -> Result<Profile, Box<dyn Error>> {
+    let file = File::open(perf_data_path)?;
+    progress.set_total_bytes(file.metadata()?.len());
+    let mut file = BufReader::new(file);
 
     let header = file.read_header()?;
     let attributes = file.read_attribute_section(&header)?;
+    let extra_headers = file.read_extra_headers(&header)?;
 
     // TODO: Pass all attributes to read_data_section(), pick the correct one for each event
     let sample_type = attributes
         .get(0)
         .ok_or_else(|| IOError::new(ErrorKind::InvalidData, "No attributes found"))?
         .sample_type;
-    file.read_data_section(&header, sample_type, binary_profiled_path, |sample| {
-        // TODO: This should send sample to be processed on another thread
-        dbg!(sample.stacktrace);
-    })?;
 
-    Ok(())
+    let mut builder = ProfileBuilder::new();
+    let mut module = None;
+    let program = file.read_data_section(
+        &header,
+        sample_type,
+        extra_headers.tracing_data,
+        &extra_headers.perf_cmdline,
+        progress,
+        |sample, program_path| {
+            let module = *module.get_or_insert_with(|| {
+                let module = program_path
+                    .file_name()
+                    .and_then(|name| name.to_str())
+                    .unwrap_or_default();
+                builder.intern_string(module)
+            });
+            let cpu = sample.cpu.unwrap_or(UNKNOWN_CPU);
+
+            if let Some(tracepoint) = &sample.tracepoint {
+                let name = builder.intern_string(&tracepoint.name());
+                let fields = tracepoint
+                    .fields()
+                    .map(|(field_name, value)| {
+                        let field_name = builder.intern_string(field_name);
+                        (field_name, builder.intern_string(&value.to_string()))
+                    })
+                    .collect();
+                builder.add_tracepoint(Tracepoint {
+                    time: sample.timestamp,
+                    tid: sample.tid,
+                    cpu,
+                    name,
+                    fields,
+                });
+            }
+
+            // Tracepoints recorded without a callchain have nothing to attribute a sample to
+            let stacktrace = match &sample.stacktrace {
+                Some(stacktrace) => stacktrace,
+                None => return,
+            };
+            let callchain = stacktrace
+                .iter()
+                .map(|stack_frame| {
+                    let file = match &stack_frame.file {
+                        Some(file) => builder.intern_string(file),
+                        None => EMPTY_STRING,
+                    };
+                    let frame = Frame {
+                        address: stack_frame.address,
+                        function: builder.intern_string(&stack_frame.function),
+                        module,
+                        file,
+                        line: stack_frame.line,
+                    };
+                    builder.intern_frame(frame)
+                })
+                .collect::<Vec<_>>();
+            let stack = builder.intern_callchain(callchain);
+            builder.add_sample(WtfSample {
+                time: sample.timestamp,
+                tid: sample.tid,
+                cpu,
+                stack,
+                weight: 1,
+            });
+        },
+    )?;
+
+    let recording = Recording {
+        program,
+        clock_data: extra_headers.clock_data,
+    };
+    recording.add_to_profile(&mut builder);
+    Ok(builder.finish())
+}
+
+// What a recording knows beyond its samples
+pub struct Recording {
+    // The profiled program's path and load bias
+    pub program: Option<(PathBuf, u64)>,
+    clock_data: Option<ClockData>,
+}
+
+impl Recording {
+    pub fn add_to_profile(&self, builder: &mut ProfileBuilder) {
+        // Lets the profiled binary be symbolicated again later, e.g. to disassemble it
+        if let Some((binary_path, binary_load_bias)) = &self.program {
+            builder.add_metadata("binary-path", &binary_path.to_string_lossy());
+            builder.add_metadata("binary-base-address", &binary_load_bias.to_string());
+        }
+
+        // Lets sample times be shown as the time of day they were taken at
+        if let Some(clock_data) = &self.clock_data {
+            builder.add_metadata("clock-wall-time", &clock_data.wall_clock_ns.to_string());
+            builder.add_metadata("clock-perf-time", &clock_data.clockid_time_ns.to_string());
+        }
+    }
+}
+
+// Only reads the headers and the events up to the profiled program's mapping, for when the samples
+// come from elsewhere, e.g. the perf.json the recording was converted to
+pub fn read_recording<P: AsRef<Path>>(perf_data_path: P) -> Result<Recording, Box<dyn Error>> {
+    let mut file = BufReader::new(File::open(perf_data_path)?);
+    let header = file.read_header()?;
+    let extra_headers = file.read_extra_headers(&header)?;
+    Ok(Recording {
+        program: file.find_program(&header, &extra_headers.perf_cmdline)?,
+        clock_data: extra_headers.clock_data,
+    })
+}
+
+// The profiled program's path, and its load bias from where it was mapped
+fn program_load_bias(mapping: Mmap2Event) -> Result<(PathBuf, u64), Box<dyn Error>> {
+    let program_path = PathBuf::from(mapping.filename);
+    let bias = load_bias(&program_path, mapping.address, mapping.offset)
+        .map_err(|error| format!("Failed to load {}: {error}", program_path.display()))?;
+    Ok((program_path, bias))
 }
 
 impl ReadExt for BufReader<File> {}
@@ -36,13 +159,21 @@ impl ReadSampleEventExt for BufReader<File> {}
 trait ReadSectionExt: ReadExt + ReadMmap2EventExt + ReadSampleEventExt {
     fn read_attribute_section(&mut self, header: &Header) -> Result<Vec<Attribute>, IOError>;
 
-    fn read_data_section<P: AsRef<Path> + Copy, F: FnMut(Sample)>(
+    fn read_data_section<F: FnMut(Sample, &Path)>(
         &mut self,
         header: &Header,
         sample_type: SampleType,
-        binary_profiled_path: P,
+        tracing_data: Option<TracingData>,
+        perf_cmdline: &[String],
+        progress: &LoadProgress,
         process_sample: F,
-    ) -> Result<(), Box<dyn Error>>;
+    ) -> Result<Option<(PathBuf, u64)>, Box<dyn Error>>;
+
+    fn find_program(
+        &mut self,
+        header: &Header,
+        perf_cmdline: &[String],
+    ) -> Result<Option<(PathBuf, u64)>, Box<dyn Error>>;
 }
 
 impl ReadSectionExt for BufReader<File> {
@@ -73,57 +204,110 @@ impl ReadSectionExt for BufReader<File> {
 
     // TODO: How to generically structure specifying which events and fields the caller is interested in?
     // and then seek past the rest
-    fn read_data_section<P: AsRef<Path> + Copy, F: FnMut(Sample)>(
+    // Samples are passed along with the path of the profiled program
+    // Returns the profiled program's path and load bias, if it was found
+    fn read_data_section<F: FnMut(Sample, &Path)>(
         &mut self,
         header: &Header,
         sample_type: SampleType,
-        binary_profiled_path: P,
+        mut tracing_data: Option<TracingData>,
+        perf_cmdline: &[String],
+        progress: &LoadProgress,
         mut process_sample: F,
-    ) -> Result<(), Box<dyn Error>> {
+    ) -> Result<Option<(PathBuf, u64)>, Box<dyn Error>> {
         self.seek(SeekFrom::Start(header.data_section.offset))?;
 
         let mut bytes_read = 0;
         let mut symbolicator = None;
+        let mut program = None;
 
         while bytes_read < header.data_section.size {
+            progress.check_cancelled()?;
+            progress.set_bytes_processed(header.data_section.offset + bytes_read);
+
             let event_header = self.read_event_header()?;
 
+            let mut event_size = event_header.event_size as u64;
             let mut event_bytes_read = 0;
             match event_header.event_type {
                 // TODO: Parse the rest of the event types
                 EventType::MMAP2 => {
-                    // TODO: Cleanup this part
-                    let (bytes, object_base_address) = self.read_mmap2_event(
-                        binary_profiled_path
-                            .as_ref()
-                            .file_name()
-                            .unwrap()
-                            .to_str()
-                            .unwrap(),
-                    )?;
+                    let (bytes, mapping) = self.read_mmap2_event()?;
                     event_bytes_read = bytes;
-                    if let Some(object_base_address) = object_base_address {
-                        symbolicator = Some(Symbolicator::new(
-                            binary_profiled_path,
-                            object_base_address,
-                        )?);
+                    if program.is_none() && mapping.is_program(perf_cmdline) {
+                        let (program_path, bias) = program_load_bias(mapping)?;
+                        symbolicator = Some(Symbolicator::new(&program_path, bias)?);
+                        program = Some((program_path, bias));
                     }
                 }
                 EventType::SAMPLE if symbolicator.is_some() => {
+                    let program_path = &program.as_ref().unwrap().0;
                     event_bytes_read = self.read_sample_event(
                         sample_type,
                         symbolicator.as_mut().unwrap(),
-                        &mut process_sample,
+                        tracing_data.as_ref(),
+                        |sample| process_sample(sample, program_path),
                     )?;
+                    progress.add_record();
+                }
+                // Pipe mode files store tracing data in the data section instead of an extra header
+                EventType::HEADER_TRACING_DATA => {
+                    let size = self.read_u32()? as u64;
+                    let mut tracing_data_bytes = vec![0; size as usize];
+                    self.read_exact(&mut tracing_data_bytes)?;
+                    tracing_data = Some(TracingData::parse(&tracing_data_bytes)?);
+                    // The tracing data follows the event, padded to a u64 boundary, and isn't
+                    // counted in its size
+                    event_bytes_read = 4 + ((size + 7) & !7);
+                    event_size = 8 + event_bytes_read;
                 }
                 _ => {}
             }
 
-            self.seek_relative(event_header.event_size as i64 - 8 - event_bytes_read as i64)?;
-            bytes_read += event_header.event_size as u64;
+            // Sizes include the header, so reading past the end means the event is malformed
+            if event_size < 8 || event_bytes_read > event_size - 8 {
+                let error_message = format!(
+                    "Invalid perf.data event at offset {}: {event_bytes_read} bytes read from a \
+                     {event_size} byte event",
+                    header.data_section.offset + bytes_read
+                );
+                return Err(IOError::new(ErrorKind::InvalidData, error_message).into());
+            }
+            self.seek_relative(event_size as i64 - 8 - event_bytes_read as i64)?;
+            bytes_read += event_size;
         }
 
-        Ok(())
+        Ok(program)
+    }
+
+    fn find_program(
+        &mut self,
+        header: &Header,
+        perf_cmdline: &[String],
+    ) -> Result<Option<(PathBuf, u64)>, Box<dyn Error>> {
+        self.seek(SeekFrom::Start(header.data_section.offset))?;
+
+        let mut bytes_read = 0;
+        while bytes_read < header.data_section.size {
+            let event_header = self.read_event_header()?;
+            let event_size = event_header.event_size as u64;
+            let mut event_bytes_read = 0;
+            if event_header.event_type == EventType::MMAP2 {
+                let (bytes, mapping) = self.read_mmap2_event()?;
+                if mapping.is_program(perf_cmdline) {
+                    return Ok(Some(program_load_bias(mapping)?));
+                }
+                event_bytes_read = bytes;
+            }
+
+            if event_size < 8 || event_bytes_read > event_size - 8 {
+                return Err(IOError::new(ErrorKind::InvalidData, "Invalid perf.data event").into());
+            }
+            self.seek_relative(event_size as i64 - 8 - event_bytes_read as i64)?;
+            bytes_read += event_size;
+        }
+
+        Ok(None)
     }
 }
 
@@ -201,9 +385,75 @@ pub trait ReadExt: Read + Seek {
         })
     }
 
-    fn read_extra_headers(&mut self, header: &Header) -> Result<(), IOError> {
-        // TODO
-        Ok(())
+    fn read_extra_headers(&mut self, header: &Header) -> Result<ExtraHeaders, IOError> {
+        // Extra header sections are listed after the data section, one per feature bit set, in bit order
+        self.seek(SeekFrom::Start(
+            header.data_section.offset + header.data_section.size,
+        ))?;
+        let mut tracing_data_section = None;
+        let mut clock_data_section = None;
+        let mut cmdline_section = None;
+        for bit_index in 0..64 {
+            let feature = ExtraHeadersPresent::from_bits_truncate(bit(bit_index));
+            if feature.is_empty() || !header.extra_headers_present.contains(feature) {
+                continue;
+            }
+            let section = self.read_section_info()?;
+            if feature == ExtraHeadersPresent::TRACING_DATA {
+                tracing_data_section = Some(section);
+            } else if feature == ExtraHeadersPresent::CLOCK_DATA {
+                clock_data_section = Some(section);
+            } else if feature == ExtraHeadersPresent::CMDLINE {
+                cmdline_section = Some(section);
+            }
+        }
+        // TODO: Read other useful extra headers
+
+        let mut tracing_data = None;
+        if let Some(section) = tracing_data_section {
+            self.seek(SeekFrom::Start(section.offset))?;
+            let mut tracing_data_bytes = vec![0; section.size as usize];
+            self.read_exact(&mut tracing_data_bytes)?;
+            tracing_data = Some(TracingData::parse(&tracing_data_bytes)?);
+        }
+
+        // The wall clock time at one moment of perf's clock, recorded with perf record -k
+        let mut clock_data = None;
+        if let Some(section) = clock_data_section {
+            self.seek(SeekFrom::Start(section.offset))?;
+            let _version = self.read_u32()?;
+            let _clockid = self.read_u32()?;
+            clock_data = Some(ClockData {
+                wall_clock_ns: self.read_u64()?,
+                clockid_time_ns: self.read_u64()?,
+            });
+        }
+
+        // perf's argv, as a count followed by length prefixed strings, each NUL padded
+        let mut perf_cmdline = Vec::new();
+        if let Some(section) = cmdline_section {
+            self.seek(SeekFrom::Start(section.offset))?;
+            let argument_count = self.read_u32()?;
+            for _ in 0..argument_count {
+                let length = self.read_u32()?;
+                if length as u64 > section.size {
+                    return Err(IOError::new(
+                        ErrorKind::InvalidData,
+                        "Invalid perf command line",
+                    ));
+                }
+                let mut bytes = vec![0; length as usize];
+                self.read_exact(&mut bytes)?;
+                let argument = String::from_utf8_lossy(&bytes);
+                perf_cmdline.push(argument.trim_end_matches('\0').to_string());
+            }
+        }
+
+        Ok(ExtraHeaders {
+            tracing_data,
+            clock_data,
+            perf_cmdline,
+        })
     }
 }
 
@@ -214,6 +464,17 @@ pub struct Header {
     extra_headers_present: ExtraHeadersPresent,
 }
 
+pub struct ExtraHeaders {
+    tracing_data: Option<TracingData>,
+    clock_data: Option<ClockData>,
+    perf_cmdline: Vec<String>,
+}
+
+pub struct ClockData {
+    wall_clock_ns: u64,
+    clockid_time_ns: u64,
+}
+
 pub struct SectionInfo {
     offset: u64,
     size: u64,
@@ -308,6 +569,8 @@ enum EventType {
     TIME_CONV,
     HEADER_FEATURE,
     COMPRESSED,
+
+    UNKNOWN,
 }
 
 impl From<u32> for EventType {
@@ -354,7 +617,8 @@ impl From<u32> for EventType {
             79 => TIME_CONV,
             80 => HEADER_FEATURE,
             81 => COMPRESSED,
-            _ => unreachable!(),
+            // Newer kinds of events are skipped over
+            _ => UNKNOWN,
         }
     }
 }
@@ -1,44 +1,467 @@
-use serde::Deserialize;
+use super::load_progress::{LoadProgress, ProgressReader};
+use super::perf_data_parser::read_recording;
+use super::symbolicator::Symbolicator;
+use crate::wtf_profile::{
+    Frame, FrameId, Profile, ProfileBuilder, Sample, StringId, EMPTY_STRING, UNKNOWN_CPU,
+};
+use serde::de::{DeserializeSeed, Error as DeError, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer};
+use std::error::Error;
+use std::fmt::{Formatter, Result as FmtResult};
 use std::fs::File;
-use std::io::{BufReader, Error as IOError};
+use std::io::BufReader;
 use std::path::Path;
 
-pub fn convert_perf_json_to_wtf<P: AsRef<Path>>(path: P) -> Result<Profile, IOError> {
-    let reader = BufReader::new(File::open(path)?);
-    Ok(serde_json::from_reader::<_, Profile>(reader)?)
+// Samples are streamed straight into a ProfileBuilder as they're parsed, and every string is
+// interned as soon as it's read, so memory use grows with unique frames rather than file size
+pub fn convert_perf_json_to_wtf<P: AsRef<Path>>(
+    path: P,
+    progress: &LoadProgress,
+) -> Result<Profile, Box<dyn Error>> {
+    let path = path.as_ref();
+    let file = File::open(path)?;
+    progress.set_total_bytes(file.metadata()?.len());
+    let reader = BufReader::new(ProgressReader::new(file, progress));
+    let mut deserializer = serde_json::Deserializer::from_reader(reader);
+
+    let mut builder = ProfileBuilder::new();
+
+    // perf data convert leaves out source lines and clock times, so they're taken from the
+    // perf.data the perf.json was converted from when it's alongside, as it is for recordings made
+    // by the app. Without it, or the program it recorded, the profile loads without them.
+    let recording = read_recording(path.with_extension("data")).ok();
+    let mut program = recording.as_ref().and_then(|recording| {
+        let (program_path, bias) = recording.program.as_ref()?;
+        let module = program_path.file_name()?.to_str()?;
+        Some(ProgramSymbolicator {
+            module: builder.intern_string(module),
+            symbolicator: Symbolicator::new(program_path, *bias).ok()?,
+        })
+    });
+    if let Some(recording) = &recording {
+        recording.add_to_profile(&mut builder);
+    }
+
+    PerfJsonSeed {
+        builder: &mut builder,
+        program: program.as_mut(),
+        progress,
+    }
+    .deserialize(&mut deserializer)?;
+    deserializer.end()?;
+
+    Ok(builder.finish())
 }
 
-#[derive(Deserialize)]
-pub struct Profile {
-    pub headers: Headers,
-    pub samples: Vec<Sample>,
+// perf names the modules of frames by their file name
+struct ProgramSymbolicator {
+    module: StringId,
+    symbolicator: Symbolicator,
 }
 
 #[derive(Deserialize)]
 #[serde(rename_all = "kebab-case")]
-pub struct Headers {
-    pub captured_on: String,
-    pub hostname: String,
-    pub os_release: String,
-    pub arch: String,
-    pub cpu_desc: String,
-    pub cpuid: String,
-    pub nrcpus_online: u16,
-    pub nrcpus_avail: u16,
-    pub perf_version: String,
-    pub cmdline: Vec<String>,
-}
-
-#[derive(Deserialize, Clone)]
-pub struct Sample {
-    pub timestamp: u64,
-    pub tid: u32,
-    pub callchain: Vec<Symbol>,
-}
-
-#[derive(Deserialize, Clone)]
-pub struct Symbol {
-    pub ip: String,
-    pub symbol: Option<String>,
-    pub dso: Option<String>,
+struct Headers {
+    captured_on: String,
+    hostname: String,
+    os_release: String,
+    arch: String,
+    cpu_desc: String,
+    cpuid: String,
+    nrcpus_online: u16,
+    nrcpus_avail: u16,
+    perf_version: String,
+    cmdline: Vec<String>,
+}
+
+impl Headers {
+    fn add_to_profile(&self, builder: &mut ProfileBuilder) {
+        builder.add_metadata("captured-on", &self.captured_on);
+        builder.add_metadata("hostname", &self.hostname);
+        builder.add_metadata("os-release", &self.os_release);
+        builder.add_metadata("arch", &self.arch);
+        builder.add_metadata("cpu-desc", &self.cpu_desc);
+        builder.add_metadata("cpuid", &self.cpuid);
+        builder.add_metadata("nrcpus-online", &self.nrcpus_online.to_string());
+        builder.add_metadata("nrcpus-avail", &self.nrcpus_avail.to_string());
+        builder.add_metadata("perf-version", &self.perf_version);
+        builder.add_metadata("cmdline", &self.cmdline.join(" "));
+    }
+}
+
+// ------------------------------------------------------------------------------
+
+// { "headers": {...}, "samples": [...] }
+struct PerfJsonSeed<'a> {
+    builder: &'a mut ProfileBuilder,
+    program: Option<&'a mut ProgramSymbolicator>,
+    progress: &'a LoadProgress,
+}
+
+impl<'de> DeserializeSeed<'de> for PerfJsonSeed<'_> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de> Visitor<'de> for PerfJsonSeed<'_> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "a perf.json object")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(mut self, mut map: A) -> Result<(), A::Error> {
+        while let Some(key) = map.next_key::<PerfJsonKey>()? {
+            match key {
+                PerfJsonKey::Headers => map.next_value::<Headers>()?.add_to_profile(self.builder),
+                PerfJsonKey::Samples => map.next_value_seed(SamplesSeed {
+                    builder: &mut *self.builder,
+                    program: self.program.as_deref_mut(),
+                    progress: self.progress,
+                })?,
+                PerfJsonKey::Other => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(field_identifier, rename_all = "lowercase")]
+enum PerfJsonKey {
+    Headers,
+    Samples,
+    #[serde(other)]
+    Other,
+}
+
+// "samples": [{...}, ...]
+struct SamplesSeed<'a> {
+    builder: &'a mut ProfileBuilder,
+    program: Option<&'a mut ProgramSymbolicator>,
+    progress: &'a LoadProgress,
+}
+
+impl<'de> DeserializeSeed<'de> for SamplesSeed<'_> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de> Visitor<'de> for SamplesSeed<'_> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "an array of samples")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(mut self, mut seq: A) -> Result<(), A::Error> {
+        // Reused between samples to avoid an allocation per sample
+        let mut callchain = Vec::new();
+        let mut symbols = Vec::new();
+        while let Some(()) = seq.next_element_seed(SampleSeed {
+            builder: &mut *self.builder,
+            program: self.program.as_deref_mut(),
+            callchain: &mut callchain,
+            symbols: &mut symbols,
+        })? {
+            self.progress.add_record();
+        }
+        Ok(())
+    }
+}
+
+// { "timestamp": 0, "pid": 0, "tid": 0, "comm": "", "cpu": 0, "callchain": [...] }
+struct SampleSeed<'a> {
+    builder: &'a mut ProfileBuilder,
+    program: Option<&'a mut ProgramSymbolicator>,
+    callchain: &'a mut Vec<FrameId>,
+    symbols: &'a mut Vec<Symbol>,
+}
+
+impl<'de> DeserializeSeed<'de> for SampleSeed<'_> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de> Visitor<'de> for SampleSeed<'_> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "a sample object")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(mut self, mut map: A) -> Result<(), A::Error> {
+        let mut timestamp = None;
+        let mut pid = None;
+        let mut tid = None;
+        let mut comm = None;
+        let mut cpu = None;
+        self.callchain.clear();
+
+        while let Some(key) = map.next_key::<SampleKey>()? {
+            match key {
+                SampleKey::Timestamp => timestamp = Some(map.next_value::<u64>()?),
+                SampleKey::Pid => pid = Some(map.next_value::<u32>()?),
+                SampleKey::Tid => tid = Some(map.next_value::<u32>()?),
+                SampleKey::Comm => comm = map.next_value_seed(InternSeed(&mut *self.builder))?,
+                SampleKey::Cpu => cpu = Some(map.next_value::<u32>()?),
+                SampleKey::Callchain => map.next_value_seed(CallchainSeed {
+                    builder: &mut *self.builder,
+                    program: self.program.as_deref_mut(),
+                    callchain: &mut *self.callchain,
+                    symbols: &mut *self.symbols,
+                })?,
+                SampleKey::Other => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+
+        let timestamp = timestamp.ok_or_else(|| A::Error::missing_field("timestamp"))?;
+        let tid = tid.ok_or_else(|| A::Error::missing_field("tid"))?;
+        if let (Some(pid), Some(comm)) = (pid, comm) {
+            self.builder.set_thread(tid, pid, comm);
+        }
+        let stack = self
+            .builder
+            .intern_callchain(self.callchain.iter().copied());
+        self.builder.add_sample(Sample {
+            time: timestamp,
+            tid,
+            cpu: cpu.unwrap_or(UNKNOWN_CPU),
+            stack,
+            weight: 1,
+        });
+        Ok(())
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(field_identifier, rename_all = "lowercase")]
+enum SampleKey {
+    Timestamp,
+    Pid,
+    Tid,
+    Comm,
+    Cpu,
+    Callchain,
+    #[serde(other)]
+    Other,
+}
+
+// "callchain": [{ "ip": "0x0", "symbol": "", "dso": "" }, ...], ordered from leaf to root
+struct CallchainSeed<'a> {
+    builder: &'a mut ProfileBuilder,
+    program: Option<&'a mut ProgramSymbolicator>,
+    callchain: &'a mut Vec<FrameId>,
+    symbols: &'a mut Vec<Symbol>,
+}
+
+impl<'de> DeserializeSeed<'de> for CallchainSeed<'_> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de> Visitor<'de> for CallchainSeed<'_> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "an array of symbols")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(mut self, mut seq: A) -> Result<(), A::Error> {
+        self.symbols.clear();
+        while let Some(symbol) = seq.next_element_seed(SymbolSeed(&mut *self.builder))? {
+            self.symbols.push(symbol);
+        }
+
+        // perf lists the functions inlined at an address as entries of their own, sharing the
+        // address, when it can find them. The program's entries get the locations of the matching
+        // symbolicator frames then, or else the outermost frame's, which is in the listed function.
+        let mut start = 0;
+        while start < self.symbols.len() {
+            let address = self.symbols[start].address;
+            let run_length = (self.symbols[start..].iter())
+                .take_while(|symbol| symbol.address == address)
+                .count();
+            let run = &self.symbols[start..start + run_length];
+
+            let locations = match self.program.as_deref_mut() {
+                Some(program) if run[0].module == program.module => {
+                    // Callers' addresses are return addresses, so look up the call instruction before them instead
+                    let call_address = if start == 0 {
+                        address
+                    } else {
+                        address.saturating_sub(1)
+                    };
+                    program
+                        .symbolicator
+                        .lookup_frames(call_address)
+                        .map_err(A::Error::custom)?
+                }
+                _ => Vec::new(),
+            };
+            for (i, symbol) in run.iter().enumerate() {
+                let location = if locations.len() == run.len() {
+                    locations.get(i)
+                } else {
+                    locations.last()
+                };
+                let (file, line) = match location {
+                    Some(location) => match &location.file {
+                        Some(file) => (self.builder.intern_string(file), location.line),
+                        None => (EMPTY_STRING, 0),
+                    },
+                    None => (EMPTY_STRING, 0),
+                };
+                self.callchain.push(self.builder.intern_frame(Frame {
+                    address,
+                    function: symbol.function,
+                    module: symbol.module,
+                    file,
+                    line,
+                }));
+            }
+            start += run_length;
+        }
+        Ok(())
+    }
+}
+
+struct Symbol {
+    address: u64,
+    function: StringId,
+    module: StringId,
+}
+
+struct SymbolSeed<'a>(&'a mut ProfileBuilder);
+
+impl<'de> DeserializeSeed<'de> for SymbolSeed<'_> {
+    type Value = Symbol;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Symbol, D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de> Visitor<'de> for SymbolSeed<'_> {
+    type Value = Symbol;
+
+    fn expecting(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "a symbol object")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Symbol, A::Error> {
+        let builder = self.0;
+        let mut address = None;
+        let mut symbol = None;
+        let mut dso = None;
+
+        while let Some(key) = map.next_key::<SymbolKey>()? {
+            match key {
+                SymbolKey::Ip => address = Some(map.next_value::<HexAddress>()?.0),
+                SymbolKey::Symbol => symbol = map.next_value_seed(InternSeed(&mut *builder))?,
+                SymbolKey::Dso => dso = map.next_value_seed(InternSeed(&mut *builder))?,
+                SymbolKey::Other => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+
+        let function = match symbol {
+            Some(symbol) => symbol,
+            None => builder.intern_string("[unknown]"),
+        };
+        Ok(Symbol {
+            address: address.ok_or_else(|| A::Error::missing_field("ip"))?,
+            function,
+            module: dso.unwrap_or(EMPTY_STRING),
+        })
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(field_identifier, rename_all = "lowercase")]
+enum SymbolKey {
+    Ip,
+    Symbol,
+    Dso,
+    #[serde(other)]
+    Other,
+}
+
+// ------------------------------------------------------------------------------
+
+// Interns a (nullable) string without allocating a String for it
+struct InternSeed<'a>(&'a mut ProfileBuilder);
+
+impl<'de> DeserializeSeed<'de> for InternSeed<'_> {
+    type Value = Option<StringId>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_option(self)
+    }
+}
+
+impl<'de> Visitor<'de> for InternSeed<'_> {
+    type Value = Option<StringId>;
+
+    fn expecting(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "a string or null")
+    }
+
+    fn visit_none<E: DeError>(self) -> Result<Self::Value, E> {
+        Ok(None)
+    }
+
+    fn visit_unit<E: DeError>(self) -> Result<Self::Value, E> {
+        Ok(None)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_str(self)
+    }
+
+    fn visit_str<E: DeError>(self, string: &str) -> Result<Self::Value, E> {
+        Ok(Some(self.0.intern_string(string)))
+    }
+}
+
+// "0x7f1234abcd"
+struct HexAddress(u64);
+
+impl<'de> Deserialize<'de> for HexAddress {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_str(HexAddressVisitor)
+    }
+}
+
+struct HexAddressVisitor;
+
+impl<'de> Visitor<'de> for HexAddressVisitor {
+    type Value = HexAddress;
+
+    fn expecting(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "a hexadecimal address string")
+    }
+
+    fn visit_str<E: DeError>(self, string: &str) -> Result<HexAddress, E> {
+        u64::from_str_radix(string.trim_start_matches("0x"), 16)
+            .map(HexAddress)
+            .map_err(|_| E::invalid_value(serde::de::Unexpected::Str(string), &self))
+    }
 }
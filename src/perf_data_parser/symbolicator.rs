@@ -1,6 +1,12 @@
 use addr2line::gimli::{EndianReader, RunTimeEndian};
-use addr2line::object::{File as ObjectFile, Object, ObjectSection};
-use addr2line::Context;
+use addr2line::object::{
+    Architecture, File as ObjectFile, Object, ObjectSection, ObjectSegment, ObjectSymbol,
+    SymbolKind,
+};
+use addr2line::{demangle_auto, Context};
+use capstone::arch::{self, BuildsCapstone};
+use capstone::Capstone;
+use std::borrow::Cow;
 use std::error::Error;
 use std::fs;
 use std::io::{Error as IOError, ErrorKind};
@@ -9,42 +15,208 @@ use std::rc::Rc;
 
 pub struct Symbolicator {
     context: Context<EndianReader<RunTimeEndian, Rc<[u8]>>>,
-    object_text_section_address: u64,
+    // How far the object was moved from the addresses it was linked at when it was loaded
+    load_bias: u64,
+    object_bytes: Vec<u8>,
+    // Function symbols, sorted by address
+    symbols: Vec<Symbol>,
 }
 
 impl Symbolicator {
-    pub fn new<P: AsRef<Path>>(
-        object_path: P,
-        object_base_address: u64,
-    ) -> Result<Self, Box<dyn Error>> {
+    pub fn new<P: AsRef<Path>>(object_path: P, load_bias: u64) -> Result<Self, Box<dyn Error>> {
         let object_bytes = fs::read(object_path)?;
         let object_file = ObjectFile::parse(object_bytes.as_slice())?;
         let context = Context::new(&object_file)?;
 
-        let object_text_section = object_file
-            .section_by_name(".text")
-            .ok_or_else(|| IOError::new(ErrorKind::NotFound, ".text section not found"))?;
-        dbg!(object_text_section.address());
-        let object_text_section_address =
-            object_base_address + object_text_section.address() - 0x7D1E40; // TODO
+        let mut symbols = object_file
+            .symbols()
+            .filter(|symbol| symbol.kind() == SymbolKind::Text && symbol.size() > 0)
+            .filter_map(|symbol| {
+                Some(Symbol {
+                    address: symbol.address(),
+                    size: symbol.size(),
+                    name: demangle_auto(Cow::from(symbol.name().ok()?), None).into_owned(),
+                })
+            })
+            .collect::<Vec<_>>();
+        symbols.sort_by_key(|symbol| symbol.address);
 
         Ok(Self {
             context,
-            object_text_section_address,
+            load_bias,
+            object_bytes,
+            symbols,
         })
     }
 
-    // TODO: Is it possible to hand out &str?
-    pub fn lookup_symbol(&mut self, instruction_pointer: u64) -> Result<String, Box<dyn Error>> {
+    // Every function at an instruction, innermost first, so functions inlined into another come before it.
+    // The innermost frame's location is the instruction's, and every other frame's is where its callee was inlined.
+    pub fn lookup_frames(
+        &mut self,
+        instruction_pointer: u64,
+    ) -> Result<Vec<SymbolFrame>, Box<dyn Error>> {
         let mut frames = self
             .context
-            .find_frames(instruction_pointer - self.object_text_section_address)?;
-        // TODO: Correctly handle inline functions, see addr2line docs
+            .find_frames(instruction_pointer.wrapping_sub(self.load_bias))?;
+        let mut symbol_frames = Vec::new();
         while let Some(frame) = frames.next()? {
-            return Ok(frame.function.unwrap().demangle()?.to_string());
+            let function = match frame.function {
+                Some(function) => function.demangle()?.into_owned(),
+                None => "[unknown]".to_string(),
+            };
+            let (file, line) = match frame.location {
+                Some(location) => (location.file.map(str::to_owned), location.line.unwrap_or(0)),
+                None => (None, 0),
+            };
+            symbol_frames.push(SymbolFrame {
+                function,
+                file,
+                line,
+            });
         }
-        Ok("[unknown]".to_string())
+
+        if symbol_frames.is_empty() {
+            symbol_frames.push(SymbolFrame {
+                function: "[unknown]".to_string(),
+                file: None,
+                line: 0,
+            });
+        }
+        Ok(symbol_frames)
+    }
+
+    // Decode every instruction of the function containing an instruction (x86_64 and aarch64 only)
+    pub fn disassemble(&self, instruction_pointer: u64) -> Result<Disassembly, Box<dyn Error>> {
+        let symbol = self
+            .symbol_at(instruction_pointer.wrapping_sub(self.load_bias))
+            .ok_or_else(|| IOError::new(ErrorKind::NotFound, "No function found at address"))?;
+
+        let object_file = ObjectFile::parse(self.object_bytes.as_slice())?;
+        let section = object_file
+            .sections()
+            .find(|section| {
+                let section_end = section.address().saturating_add(section.size());
+                section.address() <= symbol.address
+                    && (symbol.address.checked_add(symbol.size))
+                        .map_or(false, |end| end <= section_end)
+            })
+            .ok_or_else(|| IOError::new(ErrorKind::NotFound, "Function's section not found"))?;
+        // Sections without data in the file (like .bss) or a symbol overrunning its section
+        // leave nothing to decode
+        let start = (symbol.address - section.address()) as usize;
+        let bytes = (start.checked_add(symbol.size as usize))
+            .and_then(|end| section.data().ok()?.get(start..end))
+            .ok_or_else(|| IOError::new(ErrorKind::InvalidData, "Function's bytes not found"))?;
+
+        let capstone = match object_file.architecture() {
+            Architecture::X86_64 => Capstone::new()
+                .x86()
+                .mode(arch::x86::ArchMode::Mode64)
+                .build()
+                .map_err(|e| e.to_string())?,
+            Architecture::Aarch64 => Capstone::new()
+                .arm64()
+                .mode(arch::arm64::ArchMode::Arm)
+                .build()
+                .map_err(|e| e.to_string())?,
+            architecture => {
+                return Err(format!("Unsupported architecture: {architecture:?}").into())
+            }
+        };
+
+        let mut instructions = Vec::new();
+        let decoded = capstone
+            .disasm_all(bytes, symbol.address)
+            .map_err(|e| e.to_string())?;
+        for instruction in decoded.iter() {
+            let mnemonic = instruction.mnemonic().unwrap_or_default();
+            let operands = instruction.op_str().unwrap_or_default();
+            let call_target = match mnemonic {
+                "call" | "bl" => parse_address(operands)
+                    .and_then(|target| self.symbol_at(target))
+                    .map(|target| target.name.clone()),
+                _ => None,
+            };
+            let location = self
+                .context
+                .find_location(instruction.address())?
+                .and_then(|location| Some((location.file?.to_owned(), location.line?)));
+
+            instructions.push(DisassembledInstruction {
+                address: instruction.address().wrapping_add(self.load_bias),
+                text: format!("{mnemonic} {operands}"),
+                call_target,
+                location,
+            });
+        }
+
+        Ok(Disassembly {
+            function: symbol.name.clone(),
+            instructions,
+        })
+    }
+
+    fn symbol_at(&self, address: u64) -> Option<&Symbol> {
+        let index = self
+            .symbols
+            .partition_point(|symbol| symbol.address <= address)
+            .checked_sub(1)?;
+        let symbol = &self.symbols[index];
+        (address < symbol.address + symbol.size).then_some(symbol)
     }
 }
 
-// a37910
+struct Symbol {
+    address: u64,
+    size: u64,
+    name: String,
+}
+
+// The load bias of an object, from where the part of it at mapping_offset in the file was mapped
+// Non-PIE executables are linked at the address they're loaded at, so their bias is 0
+pub fn load_bias<P: AsRef<Path>>(
+    object_path: P,
+    mapping_address: u64,
+    mapping_offset: u64,
+) -> Result<u64, Box<dyn Error>> {
+    let object_bytes = fs::read(object_path)?;
+    let object_file = ObjectFile::parse(object_bytes.as_slice())?;
+    let segment = object_file
+        .segments()
+        .find(|segment| {
+            let (offset, size) = segment.file_range();
+            (offset..offset + size).contains(&mapping_offset)
+        })
+        .ok_or_else(|| IOError::new(ErrorKind::NotFound, "Mapped segment not found"))?;
+    let (segment_offset, _) = segment.file_range();
+    let linked_address = segment.address() + (mapping_offset - segment_offset);
+    Ok(mapping_address.wrapping_sub(linked_address))
+}
+
+// An immediate address operand, e.g. "0x1a2b" on x86_64 or "#0x1a2b" on aarch64
+fn parse_address(operands: &str) -> Option<u64> {
+    let address = operands.trim().trim_start_matches('#').strip_prefix("0x")?;
+    u64::from_str_radix(address, 16).ok()
+}
+
+pub struct SymbolFrame {
+    pub function: String,
+    pub file: Option<String>,
+    // 0 if unknown
+    pub line: u32,
+}
+
+pub struct Disassembly {
+    pub function: String,
+    pub instructions: Vec<DisassembledInstruction>,
+}
+
+pub struct DisassembledInstruction {
+    // Where the instruction was in the profiled process, as recorded in callchains
+    pub address: u64,
+    pub text: String,
+    // The name of the function called, for calls to known functions
+    pub call_target: Option<String>,
+    // (file, line) from DWARF
+    pub location: Option<(String, u32)>,
+}
@@ -0,0 +1,391 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::io::{Error as IOError, ErrorKind};
+use std::rc::Rc;
+
+// Tracepoint format descriptions stored in the TRACING_DATA extra header (or HEADER_TRACING_DATA event)
+// Layout is documented by tools/perf/util/trace-event-read.c in the kernel source tree
+pub struct TracingData {
+    big_endian: bool,
+    event_formats: HashMap<u16, Rc<EventFormat>>,
+}
+
+impl TracingData {
+    pub fn parse(bytes: &[u8]) -> Result<Self, IOError> {
+        let mut cursor = ByteCursor {
+            bytes,
+            position: 0,
+            big_endian: false,
+        };
+
+        let magic = cursor.read_bytes(10)?;
+        if magic != b"\x17\x08\x44tracing" {
+            return Err(invalid_data("Invalid tracing data magic"));
+        }
+        let _version = cursor.read_string()?;
+        cursor.big_endian = cursor.read_u8()? == 1;
+        let _long_size = cursor.read_u8()?;
+        let _page_size = cursor.read_u32()?;
+
+        // header_page and header_event describe the ftrace ring buffer, which perf.data doesn't use
+        for header_name in ["header_page", "header_event"] {
+            if cursor.read_string()? != header_name {
+                return Err(invalid_data("Invalid tracing data header files"));
+            }
+            let size = cursor.read_u64()? as usize;
+            cursor.read_bytes(size)?;
+        }
+
+        let mut event_formats = HashMap::new();
+        let mut add_event_format = |system: &str, format: &[u8]| -> Result<(), IOError> {
+            let format = EventFormat::parse(system, &String::from_utf8_lossy(format))?;
+            event_formats.insert(format.id, Rc::new(format));
+            Ok(())
+        };
+
+        let ftrace_format_count = cursor.read_u32()?;
+        for _ in 0..ftrace_format_count {
+            let size = cursor.read_u64()? as usize;
+            add_event_format("ftrace", cursor.read_bytes(size)?)?;
+        }
+
+        let system_count = cursor.read_u32()?;
+        for _ in 0..system_count {
+            let system = cursor.read_string()?.to_owned();
+            let format_count = cursor.read_u32()?;
+            for _ in 0..format_count {
+                let size = cursor.read_u64()? as usize;
+                add_event_format(&system, cursor.read_bytes(size)?)?;
+            }
+        }
+
+        // TODO: Parse kallsyms, ftrace printk formats, and saved cmdlines (version >= 0.6)
+
+        Ok(Self {
+            big_endian: cursor.big_endian,
+            event_formats,
+        })
+    }
+
+    pub fn event_format(&self, id: u16) -> Option<&Rc<EventFormat>> {
+        self.event_formats.get(&id)
+    }
+
+    // Decode a PERF_SAMPLE_RAW payload into named fields using its event's format description
+    pub fn decode_raw_sample(&self, raw_data: &[u8]) -> Option<TracepointEvent> {
+        // Every tracepoint record starts with common_type, which holds the event ID
+        let id = read_unsigned(raw_data.get(0..2)?, self.big_endian) as u16;
+        let format = self.event_format(id)?.clone();
+
+        let values = format
+            .fields
+            .iter()
+            .map(|field| field.decode(raw_data, self.big_endian))
+            .collect();
+
+        Some(TracepointEvent { format, values })
+    }
+}
+
+pub struct EventFormat {
+    pub system: String,
+    pub name: String,
+    pub id: u16,
+    pub fields: Vec<FieldFormat>,
+}
+
+impl EventFormat {
+    // Parse a tracefs format file, e.g. /sys/kernel/tracing/events/sched/sched_switch/format
+    fn parse(system: &str, format: &str) -> Result<Self, IOError> {
+        let mut name = None;
+        let mut id = None;
+        let mut fields = Vec::new();
+        let mut hex_fields = HashSet::new();
+
+        for line in format.lines() {
+            let line = line.trim();
+            if let Some(event_name) = line.strip_prefix("name:") {
+                name = Some(event_name.trim().to_owned());
+            } else if let Some(event_id) = line.strip_prefix("ID:") {
+                id = event_id.trim().parse::<u16>().ok();
+            } else if line.starts_with("field:") {
+                fields.push(FieldFormat::parse(line)?);
+            } else if let Some(print_format) = line.strip_prefix("print fmt:") {
+                hex_fields = parse_hex_fields(print_format).unwrap_or_default();
+            }
+        }
+        for field in &mut fields {
+            field.hex = field.type_name.contains('*') || hex_fields.contains(&field.name);
+        }
+
+        Ok(Self {
+            system: system.to_owned(),
+            name: name.ok_or_else(|| invalid_data("Event format is missing a name"))?,
+            id: id.ok_or_else(|| invalid_data("Event format is missing an ID"))?,
+            fields,
+        })
+    }
+}
+
+pub struct FieldFormat {
+    pub name: String,
+    pub type_name: String,
+    pub offset: usize,
+    pub size: usize,
+    pub signed: bool,
+    pub array_length: Option<usize>,
+    pub dynamic: bool,
+    // Pointers, and fields the event's print fmt shows in hex
+    pub hex: bool,
+}
+
+impl FieldFormat {
+    // Parse a line like "field:char prev_comm[16];	offset:8;	size:16;	signed:0;"
+    fn parse(line: &str) -> Result<Self, IOError> {
+        let mut declaration = None;
+        let mut offset = None;
+        let mut size = None;
+        let mut signed = false;
+        for part in line.split(';').map(str::trim) {
+            match part.split_once(':') {
+                Some(("field", value)) => declaration = Some(value.trim()),
+                Some(("offset", value)) => offset = value.trim().parse::<usize>().ok(),
+                Some(("size", value)) => size = value.trim().parse::<usize>().ok(),
+                Some(("signed", value)) => signed = value.trim() == "1",
+                _ => {}
+            }
+        }
+
+        let declaration = declaration.ok_or_else(|| invalid_data("Field is missing a name"))?;
+        let (type_name, mut name) = declaration
+            .rsplit_once(' ')
+            .ok_or_else(|| invalid_data(format!("Invalid field declaration: {declaration}")))?;
+        let mut type_name = type_name.to_owned();
+
+        let mut array_length = None;
+        if let Some((array_name, length)) = name.split_once('[') {
+            array_length = length.trim_end_matches(']').parse::<usize>().ok();
+            type_name.push_str("[]");
+            name = array_name;
+        }
+
+        // __data_loc fields store a u32 of (length << 16 | offset) pointing into the record
+        let dynamic = type_name.starts_with("__data_loc");
+        if dynamic {
+            type_name = type_name.trim_start_matches("__data_loc").trim().to_owned();
+        }
+
+        Ok(Self {
+            name: name.to_owned(),
+            type_name,
+            offset: offset.ok_or_else(|| invalid_data("Field is missing an offset"))?,
+            size: size.ok_or_else(|| invalid_data("Field is missing a size"))?,
+            signed,
+            array_length,
+            dynamic,
+            hex: false,
+        })
+    }
+
+    fn decode(&self, raw_data: &[u8], big_endian: bool) -> FieldValue {
+        // Offsets and sizes come from the recording, so ones out of range leave the field missing
+        let field_range = |offset: usize, size: usize| Some(offset..offset.checked_add(size)?);
+        let bytes = field_range(self.offset, self.size).and_then(|range| raw_data.get(range));
+        let bytes = match bytes {
+            Some(bytes) => bytes,
+            None => return FieldValue::Bytes(Vec::new()),
+        };
+
+        let bytes = if self.dynamic {
+            let data_location = read_unsigned(bytes, big_endian);
+            let offset = (data_location & 0xFFFF) as usize;
+            let length = ((data_location >> 16) & 0xFFFF) as usize;
+            field_range(offset, length)
+                .and_then(|range| raw_data.get(range))
+                .unwrap_or_default()
+        } else {
+            bytes
+        };
+
+        let is_string = self.type_name.starts_with("char") || self.type_name.contains("char[]");
+        if (self.dynamic || self.array_length.is_some()) && is_string {
+            let end = bytes.iter().position(|&c| c == 0).unwrap_or(bytes.len());
+            FieldValue::String(String::from_utf8_lossy(&bytes[..end]).into_owned())
+        } else if self.dynamic || self.array_length.is_some() || !(1..=8).contains(&bytes.len()) {
+            FieldValue::Bytes(bytes.to_vec())
+        } else if self.signed {
+            let value = read_unsigned(bytes, big_endian);
+            let shift = 64 - 8 * bytes.len() as u32;
+            FieldValue::Signed(((value << shift) as i64) >> shift)
+        } else if self.hex {
+            FieldValue::Hex(read_unsigned(bytes, big_endian))
+        } else {
+            FieldValue::Unsigned(read_unsigned(bytes, big_endian))
+        }
+    }
+}
+
+// A decoded tracepoint sample, e.g. sched:sched_switch or syscalls:sys_enter_write
+pub struct TracepointEvent {
+    pub format: Rc<EventFormat>,
+    pub values: Vec<FieldValue>,
+}
+
+impl TracepointEvent {
+    pub fn name(&self) -> String {
+        format!("{}:{}", self.format.system, self.format.name)
+    }
+
+    pub fn fields(&self) -> impl Iterator<Item = (&str, &FieldValue)> {
+        self.format
+            .fields
+            .iter()
+            .map(|field| field.name.as_str())
+            .zip(self.values.iter())
+    }
+}
+
+pub enum FieldValue {
+    Signed(i64),
+    Unsigned(u64),
+    Hex(u64),
+    String(String),
+    Bytes(Vec<u8>),
+}
+
+impl Display for FieldValue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            FieldValue::Signed(value) => write!(f, "{value}"),
+            FieldValue::Unsigned(value) => write!(f, "{value}"),
+            FieldValue::Hex(value) => write!(f, "{value:#x}"),
+            FieldValue::String(value) => write!(f, "{value}"),
+            FieldValue::Bytes(value) => write!(f, "{value:02x?}"),
+        }
+    }
+}
+
+struct ByteCursor<'a> {
+    bytes: &'a [u8],
+    position: usize,
+    big_endian: bool,
+}
+
+impl<'a> ByteCursor<'a> {
+    fn read_bytes(&mut self, count: usize) -> Result<&'a [u8], IOError> {
+        let bytes = (self.position.checked_add(count))
+            .and_then(|end| self.bytes.get(self.position..end))
+            .ok_or_else(|| IOError::new(ErrorKind::UnexpectedEof, "Tracing data is truncated"))?;
+        self.position += count;
+        Ok(bytes)
+    }
+
+    fn read_u8(&mut self) -> Result<u8, IOError> {
+        Ok(self.read_bytes(1)?[0])
+    }
+
+    fn read_u32(&mut self) -> Result<u32, IOError> {
+        Ok(read_unsigned(self.read_bytes(4)?, self.big_endian) as u32)
+    }
+
+    fn read_u64(&mut self) -> Result<u64, IOError> {
+        Ok(read_unsigned(self.read_bytes(8)?, self.big_endian))
+    }
+
+    fn read_string(&mut self) -> Result<&'a str, IOError> {
+        let remaining = &self.bytes[self.position..];
+        let length = remaining
+            .iter()
+            .position(|&c| c == 0)
+            .ok_or_else(|| invalid_data("Unterminated string in tracing data"))?;
+        let string = std::str::from_utf8(&remaining[..length])
+            .map_err(|e| IOError::new(ErrorKind::InvalidData, e))?;
+        self.position += length + 1;
+        Ok(string)
+    }
+}
+
+// Names of the fields a print fmt like
+// "prev_comm=%s prev_pid=%d ...", REC->prev_comm, REC->prev_pid, ...
+// formats with %x or %p. None if it can't be made sense of.
+fn parse_hex_fields(print_format: &str) -> Option<HashSet<String>> {
+    let rest = print_format.trim().strip_prefix('"')?;
+    let mut escaped = false;
+    let (format_end, _) = rest.char_indices().find(|&(_, c)| {
+        let is_end = c == '"' && !escaped;
+        escaped = c == '\\' && !escaped;
+        is_end
+    })?;
+    let (format_string, arguments) = (&rest[..format_end], &rest[format_end + 1..]);
+
+    // Whether each argument the format string consumes is shown in hex
+    let mut hex_arguments = Vec::new();
+    let mut chars = format_string.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            continue;
+        }
+        if chars.peek() == Some(&'%') {
+            chars.next();
+            continue;
+        }
+        // Flags, width, precision, and length modifiers come before the conversion
+        while let Some(&c) = chars.peek() {
+            if c.is_ascii_alphabetic() && !"hlLqjzt".contains(c) {
+                break;
+            }
+            if c == '*' {
+                hex_arguments.push(false);
+            }
+            chars.next();
+        }
+        hex_arguments.push(matches!(chars.next()?, 'x' | 'X' | 'p'));
+    }
+
+    // Arguments are C expressions, split on the commas between them
+    let mut argument_list = Vec::new();
+    let (mut depth, mut in_string, mut start) = (0, false, 0);
+    for (i, c) in arguments.char_indices() {
+        match c {
+            '"' => in_string = !in_string,
+            '(' | '[' if !in_string => depth += 1,
+            ')' | ']' if !in_string => depth -= 1,
+            ',' if !in_string && depth == 0 => {
+                argument_list.push(&arguments[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    argument_list.push(&arguments[start..]);
+
+    // The text before the first comma is what follows the format string, not an argument
+    let hex_fields = argument_list
+        .iter()
+        .skip(1)
+        .zip(hex_arguments)
+        .filter(|&(_, hex)| hex)
+        .filter_map(|(argument, _)| argument.trim().strip_prefix("REC->"))
+        .filter(|name| name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_'))
+        .map(str::to_owned)
+        .collect();
+    Some(hex_fields)
+}
+
+fn read_unsigned(bytes: &[u8], big_endian: bool) -> u64 {
+    let mut value = 0;
+    if big_endian {
+        for &byte in bytes {
+            value = (value << 8) | byte as u64;
+        }
+    } else {
+        for &byte in bytes.iter().rev() {
+            value = (value << 8) | byte as u64;
+        }
+    }
+    value
+}
+
+fn invalid_data<E: Into<Box<dyn std::error::Error + Send + Sync>>>(error: E) -> IOError {
+    IOError::new(ErrorKind::InvalidData, error)
+}
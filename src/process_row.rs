@@ -0,0 +1,107 @@
+use glib::subclass::prelude::{DerivedObjectProperties, ObjectImpl, ObjectImplExt, ObjectSubclass};
+use glib::{object_subclass, Cast, Object, ObjectExt, ParamSpec, Properties, ToVariant, Value};
+use gtk::prelude::{EventControllerExt, GestureExt, GestureSingleExt};
+use gtk::subclass::prelude::{WidgetClassSubclassExt, WidgetImpl, WidgetImplExt};
+use gtk::traits::{StyleContextExt, WidgetExt};
+use gtk::{
+    Accessible, Buildable, ConstraintTarget, EventSequenceState, GestureClick, Orientation,
+    Snapshot, Widget,
+};
+use std::cell::{Cell, RefCell};
+
+glib::wrapper! {
+    pub struct ProcessRow(ObjectSubclass<ProcessRowPrivate>)
+    @extends Widget,
+    @implements Accessible, Buildable, ConstraintTarget;
+}
+
+impl ProcessRow {
+    pub fn new(pid: u32, header: &str, collapsed: bool) -> Self {
+        Object::new(&[
+            ("pid", &pid),
+            ("header", &header),
+            ("collapsed", &collapsed),
+        ])
+        .unwrap()
+    }
+}
+
+// Clicking a process header collapses or expands its threads
+fn toggle_collapsed(gesture: &GestureClick, _: i32, _: f64, _: f64) {
+    gesture.set_state(EventSequenceState::Claimed);
+    let process_row = gesture.widget().downcast::<ProcessRow>().unwrap();
+    process_row
+        .activate_action(
+            "timeline.collapse-process",
+            Some(&process_row.pid().to_variant()),
+        )
+        .unwrap();
+}
+
+// ------------------------------------------------------------------------------
+
+#[derive(Properties, Default)]
+pub struct ProcessRowPrivate {
+    #[property(get, set, construct_only)]
+    pid: Cell<u32>,
+    // Process name, pid, thread count, and share of samples
+    #[property(get, set, construct_only)]
+    header: RefCell<String>,
+    #[property(get, set, construct_only)]
+    collapsed: Cell<bool>,
+}
+
+#[object_subclass]
+impl ObjectSubclass for ProcessRowPrivate {
+    const NAME: &'static str = "WtfProcessRow";
+    type Type = ProcessRow;
+    type ParentType = Widget;
+
+    fn class_init(klass: &mut Self::Class) {
+        klass.set_css_name("process-row");
+    }
+}
+
+impl ObjectImpl for ProcessRowPrivate {
+    fn constructed(&self, this: &Self::Type) {
+        self.parent_constructed(this);
+
+        this.set_hexpand(true);
+        this.add_css_class("heading");
+
+        let click_controller = GestureClick::new();
+        click_controller.set_button(1);
+        click_controller.connect_pressed(toggle_collapsed);
+        this.add_controller(&click_controller);
+    }
+
+    fn properties() -> &'static [ParamSpec] {
+        Self::derived_properties()
+    }
+    fn set_property(&self, this: &Self::Type, id: usize, value: &Value, pspec: &ParamSpec) {
+        Self::derived_set_property(self, this, id, value, pspec).unwrap();
+    }
+    fn property(&self, this: &Self::Type, id: usize, pspec: &ParamSpec) -> Value {
+        Self::derived_property(self, this, id, pspec).unwrap()
+    }
+}
+
+impl WidgetImpl for ProcessRowPrivate {
+    fn measure(&self, _: &Self::Type, orientation: Orientation, _: i32) -> (i32, i32, i32, i32) {
+        match orientation {
+            Orientation::Horizontal => (0, 0, -1, -1),
+            Orientation::Vertical => (24, 24, -1, -1),
+            _ => unreachable!(),
+        }
+    }
+
+    fn snapshot(&self, this: &Self::Type, snapshot: &Snapshot) {
+        self.parent_snapshot(this, snapshot);
+
+        let expander = if self.collapsed.get() { "▸" } else { "▾" };
+        let header = format!("{expander} {}", self.header.borrow());
+        let layout = this.create_pango_layout(Some(&header));
+        let y = (this.height() - layout.pixel_size().1) as f64 / 2.0;
+        snapshot.render_layout(&this.style_context(), 4.0, y, &layout);
+    }
+}
@@ -0,0 +1,70 @@
+use crate::perf_data_parser::{convert_perf_data_to_wtf, convert_perf_json_to_wtf, LoadProgress};
+use crate::wtf_profile::{merge_markers, read_marker_sidecar, read_wtf_profile, Profile};
+use glib::{Continue, MainContext, PRIORITY_DEFAULT};
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::thread;
+
+// Parse profiles one after another on a worker thread, calling on_finished on the main thread once done.
+// Loading fails as a whole if any of the profiles fails to load.
+// The returned LoadProgress can be polled for progress, and used to cancel loading.
+pub fn load_profiles_in_background<F: FnOnce(Result<Vec<Profile>, String>) + 'static>(
+    profile_paths: Vec<PathBuf>,
+    on_finished: F,
+) -> Arc<LoadProgress> {
+    let progress = Arc::new(LoadProgress::default());
+
+    let (sender, receiver) = MainContext::channel(PRIORITY_DEFAULT);
+    let mut on_finished = Some(on_finished);
+    receiver.attach(None, move |result| {
+        if let Some(on_finished) = on_finished.take() {
+            on_finished(result);
+        }
+        Continue(false)
+    });
+
+    let worker_progress = progress.clone();
+    thread::spawn(move || {
+        let result = profile_paths
+            .iter()
+            .map(|profile_path| load_profile(profile_path, &worker_progress))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| e.to_string());
+        // Nothing is listening anymore if loading was cancelled
+        if !worker_progress.is_cancelled() {
+            let _ = sender.send(result);
+        }
+    });
+
+    progress
+}
+
+fn load_profile(profile_path: &Path, progress: &LoadProgress) -> Result<Profile, Box<dyn Error>> {
+    let file_name = profile_path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or_default();
+
+    let mut profile = if file_name.ends_with(".wtf") {
+        read_wtf_profile(profile_path)?
+    } else if file_name.ends_with(".json") {
+        convert_perf_json_to_wtf(profile_path, progress)?
+    } else if file_name.ends_with(".data") {
+        convert_perf_data_to_wtf(profile_path, progress)?
+    } else {
+        return Err(format!("Unsupported profile format: {file_name}").into());
+    };
+
+    // Tracepoints recorded without callchains are events, not samples, but still worth showing
+    if profile.samples.is_empty() && profile.tracepoints.is_empty() {
+        return Err("Profile contains no samples".into());
+    }
+
+    // Markers logged by the profiled program, e.g. perf.json + perf.markers.jsonl
+    let sidecar_path = profile_path.with_extension("markers.jsonl");
+    if sidecar_path.is_file() {
+        merge_markers(&mut profile.markers, read_marker_sidecar(sidecar_path)?);
+    }
+    Ok(profile)
+}
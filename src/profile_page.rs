@@ -1,6 +1,258 @@
-use crate::perf_data_parser::convert_perf_json_to_wtf;
-use crate::timeline_view::TimelineView;
+use crate::analysis::{Function, StackTransform};
+use crate::call_tree::CallTree;
+use crate::category_summary::CategorySummary;
+use crate::flame_graph::FlameGraph;
+use crate::marker_list::MarkerList;
+use crate::profile_search_bar::ProfileSearchBar;
+use crate::sample_inspector::SampleInspector;
+use crate::stack_transform_bar::StackTransformBar;
+use crate::timeline_view::{TimelineView, TimelineViewPrivatePropertiesExt};
+use crate::tracepoint_list::TracepointList;
+use crate::wtf_profile::{Profile, StringId};
+use adw::subclass::prelude::BinImpl;
+use adw::{Bin, Leaflet};
+use glib::once_cell::sync::OnceCell;
+use glib::subclass::prelude::{
+    DerivedObjectProperties, ObjectImpl, ObjectImplExt, ObjectSubclass, ObjectSubclassIsExt,
+};
+use glib::subclass::InitializingObject;
+use glib::{
+    object_subclass, BindingFlags, BoxedAnyObject, Object, ObjectExt, ParamSpec, Properties,
+    StaticType, Value,
+};
+use gtk::gdk::{Key, ModifierType};
+use gtk::prelude::InitializingWidgetExt;
+use gtk::subclass::prelude::{
+    CompositeTemplateCallbacksClass, CompositeTemplateClass, TemplateChild, WidgetClassSubclassExt,
+    WidgetImpl,
+};
+use gtk::traits::ToggleButtonExt;
+use gtk::{
+    template_callbacks, Accessible, Buildable, CompositeTemplate, ConstraintTarget, DropDown,
+    ToggleButton, Widget,
+};
+use std::path::Path;
+use std::rc::Rc;
 
-pub fn new_profile_page() -> TimelineView {
-    TimelineView::new(convert_perf_json_to_wtf("test.perf.json").unwrap())
+glib::wrapper! {
+    pub struct ProfilePage(ObjectSubclass<ProfilePagePrivate>)
+    @extends Bin, Widget,
+    @implements Accessible, Buildable, ConstraintTarget;
 }
+
+impl ProfilePage {
+    pub fn new(profile: Rc<Profile>, profile_path: &Path) -> Self {
+        Object::new(&[
+            ("profile", &BoxedAnyObject::new(profile)),
+            ("profile-path", &profile_path.to_string_lossy().into_owned()),
+        ])
+        .unwrap()
+    }
+}
+
+// ------------------------------------------------------------------------------
+
+#[derive(CompositeTemplate, Properties, Default)]
+#[template(resource = "/com/github/jms55/WhatTheFn/ui/profile_page.ui")]
+pub struct ProfilePagePrivate {
+    #[template_child]
+    search_bar: TemplateChild<ProfileSearchBar>,
+    #[template_child]
+    timeline: TemplateChild<TimelineView>,
+    #[template_child]
+    flame_chart_toggle: TemplateChild<ToggleButton>,
+    #[template_child]
+    cpu_lanes_toggle: TemplateChild<ToggleButton>,
+    #[template_child]
+    thread_order_dropdown: TemplateChild<DropDown>,
+    #[template_child]
+    group_by_process_toggle: TemplateChild<ToggleButton>,
+    #[template_child]
+    time_of_day_toggle: TemplateChild<ToggleButton>,
+    #[template_child]
+    marker_list: TemplateChild<MarkerList>,
+    #[template_child]
+    analysis_leaflet: TemplateChild<Leaflet>,
+    #[template_child]
+    call_tree: TemplateChild<CallTree>,
+    #[template_child]
+    flame_graph: TemplateChild<FlameGraph>,
+    #[template_child]
+    flame_graph_toggle: TemplateChild<ToggleButton>,
+
+    #[property(get, set, construct_only, builder(BoxedAnyObject::static_type()))]
+    profile: OnceCell<BoxedAnyObject>,
+    // Markers are saved back into the profile
+    #[property(get, set, construct_only)]
+    profile_path: OnceCell<String>,
+}
+
+#[template_callbacks]
+impl ProfilePagePrivate {
+    // When there's only room for one analysis view, switch between the call tree and flame graph
+    #[template_callback]
+    fn switch_analysis_view(&self, toggle: &ToggleButton) {
+        if toggle.is_active() {
+            self.analysis_leaflet.set_visible_child(&*self.flame_graph);
+        } else {
+            self.analysis_leaflet.set_visible_child(&*self.call_tree);
+        }
+    }
+}
+
+#[object_subclass]
+impl ObjectSubclass for ProfilePagePrivate {
+    const NAME: &'static str = "WtfProfilePage";
+    type Type = ProfilePage;
+    type ParentType = Bin;
+
+    fn class_init(klass: &mut Self::Class) {
+        // Ensure custom widgets used by the template are registered
+        ProfileSearchBar::static_type();
+        TimelineView::static_type();
+        CategorySummary::static_type();
+        MarkerList::static_type();
+        TracepointList::static_type();
+        StackTransformBar::static_type();
+        CallTree::static_type();
+        FlameGraph::static_type();
+        SampleInspector::static_type();
+
+        klass.bind_template();
+        klass.bind_template_callbacks();
+
+        // Stack transforms offered by the call tree and flame graph's right click menus
+        let function_transforms: [(&str, fn(Function) -> StackTransform); 5] = [
+            ("profile.focus-function", StackTransform::FocusFunction),
+            ("profile.merge-function", StackTransform::MergeFunction),
+            ("profile.drop-function", StackTransform::DropFunction),
+            (
+                "profile.collapse-recursion",
+                StackTransform::CollapseRecursion,
+            ),
+            (
+                "profile.collapse-direct-recursion",
+                StackTransform::CollapseDirectRecursion,
+            ),
+        ];
+        for (action, transform) in function_transforms {
+            klass.install_action(action, Some("(uu)"), move |this, _, parameter| {
+                let function = parameter.and_then(|p| p.get::<Function>()).unwrap();
+                this.imp().timeline.push_transform(transform(function));
+            });
+        }
+        klass.install_action(
+            "profile.focus-subtree",
+            Some("a(uu)"),
+            |this, _, parameter| {
+                let path = parameter.and_then(|p| p.get::<Vec<Function>>()).unwrap();
+                this.imp()
+                    .timeline
+                    .push_transform(StackTransform::FocusSubtree(path));
+            },
+        );
+        klass.install_action(
+            "profile.collapse-module",
+            Some("u"),
+            |this, _, parameter| {
+                let module = parameter.and_then(|p| p.get::<StringId>()).unwrap();
+                this.imp()
+                    .timeline
+                    .push_transform(StackTransform::CollapseModule(module));
+            },
+        );
+        klass.install_action("profile.collapse-crate", Some("s"), |this, _, parameter| {
+            let crate_name = parameter.and_then(|p| p.get::<String>()).unwrap();
+            this.imp()
+                .timeline
+                .push_transform(StackTransform::CollapseCrate(crate_name));
+        });
+        klass.install_action("profile.undo-transform", None, |this, _, _| {
+            let timeline = &this.imp().timeline;
+            let count = timeline.transform_count() as usize;
+            timeline.truncate_transforms(count.saturating_sub(1));
+        });
+        klass.install_action("profile.search", None, |this, _, _| {
+            this.imp().search_bar.open();
+        });
+        klass.add_binding_action(Key::f, ModifierType::CONTROL_MASK, "profile.search", None);
+        klass.add_binding_action(
+            Key::z,
+            ModifierType::CONTROL_MASK,
+            "profile.undo-transform",
+            None,
+        );
+    }
+
+    fn instance_init(this: &InitializingObject<Self>) {
+        this.init_template();
+    }
+}
+
+impl ObjectImpl for ProfilePagePrivate {
+    fn constructed(&self, this: &Self::Type) {
+        self.parent_constructed(this);
+
+        // The timeline owns the profile for every view bound to it
+        self.timeline.set_profile(self.profile.get().cloned());
+
+        self.flame_chart_toggle
+            .bind_property("active", &*self.timeline, "flame-chart")
+            .flags(BindingFlags::BIDIRECTIONAL | BindingFlags::SYNC_CREATE)
+            .build();
+
+        // Recordings made without --sample-cpu only have thread rows
+        let knows_cpus = self
+            .timeline
+            .profile_data()
+            .map_or(false, |profile| profile.knows_cpus());
+        self.cpu_lanes_toggle.set_visible(knows_cpus);
+        self.cpu_lanes_toggle
+            .bind_property("active", &*self.timeline, "cpu-lanes")
+            .flags(BindingFlags::BIDIRECTIONAL | BindingFlags::SYNC_CREATE)
+            .build();
+
+        // Thread rows are sorted in the order of ThreadOrder::ALL, as listed in the dropdown
+        self.thread_order_dropdown
+            .bind_property("selected", &*self.timeline, "thread-order")
+            .flags(BindingFlags::BIDIRECTIONAL | BindingFlags::SYNC_CREATE)
+            .build();
+
+        self.group_by_process_toggle
+            .bind_property("active", &*self.timeline, "group-by-process")
+            .flags(BindingFlags::BIDIRECTIONAL | BindingFlags::SYNC_CREATE)
+            .build();
+
+        // Only recordings made with a clock (perf record -k) know the time of day, which a perf.json
+        // takes from the perf.data it was converted from
+        self.time_of_day_toggle
+            .set_visible(self.timeline.wall_clock_offset().is_some());
+        self.time_of_day_toggle
+            .bind_property("active", &*self.timeline, "time-of-day")
+            .flags(BindingFlags::BIDIRECTIONAL | BindingFlags::SYNC_CREATE)
+            .build();
+
+        this.bind_property("profile-path", &*self.marker_list, "profile-path")
+            .flags(BindingFlags::SYNC_CREATE)
+            .build();
+
+        // Both analysis views are shown side by side when there's room
+        self.analysis_leaflet
+            .bind_property("folded", &*self.flame_graph_toggle, "visible")
+            .flags(BindingFlags::SYNC_CREATE)
+            .build();
+    }
+
+    fn properties() -> &'static [ParamSpec] {
+        Self::derived_properties()
+    }
+    fn set_property(&self, this: &Self::Type, id: usize, value: &Value, pspec: &ParamSpec) {
+        Self::derived_set_property(self, this, id, value, pspec).unwrap();
+    }
+    fn property(&self, this: &Self::Type, id: usize, pspec: &ParamSpec) -> Value {
+        Self::derived_property(self, this, id, pspec).unwrap()
+    }
+}
+
+impl WidgetImpl for ProfilePagePrivate {}
+impl BinImpl for ProfilePagePrivate {}
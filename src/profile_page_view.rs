@@ -1,17 +1,30 @@
-use crate::profile_page::new_profile_page;
+use crate::diff_page::DiffPage;
+use crate::perf_data_parser::LoadProgress;
+use crate::profile_loader::load_profiles_in_background;
+use crate::profile_page::ProfilePage;
 use crate::profile_setup_page::ProfileSetupPage;
+use crate::wtf_profile::Profile;
 use adw::subclass::prelude::BinImpl;
 use adw::traits::BinExt;
-use adw::Bin;
-use glib::subclass::prelude::{DerivedObjectProperties, ObjectImpl, ObjectImplExt, ObjectSubclass};
+use adw::{Bin, StatusPage};
+use gio::traits::FileExt;
+use gio::File;
+use glib::subclass::prelude::{
+    DerivedObjectProperties, ObjectImpl, ObjectImplExt, ObjectSubclass, ObjectSubclassIsExt,
+};
 use glib::{
-    object_subclass, Enum as GEnum, Object, ObjectExt, ParamSpec, Properties, StaticType, Value,
+    clone, object_subclass, timeout_add_local, Cast, Continue, Enum as GEnum, Object, ObjectExt,
+    ParamSpec, Properties, StaticType, Value,
 };
+use gtk::gdk::DragAction;
 use gtk::subclass::prelude::WidgetImpl;
-use gtk::traits::WidgetExt;
-use gtk::{Accessible, Buildable, ConstraintTarget, Widget};
-use std::cell::RefCell;
-use std::path::Path;
+use gtk::traits::{EventControllerExt, WidgetExt};
+use gtk::{Accessible, Buildable, ConstraintTarget, DropTarget, ProgressBar, Widget};
+use std::cell::{Cell, RefCell};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::Duration;
 
 glib::wrapper! {
     pub struct ProfilePageView(ObjectSubclass<ProfilePageViewPrivate>)
@@ -25,13 +38,152 @@ impl ProfilePageView {
     }
 
     pub fn switch_to_profile_page(&self, profile_path: &Path) {
-        todo!()
+        self.load_profiles(vec![profile_path.to_owned()]);
+    }
+
+    pub fn switch_to_comparison_page(&self, before_path: &Path, after_path: &Path) {
+        self.load_profiles(vec![before_path.to_owned(), after_path.to_owned()]);
+    }
+
+    // Dropping a profile onto a page showing another one compares the two,
+    // otherwise the dropped profile is opened on its own
+    pub fn open_dropped_profile(&self, profile_path: &Path) -> bool {
+        match self.state() {
+            ProfilePageViewState::Setup => self.switch_to_profile_page(profile_path),
+            ProfilePageViewState::Profile => {
+                let before_path = self.imp().profile_paths.borrow().first().cloned();
+                match before_path {
+                    Some(before_path) => self.switch_to_comparison_page(&before_path, profile_path),
+                    None => self.switch_to_profile_page(profile_path),
+                }
+            }
+            _ => return false,
+        }
+        true
     }
 
     pub fn set_data(&self, state: ProfilePageViewState, profile_name: &str) {
-        self.set_state(state);
+        // The tab reads both properties whenever either changes, so set the name first
         self.set_profile_name(Some(profile_name.to_owned()));
+        self.set_state(state);
+    }
+
+    // Load profiles on a worker thread, displaying their progress until they're ready.
+    // One profile is shown on its own, and two are compared.
+    fn load_profiles(&self, profile_paths: Vec<PathBuf>) {
+        let profile_names = profile_paths
+            .iter()
+            .map(|path| {
+                path.file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_default()
+            })
+            .collect::<Vec<_>>();
+        let profile_name = profile_names.join(" vs ");
+
+        let progress_bar = ProgressBar::builder().show_text(true).build();
+        let loading_page = StatusPage::builder()
+            .title(match profile_paths.len() {
+                1 => "Loading Profile",
+                _ => "Loading Profiles",
+            })
+            .description(&profile_name)
+            .child(&progress_bar)
+            .build();
+        self.imp().page.replace(Some(loading_page.upcast()));
+        self.imp().profile_paths.replace(profile_paths.clone());
+        self.set_data(ProfilePageViewState::LoadingProfile, &profile_name);
+
+        // A cancelled load may still finish before it notices, so results are tagged with the
+        // load they came from
+        let generation = self.imp().load_generation.get() + 1;
+        self.imp().load_generation.set(generation);
+        let load_progress = load_profiles_in_background(
+            profile_paths,
+            clone!(@weak self as this => move |result| {
+                this.finish_loading(generation, result, profile_names)
+            }),
+        );
+
+        // Poll the worker thread's progress until the loading page is replaced
+        timeout_add_local(
+            Duration::from_millis(100),
+            clone!(@weak progress_bar, @strong load_progress => @default-return Continue(false), move || {
+                update_progress_bar(&progress_bar, &load_progress);
+                Continue(true)
+            }),
+        );
+
+        if let Some(previous_load) = self.imp().load_progress.replace(Some(load_progress)) {
+            previous_load.cancel();
+        }
     }
+
+    fn finish_loading(
+        &self,
+        generation: u64,
+        result: Result<Vec<Profile>, String>,
+        profile_names: Vec<String>,
+    ) {
+        // Superseded by a newer load, whose paths and progress have replaced this one's
+        if generation != self.imp().load_generation.get() {
+            return;
+        }
+        self.imp().load_progress.take();
+
+        let page: Widget = match result {
+            Ok(mut profiles) if profiles.len() == 2 => {
+                let after = Rc::new(profiles.pop().unwrap());
+                let before = Rc::new(profiles.pop().unwrap());
+                DiffPage::new((before, &profile_names[0]), (after, &profile_names[1])).upcast()
+            }
+            Ok(mut profiles) => {
+                let profile_path = self.imp().profile_paths.borrow()[0].clone();
+                ProfilePage::new(Rc::new(profiles.pop().unwrap()), &profile_path).upcast()
+            }
+            Err(error) => StatusPage::builder()
+                .icon_name("dialog-error-symbolic")
+                .title("Failed to Load Profile")
+                .description(&error)
+                .build()
+                .upcast(),
+        };
+        self.imp().page.replace(Some(page));
+        self.set_state(ProfilePageViewState::Profile);
+    }
+
+    // Show the page for the current state
+    fn update_child(&self) {
+        match self.state() {
+            ProfilePageViewState::Setup
+            | ProfilePageViewState::SetupCompilingProgram
+            | ProfilePageViewState::SetupProfilingProgram => {
+                let showing_setup_page = self
+                    .child()
+                    .map_or(false, |child| child.is::<ProfileSetupPage>());
+                if !showing_setup_page {
+                    self.set_child(Some(&ProfileSetupPage::new()));
+                }
+            }
+            ProfilePageViewState::LoadingProfile | ProfilePageViewState::Profile => {
+                self.set_child(self.imp().page.borrow().as_ref());
+            }
+        }
+    }
+}
+
+fn update_progress_bar(progress_bar: &ProgressBar, progress: &LoadProgress) {
+    let bytes_processed = progress.bytes_processed() as f64;
+    let total_bytes = progress.total_bytes() as f64;
+    if total_bytes > 0.0 {
+        progress_bar.set_fraction(bytes_processed / total_bytes);
+    }
+    progress_bar.set_text(Some(&format!(
+        "{:.1} / {:.1} MB - {} samples",
+        bytes_processed / 1_000_000.0,
+        total_bytes / 1_000_000.0,
+        progress.records_processed(),
+    )));
 }
 
 #[derive(GEnum, Clone, Copy, PartialEq, Eq)]
@@ -58,6 +210,15 @@ pub struct ProfilePageViewPrivate {
     state: RefCell<ProfilePageViewState>,
     #[property(get, set)]
     profile_name: RefCell<Option<String>>,
+
+    // The loading, profile, or error page, shown once the state is past setup
+    page: RefCell<Option<Widget>>,
+    // The profiles being loaded or shown, two if they're being compared
+    profile_paths: RefCell<Vec<PathBuf>>,
+    // Set while a profile is being loaded
+    load_progress: RefCell<Option<Arc<LoadProgress>>>,
+    // Incremented for each load, only the latest one's result is shown
+    load_generation: Cell<u64>,
 }
 
 #[object_subclass]
@@ -71,7 +232,18 @@ impl ObjectImpl for ProfilePageViewPrivate {
     fn constructed(&self, this: &Self::Type) {
         self.parent_constructed(this);
 
-        this.set_child(Some(&new_profile_page())); // TODO: ProfileSetupPage
+        this.connect_notify_local(Some("state"), |this, _| this.update_child());
+        this.update_child();
+
+        let drop_target = DropTarget::new(File::static_type(), DragAction::COPY);
+        drop_target.connect_drop(|drop_target, value, _, _| {
+            let this = drop_target.widget().downcast::<ProfilePageView>().unwrap();
+            match value.get::<File>().ok().and_then(|file| file.path()) {
+                Some(profile_path) => this.open_dropped_profile(&profile_path),
+                None => false,
+            }
+        });
+        this.add_controller(&drop_target);
 
         this.set_margin_top(18);
         this.set_margin_bottom(18);
@@ -79,6 +251,13 @@ impl ObjectImpl for ProfilePageViewPrivate {
         this.set_margin_end(18);
     }
 
+    fn dispose(&self, _: &Self::Type) {
+        // Stop the worker thread if the tab is closed while still loading
+        if let Some(load_progress) = self.load_progress.take() {
+            load_progress.cancel();
+        }
+    }
+
     fn properties() -> &'static [ParamSpec] {
         Self::derived_properties()
     }
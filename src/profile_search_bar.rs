@@ -0,0 +1,193 @@
+use crate::timeline_view::TimelineView;
+use adw::subclass::prelude::BinImpl;
+use adw::traits::BinExt;
+use adw::Bin;
+use glib::once_cell::sync::OnceCell;
+use glib::subclass::prelude::{
+    DerivedObjectProperties, ObjectImpl, ObjectImplExt, ObjectSubclass, ObjectSubclassExt,
+    ObjectSubclassIsExt,
+};
+use glib::{
+    clone, idle_add_local_once, object_subclass, ObjectExt, ParamSpec, Properties, StaticType,
+    Value,
+};
+use gtk::subclass::prelude::{WidgetClassSubclassExt, WidgetImpl};
+use gtk::traits::{BoxExt, EditableExt, ToggleButtonExt, WidgetExt};
+use gtk::{
+    Accessible, Buildable, ConstraintTarget, Label, Orientation, SearchBar, SearchEntry,
+    ToggleButton, Widget,
+};
+use std::cell::{Cell, RefCell};
+
+glib::wrapper! {
+    pub struct ProfileSearchBar(ObjectSubclass<ProfileSearchBarPrivate>)
+    @extends Bin, Widget,
+    @implements Accessible, Buildable, ConstraintTarget;
+}
+
+impl ProfileSearchBar {
+    pub fn open(&self) {
+        let widgets = self.imp().widgets.get().unwrap();
+        widgets.search_bar.set_search_mode(true);
+        widgets.entry.grab_focus();
+    }
+
+    fn update_search(&self) {
+        let widgets = self.imp().widgets.get().unwrap();
+        let timeline = match self.timeline() {
+            Some(timeline) => timeline,
+            None => return,
+        };
+
+        let query = match widgets.search_bar.is_search_mode() {
+            true => widgets.entry.text(),
+            false => "".into(),
+        };
+        match timeline.set_search(&query, widgets.regex_toggle.is_active()) {
+            Ok(()) => {
+                widgets.entry.remove_css_class("error");
+                widgets.entry.set_tooltip_text(None);
+            }
+            Err(e) => {
+                widgets.entry.add_css_class("error");
+                widgets.entry.set_tooltip_text(Some(&e));
+            }
+        }
+    }
+
+    // Selection changes arrive on every drag update, so coalesce them into one update
+    fn queue_update_share(&self) {
+        if !self.imp().update_queued.replace(true) {
+            idle_add_local_once(clone!(@weak self as this => move || {
+                this.imp().update_queued.set(false);
+                this.update_share();
+            }));
+        }
+    }
+
+    // Show how much of the analyzed time range the matching functions were on-stack for
+    fn update_share(&self) {
+        let widgets = self.imp().widgets.get().unwrap();
+        let share = self.timeline().and_then(|timeline| {
+            let search_matches = timeline.search_matches()?;
+            let profile = timeline.profile_data()?;
+            let (start, end) = timeline.analysis_time_range()?;
+            Some(search_matches.matched_weight(&profile, start, end))
+        });
+        match share {
+            Some((matched_weight, total_weight)) => widgets.share.set_label(&format!(
+                "{:.1}% of samples",
+                matched_weight as f64 * 100.0 / total_weight.max(1) as f64
+            )),
+            None => widgets.share.set_label(""),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct SearchWidgets {
+    search_bar: SearchBar,
+    entry: SearchEntry,
+    regex_toggle: ToggleButton,
+    share: Label,
+}
+
+// ------------------------------------------------------------------------------
+
+#[derive(Properties, Default)]
+pub struct ProfileSearchBarPrivate {
+    #[property(get, set = Self::set_timeline, builder(TimelineView::static_type()))]
+    timeline: RefCell<Option<TimelineView>>,
+
+    widgets: OnceCell<SearchWidgets>,
+    update_queued: Cell<bool>,
+}
+
+impl ProfileSearchBarPrivate {
+    pub fn set_timeline(&self, value: Option<TimelineView>) {
+        let this = self.instance();
+        if let Some(timeline) = &value {
+            let properties = [
+                "profile",
+                "selected-time-range",
+                "transform-count",
+                "search-query",
+            ];
+            for property in properties {
+                timeline.connect_notify_local(
+                    Some(property),
+                    clone!(@weak this => move |_, _| this.queue_update_share()),
+                );
+            }
+        }
+
+        *self.timeline.borrow_mut() = value;
+        this.update_search();
+    }
+}
+
+#[object_subclass]
+impl ObjectSubclass for ProfileSearchBarPrivate {
+    const NAME: &'static str = "WtfProfileSearchBar";
+    type Type = ProfileSearchBar;
+    type ParentType = Bin;
+
+    fn class_init(klass: &mut Self::Class) {
+        klass.set_css_name("profile-search-bar");
+    }
+}
+
+impl ObjectImpl for ProfileSearchBarPrivate {
+    fn constructed(&self, this: &Self::Type) {
+        self.parent_constructed(this);
+
+        let entry = SearchEntry::new();
+        entry.set_placeholder_text(Some("Search functions, files, and modules"));
+        entry.set_hexpand(true);
+        entry.connect_search_changed(clone!(@weak this => move |_| this.update_search()));
+
+        let regex_toggle = ToggleButton::with_label(".*");
+        regex_toggle.set_tooltip_text(Some("Use Regular Expression"));
+        regex_toggle.connect_toggled(clone!(@weak this => move |_| this.update_search()));
+
+        let share = Label::new(None);
+        share.add_css_class("dim-label");
+        share.add_css_class("numeric");
+
+        let content = gtk::Box::new(Orientation::Horizontal, 6);
+        content.append(&entry);
+        content.append(&regex_toggle);
+        content.append(&share);
+
+        // Closing the search bar (e.g. with Escape) stops searching
+        let search_bar = SearchBar::new();
+        search_bar.set_child(Some(&content));
+        search_bar.connect_entry(&entry);
+        search_bar.set_show_close_button(true);
+        search_bar.connect_search_mode_enabled_notify(
+            clone!(@weak this => move |_| this.update_search()),
+        );
+        this.set_child(Some(&search_bar));
+
+        let widgets = SearchWidgets {
+            search_bar,
+            entry,
+            regex_toggle,
+            share,
+        };
+        self.widgets.set(widgets).unwrap();
+    }
+
+    fn properties() -> &'static [ParamSpec] {
+        Self::derived_properties()
+    }
+    fn set_property(&self, this: &Self::Type, id: usize, value: &Value, pspec: &ParamSpec) {
+        Self::derived_set_property(self, this, id, value, pspec).unwrap();
+    }
+    fn property(&self, this: &Self::Type, id: usize, pspec: &ParamSpec) -> Value {
+        Self::derived_property(self, this, id, pspec).unwrap()
+    }
+}
+
+impl WidgetImpl for ProfileSearchBarPrivate {}
+impl BinImpl for ProfileSearchBarPrivate {}
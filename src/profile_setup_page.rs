@@ -22,6 +22,9 @@ use std::error::Error;
 use std::ffi::OsStr;
 use std::path::{Path, PathBuf};
 
+// Every format profile_loader can load
+const PROFILE_PATTERNS: &[&str] = &["*.wtf", "*.perf.json", "*perf.data"];
+
 glib::wrapper! {
     pub struct ProfileSetupPage(ObjectSubclass<ProfileSetupPagePrivate>)
     @extends Bin, Widget,
@@ -63,7 +66,7 @@ impl ProfileSetupPagePrivate {
     #[template_callback]
     async fn select_cargo_toml(&self) {
         let cargo_toml_path = self
-            .get_file_from_user("Select a Cargo.toml", "Cargo.toml")
+            .get_file_from_user("Select a Cargo.toml", &["Cargo.toml"])
             .await;
         if let Some(mut cargo_toml_path) = cargo_toml_path {
             cargo_toml_path.pop();
@@ -133,21 +136,40 @@ impl ProfileSetupPagePrivate {
         }
     }
 
-    // When a .perf.json is chosen, switch the page view to ProfilePage
+    // When a profile is chosen, switch the page view to ProfilePage
     #[template_callback]
     async fn open_existing_profile(&self) {
         let profile_path = self
-            .get_file_from_user("Select a .perf.json", "*.perf.json")
+            .get_file_from_user("Select a Profile", PROFILE_PATTERNS)
             .await;
         if let Some(profile_path) = profile_path {
             self.page_view().switch_to_profile_page(&profile_path);
         }
     }
 
+    // When the profiles before and after a change are chosen, switch the page view to comparing them
+    #[template_callback]
+    async fn compare_profiles(&self) {
+        let before_path = self
+            .get_file_from_user("Select the Profile Before", PROFILE_PATTERNS)
+            .await;
+        let before_path = match before_path {
+            Some(before_path) => before_path,
+            None => return,
+        };
+        let after_path = self
+            .get_file_from_user("Select the Profile After", PROFILE_PATTERNS)
+            .await;
+        if let Some(after_path) = after_path {
+            self.page_view()
+                .switch_to_comparison_page(&before_path, &after_path);
+        }
+    }
+
     async fn get_file_from_user(
         &self,
         file_chooser_title: &str,
-        file_filter_pattern: &str,
+        file_filter_patterns: &[&str],
     ) -> Option<PathBuf> {
         let parent_window = self
             .instance()
@@ -166,8 +188,10 @@ impl ProfileSetupPagePrivate {
         file_chooser.set_modal(true);
 
         let file_filter = FileFilter::new();
-        file_filter.set_name(Some(file_filter_pattern));
-        file_filter.add_pattern(file_filter_pattern);
+        file_filter.set_name(Some(&file_filter_patterns.join(", ")));
+        for pattern in file_filter_patterns {
+            file_filter.add_pattern(pattern);
+        }
         file_chooser.add_filter(&file_filter);
 
         let response = file_chooser.run_future().await;
@@ -0,0 +1,225 @@
+use crate::analysis::{module_file_name, Category, SampleGroup};
+use crate::flame_graph::category_color;
+use crate::timeline_range::TimelineRangePrivatePropertiesExt;
+use crate::timeline_view::{TimelineView, TimelineViewPrivatePropertiesExt};
+use crate::wtf_profile::Profile;
+use adw::subclass::prelude::BinImpl;
+use adw::traits::BinExt;
+use adw::Bin;
+use glib::once_cell::sync::OnceCell;
+use glib::subclass::prelude::{
+    DerivedObjectProperties, ObjectImpl, ObjectImplExt, ObjectSubclass, ObjectSubclassExt,
+    ObjectSubclassIsExt,
+};
+use glib::{clone, object_subclass, ObjectExt, ParamSpec, Properties, StaticType, Value};
+use gtk::pango::EllipsizeMode;
+use gtk::subclass::prelude::{WidgetClassSubclassExt, WidgetImpl};
+use gtk::traits::{BoxExt, ButtonExt, WidgetExt};
+use gtk::{
+    Accessible, Buildable, Button, ConstraintTarget, Label, ListBox, Orientation, ScrolledWindow,
+    SelectionMode, Widget,
+};
+use std::cell::RefCell;
+
+glib::wrapper! {
+    pub struct SampleInspector(ObjectSubclass<SampleInspectorPrivate>)
+    @extends Bin, Widget,
+    @implements Accessible, Buildable, ConstraintTarget;
+}
+
+impl SampleInspector {
+    fn rebuild(&self) {
+        let imp = self.imp();
+        let list = imp.list.get().unwrap();
+        while let Some(row) = list.first_child() {
+            list.remove(&row);
+        }
+
+        let inspected = self.timeline().and_then(|timeline| {
+            let profile = timeline.profile_data()?;
+            let samples = timeline.inspected_samples()?;
+            Some((timeline, profile, samples))
+        });
+        let (timeline, profile, samples) = match inspected {
+            Some(inspected) => inspected,
+            None => {
+                self.set_visible(false);
+                return;
+            }
+        };
+        self.set_visible(true);
+
+        let (title, subtitle) = describe_samples(&timeline, &profile, &samples);
+        imp.title.get().unwrap().set_label(&title);
+        imp.subtitle.get().unwrap().set_label(&subtitle);
+
+        // The full stack, from the leaf frame to the root
+        for frame in profile.stack_frames(samples.stack) {
+            let frame = frame as usize;
+            list.append(&new_row(
+                profile.strings.get(profile.frames.function[frame]),
+                profile.strings.get(profile.frames.module[frame]),
+                profile.strings.get(profile.frames.file[frame]),
+                profile.frames.line[frame],
+            ));
+        }
+    }
+}
+
+fn describe_samples(
+    timeline: &TimelineView,
+    profile: &Profile,
+    samples: &SampleGroup,
+) -> (String, String) {
+    let resolution = timeline.display_time_range().duration() / timeline.width().max(1) as u64;
+    let start_time = timeline.format_time_label(samples.start_time(profile), resolution);
+    let thread = match profile.thread_name(samples.tid) {
+        Some(name) => format!("{name} ({})", samples.tid),
+        None => format!("Thread {}", samples.tid),
+    };
+
+    if samples.sample_count() == 1 {
+        return (format!("Sample at {start_time}"), thread);
+    }
+    let end_time = timeline.format_time_label(samples.end_time(profile), resolution);
+    (
+        format!(
+            "{} Samples, {start_time} – {end_time}",
+            samples.sample_count()
+        ),
+        format!("{thread}, stack of {} of the samples", samples.stack_count),
+    )
+}
+
+fn new_row(function: &str, module: &str, file: &str, line: u32) -> gtk::Box {
+    let color = category_color(Category::of(function, module));
+    let swatch = Label::new(None);
+    swatch.set_markup(&format!(
+        "<span foreground=\"#{:02x}{:02x}{:02x}\">●</span>",
+        (color.red() * 255.0) as u8,
+        (color.green() * 255.0) as u8,
+        (color.blue() * 255.0) as u8,
+    ));
+
+    let name = Label::new(Some(function));
+    name.set_xalign(0.0);
+    name.set_hexpand(true);
+    name.set_ellipsize(EllipsizeMode::Middle);
+    name.set_tooltip_text(Some(function));
+
+    let location = if file.is_empty() {
+        module_file_name(module).to_owned()
+    } else {
+        format!("{}:{line}", module_file_name(file))
+    };
+    let location = Label::new(Some(&location));
+    location.add_css_class("dim-label");
+
+    let row = gtk::Box::new(Orientation::Horizontal, 6);
+    row.append(&swatch);
+    row.append(&name);
+    row.append(&location);
+    row
+}
+
+// ------------------------------------------------------------------------------
+
+#[derive(Properties, Default)]
+pub struct SampleInspectorPrivate {
+    #[property(get, set = Self::set_timeline, builder(TimelineView::static_type()))]
+    timeline: RefCell<Option<TimelineView>>,
+
+    title: OnceCell<Label>,
+    subtitle: OnceCell<Label>,
+    list: OnceCell<ListBox>,
+}
+
+impl SampleInspectorPrivate {
+    pub fn set_timeline(&self, value: Option<TimelineView>) {
+        let this = self.instance();
+        if let Some(timeline) = &value {
+            timeline.connect_notify_local(
+                Some("inspected-sample-count"),
+                clone!(@weak this => move |_, _| this.rebuild()),
+            );
+        }
+
+        *self.timeline.borrow_mut() = value;
+        this.rebuild();
+    }
+}
+
+#[object_subclass]
+impl ObjectSubclass for SampleInspectorPrivate {
+    const NAME: &'static str = "WtfSampleInspector";
+    type Type = SampleInspector;
+    type ParentType = Bin;
+
+    fn class_init(klass: &mut Self::Class) {
+        klass.set_css_name("sample-inspector");
+    }
+}
+
+impl ObjectImpl for SampleInspectorPrivate {
+    fn constructed(&self, this: &Self::Type) {
+        self.parent_constructed(this);
+
+        let title = Label::new(None);
+        title.set_xalign(0.0);
+        title.set_hexpand(true);
+        title.set_ellipsize(EllipsizeMode::End);
+        title.add_css_class("heading");
+
+        let close_button = Button::from_icon_name("window-close-symbolic");
+        close_button.set_tooltip_text(Some("Close Inspector"));
+        close_button.add_css_class("flat");
+        close_button.add_css_class("circular");
+        close_button.connect_clicked(clone!(@weak this => move |_| {
+            if let Some(timeline) = this.timeline() {
+                timeline.inspect_samples(None);
+            }
+        }));
+
+        let header = gtk::Box::new(Orientation::Horizontal, 6);
+        header.append(&title);
+        header.append(&close_button);
+
+        let subtitle = Label::new(None);
+        subtitle.set_xalign(0.0);
+        subtitle.set_ellipsize(EllipsizeMode::End);
+        subtitle.add_css_class("dim-label");
+
+        let list = ListBox::new();
+        list.set_selection_mode(SelectionMode::None);
+        list.add_css_class("boxed-list");
+
+        let scrolled_window = ScrolledWindow::new();
+        scrolled_window.set_child(Some(&list));
+        scrolled_window.set_min_content_height(160);
+        scrolled_window.set_max_content_height(320);
+        scrolled_window.set_propagate_natural_height(true);
+
+        let content = gtk::Box::new(Orientation::Vertical, 6);
+        content.append(&header);
+        content.append(&subtitle);
+        content.append(&scrolled_window);
+        this.set_child(Some(&content));
+
+        self.title.set(title).unwrap();
+        self.subtitle.set(subtitle).unwrap();
+        self.list.set(list).unwrap();
+    }
+
+    fn properties() -> &'static [ParamSpec] {
+        Self::derived_properties()
+    }
+    fn set_property(&self, this: &Self::Type, id: usize, value: &Value, pspec: &ParamSpec) {
+        Self::derived_set_property(self, this, id, value, pspec).unwrap();
+    }
+    fn property(&self, this: &Self::Type, id: usize, pspec: &ParamSpec) -> Value {
+        Self::derived_property(self, this, id, pspec).unwrap()
+    }
+}
+
+impl WidgetImpl for SampleInspectorPrivate {}
+impl BinImpl for SampleInspectorPrivate {}
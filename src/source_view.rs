@@ -0,0 +1,382 @@
+use crate::analysis::{function_source_lines, Function, LineWeight, SourceFile};
+use crate::timeline_view::TimelineView;
+use adw::subclass::prelude::BinImpl;
+use adw::traits::BinExt;
+use adw::Bin;
+use gio::ListStore;
+use glib::once_cell::sync::{Lazy, OnceCell};
+use glib::subclass::prelude::{
+    DerivedObjectProperties, ObjectImpl, ObjectImplExt, ObjectSubclass, ObjectSubclassExt,
+    ObjectSubclassIsExt,
+};
+use glib::{
+    clone, idle_add_local_once, markup_escape_text, object_subclass, BoxedAnyObject, Cast, Object,
+    ObjectExt, ParamSpec, Properties, StaticType, ToVariant, Value,
+};
+use gtk::subclass::prelude::{WidgetClassSubclassExt, WidgetImpl};
+use gtk::traits::{BoxExt, EditableExt, WidgetExt};
+use gtk::{
+    Accessible, Buildable, ConstraintTarget, DropDown, Entry, Label, ListItem, ListView,
+    NoSelection, Orientation, ScrolledWindow, SignalListItemFactory, Stack, StringList, Widget,
+};
+use std::cell::{Cell, RefCell};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+// Where the sources of the Rust toolchain are installed, if they are
+static RUST_SOURCE_DIRECTORY: Lazy<Option<PathBuf>> = Lazy::new(|| {
+    let output = Command::new("rustc")
+        .args(["--print", "sysroot"])
+        .output()
+        .ok()?;
+    let sysroot = String::from_utf8(output.stdout).ok()?;
+    Some(Path::new(sysroot.trim()).join("lib/rustlib/src/rust"))
+});
+
+static CARGO_HOME: Lazy<Option<PathBuf>> = Lazy::new(|| {
+    env::var_os("CARGO_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".cargo")))
+});
+
+glib::wrapper! {
+    pub struct SourceView(ObjectSubclass<SourceViewPrivate>)
+    @extends Bin, Widget,
+    @implements Accessible, Buildable, ConstraintTarget;
+}
+
+impl SourceView {
+    pub fn new() -> Self {
+        Object::new(&[]).unwrap()
+    }
+
+    pub fn show_function(&self, function: Function) {
+        self.imp().function.set(Some(function));
+        self.queue_rebuild();
+    }
+
+    // Selection changes arrive on every drag update, so coalesce them into one rebuild
+    fn queue_rebuild(&self) {
+        if !self.imp().rebuild_queued.replace(true) {
+            idle_add_local_once(clone!(@weak self as this => move || {
+                this.imp().rebuild_queued.set(false);
+                this.rebuild();
+            }));
+        }
+    }
+
+    // Re-attribute the function's samples to its source lines, and list its source files
+    fn rebuild(&self) {
+        let imp = self.imp();
+        let files = self.timeline().and_then(|timeline| {
+            let function = imp.function.get()?;
+            let (start, end) = timeline.analysis_time_range()?;
+            let profile = timeline.analysis_profile()?;
+            let files = function_source_lines(&profile, function, start, end)
+                .into_iter()
+                .map(|file| (profile.strings.get(file.file).to_owned(), file))
+                .collect::<Vec<_>>();
+            Some(files)
+        });
+
+        imp.files.replace(files.unwrap_or_default());
+
+        let widgets = imp.widgets.get().unwrap();
+        let files = imp.files.borrow();
+        let file_names = files
+            .iter()
+            .map(|(path, _)| path.as_str())
+            .collect::<Vec<_>>();
+        widgets.file_dropdown.set_visible(file_names.len() > 1);
+        let file_names = StringList::new(&file_names);
+        drop(files);
+        widgets.file_dropdown.set_model(Some(&file_names));
+        self.show_file(widgets.file_dropdown.selected());
+    }
+
+    fn show_file(&self, index: u32) {
+        let imp = self.imp();
+        let widgets = imp.widgets.get().unwrap();
+        let files = imp.files.borrow();
+        let (path, file) = match files.get(index as usize) {
+            Some(file) => file,
+            None => {
+                let message = match imp.function.get() {
+                    Some(_) => "No line information for this function",
+                    None => "Select a function to see its source",
+                };
+                widgets.message.set_label(message);
+                widgets.stack.set_visible_child(&widgets.message);
+                return;
+            }
+        };
+
+        let text = match read_source_file(path, &widgets.remap_entry.text()) {
+            Ok(text) => text,
+            Err(error) => {
+                widgets.message.set_label(&error);
+                widgets.stack.set_visible_child(&widgets.message);
+                return;
+            }
+        };
+
+        let max_weight = file
+            .lines
+            .values()
+            .map(|weight| weight.total_weight)
+            .max()
+            .unwrap_or_default()
+            .max(1);
+        let model = ListStore::new(BoxedAnyObject::static_type());
+        let mut hottest_line = (0, 0);
+        for (i, text) in text.lines().enumerate() {
+            let number = i as u32 + 1;
+            let weight = file.lines.get(&number).copied().unwrap_or_default();
+            if weight.total_weight > hottest_line.1 {
+                hottest_line = (i, weight.total_weight);
+            }
+            model.append(&BoxedAnyObject::new(SourceLine {
+                number,
+                text: text.to_owned(),
+                weight,
+                heat: weight.total_weight as f64 / max_weight as f64,
+            }));
+        }
+
+        widgets
+            .list_view
+            .set_model(Some(&NoSelection::new(Some(&model))));
+        widgets.stack.set_visible_child(&widgets.scrolled_window);
+        let _ = widgets.list_view.activate_action(
+            "list.scroll-to-item",
+            Some(&(hottest_line.0 as u32).to_variant()),
+        );
+    }
+}
+
+// Read a source file from where it was when the profiled program was built, or from where it's likely
+// to be on this machine. remaps are "build prefix=local prefix" pairs separated by ';'.
+pub fn read_source_file(path: &str, remaps: &str) -> Result<String, String> {
+    let mut candidates = Vec::new();
+    let remaps = remaps.split(';').filter_map(|remap| remap.split_once('='));
+    for (build_prefix, local_prefix) in remaps {
+        if let Some(rest) = path.strip_prefix(build_prefix.trim()) {
+            candidates.push(Path::new(local_prefix.trim()).join(rest.trim_start_matches('/')));
+        }
+    }
+
+    // The toolchain's debug info refers to its sources as /rustc/<commit hash>/...
+    if let Some((_, rest)) = path.strip_prefix("/rustc/").and_then(|p| p.split_once('/')) {
+        if let Some(rust_source_directory) = &*RUST_SOURCE_DIRECTORY {
+            candidates.push(rust_source_directory.join(rest));
+        }
+    }
+    // Crates from crates.io are in the cargo home of whichever machine built the program
+    if let Some((_, rest)) = path.split_once("/.cargo/registry/") {
+        if let Some(cargo_home) = &*CARGO_HOME {
+            candidates.push(cargo_home.join("registry").join(rest));
+        }
+    }
+    candidates.push(PathBuf::from(path));
+
+    candidates
+        .iter()
+        .find_map(|candidate| fs::read_to_string(candidate).ok())
+        .ok_or_else(|| format!("Could not read {path}, try remapping its path"))
+}
+
+struct SourceLine {
+    number: u32,
+    text: String,
+    weight: LineWeight,
+    // Total weight relative to the file's heaviest line
+    heat: f64,
+}
+
+fn new_line_factory() -> SignalListItemFactory {
+    let factory = SignalListItemFactory::new();
+    factory.connect_setup(|_, list_item| {
+        let self_weight = Label::new(None);
+        let total_weight = Label::new(None);
+        let number = Label::new(None);
+        for label in [&self_weight, &total_weight, &number] {
+            label.set_xalign(1.0);
+            label.set_width_chars(6);
+            label.add_css_class("numeric");
+            label.add_css_class("dim-label");
+        }
+        let text = Label::new(None);
+        text.set_xalign(0.0);
+        text.set_hexpand(true);
+
+        let row = gtk::Box::new(Orientation::Horizontal, 12);
+        row.add_css_class("monospace");
+        row.append(&self_weight);
+        row.append(&total_weight);
+        row.append(&number);
+        row.append(&text);
+        list_item.set_child(Some(&row));
+    });
+    factory.connect_bind(|_, list_item| {
+        let labels = list_item_labels(list_item);
+        let line = list_item
+            .item()
+            .unwrap()
+            .downcast::<BoxedAnyObject>()
+            .unwrap();
+        let line = line.borrow::<SourceLine>();
+
+        let weight_label = |weight: u64| match weight {
+            0 => String::new(),
+            weight => weight.to_string(),
+        };
+        labels[0].set_label(&weight_label(line.weight.self_weight));
+        labels[1].set_label(&weight_label(line.weight.total_weight));
+        labels[2].set_label(&line.number.to_string());
+
+        labels[3].set_markup(&heat_markup(&line.text, line.heat));
+    });
+    factory
+}
+
+// Hotter text gets a more opaque background, heat is from 0 to 1
+pub fn heat_markup(text: &str, heat: f64) -> String {
+    let text = markup_escape_text(text);
+    match (heat * 60.0) as u32 {
+        0 => text.to_string(),
+        alpha => format!("<span background=\"#ff5000\" bgalpha=\"{alpha}%\">{text}</span>"),
+    }
+}
+
+// The labels of a row, in order
+pub fn list_item_labels(list_item: &ListItem) -> Vec<Label> {
+    let mut labels = Vec::new();
+    let mut child = list_item.child().unwrap().first_child();
+    while let Some(label) = child {
+        child = label.next_sibling();
+        labels.push(label.downcast::<Label>().unwrap());
+    }
+    labels
+}
+
+struct SourceWidgets {
+    file_dropdown: DropDown,
+    remap_entry: Entry,
+    stack: Stack,
+    message: Label,
+    scrolled_window: ScrolledWindow,
+    list_view: ListView,
+}
+
+// ------------------------------------------------------------------------------
+
+#[derive(Properties, Default)]
+pub struct SourceViewPrivate {
+    #[property(get, set = Self::set_timeline, builder(TimelineView::static_type()))]
+    timeline: RefCell<Option<TimelineView>>,
+
+    function: Cell<Option<Function>>,
+    // The function's source files, heaviest first, with their paths
+    files: RefCell<Vec<(String, SourceFile)>>,
+    widgets: OnceCell<SourceWidgets>,
+    rebuild_queued: Cell<bool>,
+}
+
+impl SourceViewPrivate {
+    pub fn set_timeline(&self, value: Option<TimelineView>) {
+        let this = self.instance();
+        if let Some(timeline) = &value {
+            for property in [
+                "profile",
+                "selected-time-range",
+                "transform-count",
+                "selected-thread-count",
+            ] {
+                timeline.connect_notify_local(
+                    Some(property),
+                    clone!(@weak this => move |_, _| this.queue_rebuild()),
+                );
+            }
+        }
+
+        *self.timeline.borrow_mut() = value;
+        this.queue_rebuild();
+    }
+}
+
+#[object_subclass]
+impl ObjectSubclass for SourceViewPrivate {
+    const NAME: &'static str = "WtfSourceView";
+    type Type = SourceView;
+    type ParentType = Bin;
+
+    fn class_init(klass: &mut Self::Class) {
+        klass.set_css_name("source-view");
+    }
+}
+
+impl ObjectImpl for SourceViewPrivate {
+    fn constructed(&self, this: &Self::Type) {
+        self.parent_constructed(this);
+
+        let file_dropdown = DropDown::from_strings(&[]);
+        file_dropdown.set_hexpand(true);
+        file_dropdown.connect_selected_notify(clone!(@weak this => move |file_dropdown| {
+            this.show_file(file_dropdown.selected());
+        }));
+
+        // Sources of programs built elsewhere (e.g. in CI) are at different paths on this machine
+        let remap_entry = Entry::new();
+        remap_entry.set_placeholder_text(Some("/build/path=/local/path; …"));
+        remap_entry.set_tooltip_text(Some("Remap source path prefixes"));
+        remap_entry.set_hexpand(true);
+        remap_entry.connect_activate(clone!(@weak this => move |_| this.queue_rebuild()));
+
+        let header = gtk::Box::new(Orientation::Horizontal, 6);
+        header.append(&file_dropdown);
+        header.append(&remap_entry);
+
+        let list_view = ListView::new(None::<&NoSelection>, Some(&new_line_factory()));
+        let scrolled_window = ScrolledWindow::new();
+        scrolled_window.set_child(Some(&list_view));
+
+        let message = Label::new(None);
+        message.add_css_class("dim-label");
+        message.set_wrap(true);
+
+        let stack = Stack::new();
+        stack.add_child(&scrolled_window);
+        stack.add_child(&message);
+        stack.set_vexpand(true);
+
+        let content = gtk::Box::new(Orientation::Vertical, 6);
+        content.append(&header);
+        content.append(&stack);
+        this.set_child(Some(&content));
+
+        let widgets = SourceWidgets {
+            file_dropdown,
+            remap_entry,
+            stack,
+            message,
+            scrolled_window,
+            list_view,
+        };
+        self.widgets.set(widgets).ok().unwrap();
+    }
+
+    fn properties() -> &'static [ParamSpec] {
+        Self::derived_properties()
+    }
+    fn set_property(&self, this: &Self::Type, id: usize, value: &Value, pspec: &ParamSpec) {
+        Self::derived_set_property(self, this, id, value, pspec).unwrap();
+    }
+    fn property(&self, this: &Self::Type, id: usize, pspec: &ParamSpec) -> Value {
+        Self::derived_property(self, this, id, pspec).unwrap()
+    }
+}
+
+impl WidgetImpl for SourceViewPrivate {}
+impl BinImpl for SourceViewPrivate {}
@@ -0,0 +1,143 @@
+use crate::timeline_view::TimelineView;
+use adw::subclass::prelude::BinImpl;
+use adw::traits::BinExt;
+use adw::Bin;
+use glib::once_cell::sync::OnceCell;
+use glib::subclass::prelude::{
+    DerivedObjectProperties, ObjectImpl, ObjectImplExt, ObjectSubclass, ObjectSubclassExt,
+    ObjectSubclassIsExt,
+};
+use glib::{clone, object_subclass, ObjectExt, ParamSpec, Properties, StaticType, Value};
+use gtk::subclass::prelude::{WidgetClassSubclassExt, WidgetImpl};
+use gtk::traits::{ActionableExt, BoxExt, ButtonExt, WidgetExt};
+use gtk::{
+    Accessible, Buildable, Button, ConstraintTarget, Label, Orientation, PolicyType,
+    ScrolledWindow, Widget,
+};
+use std::cell::RefCell;
+
+glib::wrapper! {
+    pub struct StackTransformBar(ObjectSubclass<StackTransformBarPrivate>)
+    @extends Bin, Widget,
+    @implements Accessible, Buildable, ConstraintTarget;
+}
+
+impl StackTransformBar {
+    // One breadcrumb per transform, clicking one undoes every transform after it
+    fn update_breadcrumbs(&self) {
+        let breadcrumbs = self.imp().breadcrumbs.get().unwrap();
+        while let Some(child) = breadcrumbs.first_child() {
+            breadcrumbs.remove(&child);
+        }
+
+        let timeline = match self.timeline() {
+            Some(timeline) => timeline,
+            None => return,
+        };
+        let profile = match timeline.profile_data() {
+            Some(profile) => profile,
+            None => return,
+        };
+        let transforms = timeline.transforms();
+        self.set_visible(!transforms.is_empty());
+
+        let descriptions = transforms
+            .iter()
+            .map(|transform| transform.description(&profile));
+        for (count, description) in ["Complete Profile".to_owned()]
+            .into_iter()
+            .chain(descriptions)
+            .enumerate()
+        {
+            if count != 0 {
+                breadcrumbs.append(&Label::new(Some("›")));
+            }
+
+            let button = Button::with_label(&description);
+            button.add_css_class("flat");
+            button.set_sensitive(count != transforms.len());
+            button.connect_clicked(clone!(@weak timeline => move |_| {
+                timeline.truncate_transforms(count);
+            }));
+            breadcrumbs.append(&button);
+        }
+    }
+}
+
+// ------------------------------------------------------------------------------
+
+#[derive(Properties, Default)]
+pub struct StackTransformBarPrivate {
+    #[property(get, set = Self::set_timeline, builder(TimelineView::static_type()))]
+    timeline: RefCell<Option<TimelineView>>,
+
+    breadcrumbs: OnceCell<gtk::Box>,
+}
+
+impl StackTransformBarPrivate {
+    pub fn set_timeline(&self, value: Option<TimelineView>) {
+        let this = self.instance();
+        if let Some(timeline) = &value {
+            for property in ["profile", "transform-count"] {
+                timeline.connect_notify_local(
+                    Some(property),
+                    clone!(@weak this => move |_, _| this.update_breadcrumbs()),
+                );
+            }
+        }
+
+        *self.timeline.borrow_mut() = value;
+        this.update_breadcrumbs();
+    }
+}
+
+#[object_subclass]
+impl ObjectSubclass for StackTransformBarPrivate {
+    const NAME: &'static str = "WtfStackTransformBar";
+    type Type = StackTransformBar;
+    type ParentType = Bin;
+
+    fn class_init(klass: &mut Self::Class) {
+        klass.set_css_name("stack-transform-bar");
+    }
+}
+
+impl ObjectImpl for StackTransformBarPrivate {
+    fn constructed(&self, this: &Self::Type) {
+        self.parent_constructed(this);
+
+        let breadcrumbs = gtk::Box::new(Orientation::Horizontal, 0);
+
+        let undo_button = Button::from_icon_name("edit-undo-symbolic");
+        undo_button.add_css_class("flat");
+        undo_button.set_tooltip_text(Some("Undo Transform"));
+        undo_button.set_action_name(Some("profile.undo-transform"));
+
+        // Long chains of transforms scroll instead of widening the page
+        let scrolled_window = ScrolledWindow::new();
+        scrolled_window.set_policy(PolicyType::Automatic, PolicyType::Never);
+        scrolled_window.set_child(Some(&breadcrumbs));
+        scrolled_window.set_hexpand(true);
+
+        let content = gtk::Box::new(Orientation::Horizontal, 6);
+        content.append(&undo_button);
+        content.append(&scrolled_window);
+        this.set_child(Some(&content));
+
+        self.breadcrumbs.set(breadcrumbs).unwrap();
+        this.set_visible(false);
+    }
+
+    fn properties() -> &'static [ParamSpec] {
+        Self::derived_properties()
+    }
+    fn set_property(&self, this: &Self::Type, id: usize, value: &Value, pspec: &ParamSpec) {
+        Self::derived_set_property(self, this, id, value, pspec).unwrap();
+    }
+    fn property(&self, this: &Self::Type, id: usize, pspec: &ParamSpec) -> Value {
+        Self::derived_property(self, this, id, pspec).unwrap()
+    }
+}
+
+impl WidgetImpl for StackTransformBarPrivate {}
+impl BinImpl for StackTransformBarPrivate {}
@@ -0,0 +1,311 @@
+use crate::analysis::sample_columns;
+use crate::timeline_range::TimelineRangePrivatePropertiesExt;
+use crate::timeline_view::{marker_color, TimelineView, TimelineViewPrivatePropertiesExt};
+use adw::StyleManager;
+use glib::subclass::prelude::{ObjectImpl, ObjectImplExt, ObjectSubclass, ObjectSubclassIsExt};
+use glib::{object_subclass, Cast, Object, Properties};
+use gtk::gdk::RGBA;
+use gtk::graphene::Rect;
+use gtk::gsk::RoundedRect;
+use gtk::prelude::{EventControllerExt, GestureDragExt, GestureExt};
+use gtk::subclass::prelude::{WidgetClassSubclassExt, WidgetImpl, WidgetImplExt};
+use gtk::traits::{StyleContextExt, WidgetExt};
+use gtk::{
+    Accessible, Buildable, ConstraintTarget, EventControllerMotion, EventSequenceState,
+    GestureDrag, Orientation, Snapshot, Widget,
+};
+use std::cell::Cell;
+
+const MINIMAP_HEIGHT: i32 = 32;
+// How close to a viewport edge, in pixels, grabs the edge instead of the whole viewport
+const EDGE_GRAB_DISTANCE: f64 = 6.0;
+
+glib::wrapper! {
+    pub struct TimelineMinimap(ObjectSubclass<TimelineMinimapPrivate>)
+    @extends Widget,
+    @implements Accessible, Buildable, ConstraintTarget;
+}
+
+impl TimelineMinimap {
+    pub fn new() -> Self {
+        Object::new(&[]).unwrap()
+    }
+
+    fn timeline_view(&self) -> TimelineView {
+        self.parent().unwrap().downcast::<TimelineView>().unwrap()
+    }
+
+    // The minimap always spans the whole profile, unlike the timeline view
+    fn time_to_minimap_point(&self, time: u64) -> f64 {
+        let profile_time_range = self.timeline_view().profile_time_range();
+        let offset = time.saturating_sub(profile_time_range.start()) as f64;
+        (offset * self.width() as f64 / profile_time_range.duration().max(1) as f64)
+            .clamp(0.0, self.width() as f64)
+    }
+
+    fn minimap_to_time_point(&self, minimap_point: f64) -> u64 {
+        let profile_time_range = self.timeline_view().profile_time_range();
+        let offset =
+            minimap_point * profile_time_range.duration() as f64 / self.width().max(1) as f64;
+        (profile_time_range.start() as f64 + offset.round()).clamp(
+            profile_time_range.start() as f64,
+            profile_time_range.end() as f64,
+        ) as u64
+    }
+
+    // The part of the viewport (the display time range) a point grabs, if any
+    fn viewport_part_at(&self, minimap_point: f64) -> Option<ViewportPart> {
+        let display_time_range = self.timeline_view().display_time_range();
+        let viewport_start = self.time_to_minimap_point(display_time_range.start());
+        let viewport_end = self.time_to_minimap_point(display_time_range.end());
+        if (minimap_point - viewport_end).abs() <= EDGE_GRAB_DISTANCE {
+            Some(ViewportPart::End)
+        } else if (minimap_point - viewport_start).abs() <= EDGE_GRAB_DISTANCE {
+            Some(ViewportPart::Start)
+        } else if (viewport_start..=viewport_end).contains(&minimap_point) {
+            Some(ViewportPart::Body)
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ViewportPart {
+    Start,
+    End,
+    Body,
+}
+
+// Dragging the viewport pans, dragging its edges zooms, and pressing outside it jumps there
+fn begin_drag(gesture: &GestureDrag, x: f64, _: f64) {
+    gesture.set_state(EventSequenceState::Claimed);
+    let minimap = gesture.widget().downcast::<TimelineMinimap>().unwrap();
+    let timeline_view = minimap.timeline_view();
+
+    let part = match minimap.viewport_part_at(x) {
+        Some(part) => part,
+        None => {
+            let duration = timeline_view.display_time_range().duration();
+            let center = minimap.minimap_to_time_point(x);
+            timeline_view.show_time_range(center.saturating_sub(duration / 2), duration);
+            ViewportPart::Body
+        }
+    };
+    let display_time_range = timeline_view.display_time_range();
+    let imp = minimap.imp();
+    imp.dragged_part.set(Some(part));
+    imp.drag_start_time.set(display_time_range.start());
+}
+
+fn update_drag(gesture: &GestureDrag, _: f64, _: f64) {
+    let minimap = gesture.widget().downcast::<TimelineMinimap>().unwrap();
+    let timeline_view = minimap.timeline_view();
+    let imp = minimap.imp();
+    let drag_point = gesture.start_point().unwrap().0 + gesture.offset().unwrap().0;
+    let display_time_range = timeline_view.display_time_range();
+
+    match imp.dragged_part.get() {
+        Some(ViewportPart::Body) => {
+            let profile_duration = timeline_view.profile_time_range().duration() as f64;
+            let time_offset =
+                gesture.offset().unwrap().0 * profile_duration / minimap.width().max(1) as f64;
+            let start = (imp.drag_start_time.get() as f64 + time_offset)
+                .max(0.0)
+                .round();
+            timeline_view.show_time_range(start as u64, display_time_range.duration());
+        }
+        // Edges can't be dragged past each other
+        Some(ViewportPart::Start) => {
+            let start = minimap
+                .minimap_to_time_point(drag_point)
+                .min(display_time_range.end().saturating_sub(1));
+            timeline_view.show_time_range(start, display_time_range.end() - start);
+        }
+        Some(ViewportPart::End) => {
+            let end = minimap
+                .minimap_to_time_point(drag_point)
+                .max(display_time_range.start() + 1);
+            timeline_view
+                .show_time_range(display_time_range.start(), end - display_time_range.start());
+        }
+        None => {}
+    }
+}
+
+fn finish_drag(gesture: &GestureDrag, _: f64, _: f64) {
+    let minimap = gesture.widget().downcast::<TimelineMinimap>().unwrap();
+    minimap.imp().dragged_part.set(None);
+}
+
+fn track_pointer(controller: &EventControllerMotion, x: f64, _: f64) {
+    let minimap = controller.widget().downcast::<TimelineMinimap>().unwrap();
+    let cursor = match minimap.viewport_part_at(x) {
+        Some(ViewportPart::Start | ViewportPart::End) => "col-resize",
+        Some(ViewportPart::Body) => "grab",
+        None => "pointer",
+    };
+    minimap.set_cursor_from_name(Some(cursor));
+}
+
+// ------------------------------------------------------------------------------
+
+#[derive(Properties, Default)]
+pub struct TimelineMinimapPrivate {
+    dragged_part: Cell<Option<ViewportPart>>,
+    // Start of the display time range when the viewport was grabbed
+    drag_start_time: Cell<u64>,
+}
+
+#[object_subclass]
+impl ObjectSubclass for TimelineMinimapPrivate {
+    const NAME: &'static str = "WtfTimelineMinimap";
+    type Type = TimelineMinimap;
+    type ParentType = Widget;
+
+    fn class_init(klass: &mut Self::Class) {
+        klass.set_css_name("timeline-minimap");
+    }
+}
+
+impl ObjectImpl for TimelineMinimapPrivate {
+    fn constructed(&self, this: &Self::Type) {
+        self.parent_constructed(this);
+
+        this.set_hexpand(true);
+
+        let drag_controller = GestureDrag::new();
+        drag_controller.connect_drag_begin(begin_drag);
+        drag_controller.connect_drag_update(update_drag);
+        drag_controller.connect_drag_end(finish_drag);
+        this.add_controller(&drag_controller);
+
+        let motion_controller = EventControllerMotion::new();
+        motion_controller.connect_motion(track_pointer);
+        this.add_controller(&motion_controller);
+    }
+}
+
+impl WidgetImpl for TimelineMinimapPrivate {
+    fn measure(&self, _: &Self::Type, orientation: Orientation, _: i32) -> (i32, i32, i32, i32) {
+        match orientation {
+            Orientation::Horizontal => (0, 0, -1, -1),
+            Orientation::Vertical => (MINIMAP_HEIGHT, MINIMAP_HEIGHT, -1, -1),
+            _ => unreachable!(),
+        }
+    }
+
+    // Sample counts across the whole profile, with the viewport outlined and the selection and
+    // markers marked
+    fn snapshot(&self, this: &Self::Type, snapshot: &Snapshot) {
+        self.parent_snapshot(this, snapshot);
+
+        let timeline_view = this.timeline_view();
+        let profile = match timeline_view.profile_data() {
+            Some(profile) => profile,
+            None => return,
+        };
+        let (width, height) = (this.width() as f32, this.height() as f32);
+        let style_context = this.style_context();
+        let accent_color = style_context
+            .lookup_color("accent_color")
+            .unwrap_or(RGBA::new(0.21, 0.52, 0.89, 1.0));
+        let foreground_color = style_context.color();
+
+        // Opaque, so the timeline's selection shading doesn't show through
+        let background_color = style_context
+            .lookup_color("view_bg_color")
+            .unwrap_or(RGBA::WHITE);
+        snapshot.append_color(&background_color, &Rect::new(0.0, 0.0, width, height));
+
+        // Every thread's samples, summarized the same way as rows are
+        let profile_time_range = timeline_view.profile_time_range();
+        let column_count = this.width().max(0) as usize;
+        let mut counts = vec![0; column_count];
+        let (start, end) = (profile_time_range.start(), profile_time_range.end());
+        for &tid in profile.threads.tid.iter() {
+            let columns = timeline_view
+                .sample_pyramid(tid)
+                .and_then(|sample_pyramid| sample_pyramid.columns(start, end, column_count))
+                .unwrap_or_else(|| sample_columns(&profile, tid, start, end, column_count));
+            for (count, column) in counts.iter_mut().zip(columns) {
+                *count += column.count;
+            }
+        }
+        let max_count = counts.iter().copied().max().unwrap_or_default().max(1) as f32;
+        let mut activity_color = foreground_color;
+        activity_color.set_alpha(0.3);
+        for (x, count) in counts.into_iter().enumerate() {
+            let bar_height = (count as f32 / max_count * (height - 2.0)).round();
+            if bar_height > 0.0 {
+                snapshot.append_color(
+                    &activity_color,
+                    &Rect::new(x as f32, height - bar_height, 1.0, bar_height),
+                );
+            }
+        }
+
+        // Markers are ticks along the top, as wide as their span
+        let marker_color = marker_color(this);
+        for marker in timeline_view.markers() {
+            let marker_start = this.time_to_minimap_point(marker.start) as f32;
+            let marker_end = this.time_to_minimap_point(marker.end()) as f32;
+            snapshot.append_color(
+                &marker_color,
+                &Rect::new(
+                    marker_start - 0.5,
+                    0.0,
+                    (marker_end - marker_start).max(1.0),
+                    3.0,
+                ),
+            );
+        }
+
+        if let Some(selected_time_range) = timeline_view.selected_time_range() {
+            let selection_start = this.time_to_minimap_point(selected_time_range.start()) as f32;
+            let selection_end = this.time_to_minimap_point(selected_time_range.end()) as f32;
+            let mut selection_color = accent_color;
+            selection_color.set_alpha(0.25);
+            snapshot.append_color(
+                &selection_color,
+                &Rect::new(
+                    selection_start,
+                    0.0,
+                    (selection_end - selection_start).max(1.0),
+                    height,
+                ),
+            );
+        }
+
+        // Outside the viewport is dimmed, and the viewport is outlined with grips on its edges
+        let display_time_range = timeline_view.display_time_range();
+        let viewport_start = this.time_to_minimap_point(display_time_range.start()) as f32;
+        let viewport_end = this.time_to_minimap_point(display_time_range.end()) as f32;
+        let is_dark_mode = StyleManager::default().is_dark();
+        let dim_color = RGBA::new(0.0, 0.0, 0.0, if is_dark_mode { 0.3 } else { 0.1 });
+        snapshot.append_color(&dim_color, &Rect::new(0.0, 0.0, viewport_start, height));
+        snapshot.append_color(
+            &dim_color,
+            &Rect::new(viewport_end, 0.0, width - viewport_end, height),
+        );
+
+        let viewport = Rect::new(
+            viewport_start,
+            0.0,
+            (viewport_end - viewport_start).max(2.0),
+            height,
+        );
+        snapshot.append_border(
+            &RoundedRect::from_rect(viewport, 3.0),
+            &[1.0; 4],
+            &[accent_color; 4],
+        );
+        for x in [viewport.x(), viewport.x() + viewport.width()] {
+            let grip =
+                RoundedRect::from_rect(Rect::new(x - 2.0, height / 2.0 - 6.0, 4.0, 12.0), 2.0);
+            snapshot.push_rounded_clip(&grip);
+            snapshot.append_color(&accent_color, grip.bounds());
+            snapshot.pop();
+        }
+    }
+}
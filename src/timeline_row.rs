@@ -1,16 +1,36 @@
-use crate::perf_data_parser::Sample;
+use crate::analysis::{sample_columns, thread_spans, Category, SampleGroup};
+use crate::flame_graph::{append_frame, category_color};
+use crate::timeline_range::TimelineRangePrivatePropertiesExt;
 use crate::timeline_view::{TimelineView, TimelineViewPrivatePropertiesExt};
+use crate::wtf_profile::{Profile, NO_STACK};
+use gio::{Menu, MenuItem};
 use glib::once_cell::sync::OnceCell;
-use glib::subclass::prelude::{DerivedObjectProperties, ObjectImpl, ObjectImplExt, ObjectSubclass};
+use glib::subclass::prelude::{
+    DerivedObjectProperties, ObjectImpl, ObjectImplExt, ObjectSubclass, ObjectSubclassIsExt,
+};
 use glib::{
     object_subclass, BoxedAnyObject, Cast, Object, ObjectExt, ParamSpec, Properties, StaticType,
     Value,
 };
+use glib::{IsA, ToVariant};
+use gtk::gdk::{ModifierType, Rectangle, RGBA};
 use gtk::graphene::Rect;
+use gtk::gsk::RoundedRect;
+use gtk::prelude::{EventControllerExt, GestureExt, GestureSingleExt};
 use gtk::subclass::prelude::{WidgetClassSubclassExt, WidgetImpl, WidgetImplExt};
-use gtk::traits::{StyleContextExt, WidgetExt};
-use gtk::{Accessible, Buildable, ConstraintTarget, Orientation, Snapshot, Widget};
-use std::cell::Ref;
+use gtk::traits::{PopoverExt, StyleContextExt, WidgetExt};
+use gtk::{
+    Accessible, Buildable, ConstraintTarget, EventSequenceState, GestureClick, Orientation,
+    PopoverMenu, Snapshot, Tooltip, Widget,
+};
+use std::cell::{Cell, Ref, RefCell};
+use std::rc::Rc;
+
+const SPAN_HEIGHT: f32 = 18.0;
+// Deeper spans aren't drawn, to keep rows of deeply recursive threads a usable height
+const MAX_SPAN_DEPTH: u32 = 40;
+// How far from a sample's bar, in pixels, hovering or clicking still finds it
+const SAMPLE_HIT_DISTANCE: f64 = 3.0;
 
 glib::wrapper! {
     pub struct TimelineRow(ObjectSubclass<TimelineRowPrivate>)
@@ -19,9 +39,175 @@ glib::wrapper! {
 }
 
 impl TimelineRow {
-    pub fn new(samples: Vec<Sample>) -> Self {
-        Object::new(&[("samples", &BoxedAnyObject::new(samples))]).unwrap()
+    pub fn new(profile: Rc<Profile>, tid: u32, header: &str) -> Self {
+        Object::new(&[
+            ("profile", &BoxedAnyObject::new(profile)),
+            ("tid", &tid),
+            ("header", &header),
+        ])
+        .unwrap()
+    }
+}
+
+// A label over the top-left corner of a row, on a background so it stays readable over the row
+pub fn append_row_header(widget: &impl IsA<Widget>, snapshot: &Snapshot, header: &str) {
+    let layout = widget.create_pango_layout(Some(header));
+    let (width, height) = layout.pixel_size();
+    let mut background = widget
+        .style_context()
+        .lookup_color("view_bg_color")
+        .unwrap_or(RGBA::WHITE);
+    background.set_alpha(0.8);
+    snapshot.append_color(
+        &background,
+        &Rect::new(0.0, 0.0, width as f32 + 8.0, height as f32),
+    );
+    snapshot.render_layout(&widget.style_context(), 4.0, 0.0, &layout);
+}
+
+// Ctrl+click selects a thread, limiting the analysis views to the selected threads
+fn toggle_thread_selected(gesture: &GestureClick, _: i32, _: f64, _: f64) {
+    if gesture
+        .current_event_state()
+        .contains(ModifierType::CONTROL_MASK)
+    {
+        gesture.set_state(EventSequenceState::Claimed);
+        let timeline_row = gesture.widget().downcast::<TimelineRow>().unwrap();
+        timeline_row
+            .activate_action(
+                "timeline.select-thread",
+                Some(&timeline_row.tid().to_variant()),
+            )
+            .unwrap();
+    }
+}
+
+// Clicking a sample opens its stack in the inspector
+fn inspect_clicked_samples(gesture: &GestureClick, _: i32, x: f64, _: f64) {
+    if gesture
+        .current_event_state()
+        .contains(ModifierType::CONTROL_MASK)
+    {
+        return;
+    }
+    let timeline_row = gesture.widget().downcast::<TimelineRow>().unwrap();
+    let timeline_view = timeline_row
+        .parent()
+        .unwrap()
+        .downcast::<TimelineView>()
+        .unwrap();
+    if let Some(samples) = timeline_row.imp().samples_at(&timeline_view, x) {
+        timeline_view.inspect_samples(Some(samples));
+    }
+}
+
+// The time, thread and leaf function of the samples under the pointer
+fn describe_hovered_samples(
+    timeline_row: &TimelineRow,
+    x: i32,
+    _: i32,
+    _: bool,
+    tooltip: &Tooltip,
+) -> bool {
+    let timeline_view = match timeline_row
+        .parent()
+        .and_then(|parent| parent.downcast::<TimelineView>().ok())
+    {
+        Some(timeline_view) => timeline_view,
+        None => return false,
+    };
+    let samples = match timeline_row.imp().samples_at(&timeline_view, x as f64) {
+        Some(samples) => samples,
+        None => return false,
+    };
+    let profile: Ref<Rc<Profile>> = timeline_row.imp().profile.get().unwrap().borrow();
+
+    // Times are shown to the precision of a pixel
+    let resolution =
+        timeline_view.display_time_range().duration() / timeline_view.width().max(1) as u64;
+    let start_time = timeline_view.format_time_label(samples.start_time(&profile), resolution);
+    let time = if samples.sample_count() == 1 {
+        start_time
+    } else {
+        let end_time = timeline_view.format_time_label(samples.end_time(&profile), resolution);
+        format!(
+            "{} samples, {start_time} – {end_time}",
+            samples.sample_count()
+        )
+    };
+    let thread = match profile.thread_name(samples.tid) {
+        Some(name) => format!("{name} ({})", samples.tid),
+        None => format!("Thread {}", samples.tid),
+    };
+    let mut function = match profile.stack_frames(samples.stack).next() {
+        Some(frame) => profile.function_name(frame).to_owned(),
+        None => "No Stack".to_owned(),
+    };
+    if samples.sample_count() > 1 {
+        function.push_str(&format!(" (in {} of them)", samples.stack_count));
     }
+
+    tooltip.set_text(Some(&format!("{time}\n{thread}\n{function}")));
+    true
+}
+
+fn popup_thread_menu(gesture: &GestureClick, _: i32, x: f64, y: f64) {
+    let timeline_row = gesture.widget().downcast::<TimelineRow>().unwrap();
+    let timeline_view = timeline_row
+        .parent()
+        .unwrap()
+        .downcast::<TimelineView>()
+        .unwrap();
+    let tid = timeline_row.tid();
+    let new_item = |label: &str, action: &str| {
+        let item = MenuItem::new(Some(label), None);
+        item.set_action_and_target_value(Some(action), Some(&tid.to_variant()));
+        item
+    };
+
+    let menu = Menu::new();
+    let thread_section = Menu::new();
+    thread_section.append_item(&new_item(
+        if timeline_view.is_thread_selected(tid) {
+            "Deselect Thread"
+        } else {
+            "Select Thread"
+        },
+        "timeline.select-thread",
+    ));
+    thread_section.append_item(&new_item(
+        if timeline_view.is_thread_pinned(tid) {
+            "Unpin Thread"
+        } else {
+            "Pin Thread to Top"
+        },
+        "timeline.pin-thread",
+    ));
+    thread_section.append_item(&new_item("Hide Thread", "timeline.hide-thread"));
+    menu.append_section(None, &thread_section);
+
+    let timeline_section = Menu::new();
+    if timeline_view.selected_thread_count() > 0 {
+        timeline_section.append(
+            Some("Clear Thread Selection"),
+            Some("timeline.clear-thread-selection"),
+        );
+    }
+    let hidden_thread_count = timeline_view.hidden_thread_count();
+    if hidden_thread_count > 0 {
+        timeline_section.append(
+            Some(&format!("Show {hidden_thread_count} Hidden Threads")),
+            Some("timeline.show-hidden-threads"),
+        );
+    }
+    menu.append_section(None, &timeline_section);
+
+    let popover = PopoverMenu::from_model(Some(&menu));
+    popover.set_parent(&timeline_row);
+    popover.set_has_arrow(false);
+    popover.set_pointing_to(Some(&Rectangle::new(x as i32, y as i32, 1, 1)));
+    popover.connect_closed(|popover| popover.unparent());
+    popover.popup();
 }
 
 // ------------------------------------------------------------------------------
@@ -29,7 +215,179 @@ impl TimelineRow {
 #[derive(Properties, Default)]
 pub struct TimelineRowPrivate {
     #[property(get, set, construct_only, builder(BoxedAnyObject::static_type()))]
-    samples: OnceCell<BoxedAnyObject>,
+    profile: OnceCell<BoxedAnyObject>,
+    #[property(get, set, construct_only)]
+    tid: Cell<u32>,
+    // Thread name, tid, and share of samples
+    #[property(get, set, construct_only)]
+    header: RefCell<String>,
+
+    // Deepest stack of the thread, computed on first use
+    max_depth: OnceCell<u32>,
+}
+
+impl TimelineRowPrivate {
+    fn max_depth(&self) -> u32 {
+        *self.max_depth.get_or_init(|| {
+            let profile: Ref<Rc<Profile>> = self.profile.get().unwrap().borrow();
+            let tid = self.tid.get();
+            profile
+                .thread_chunks_in_range(tid, 0, u64::MAX)
+                .map(|chunk| profile.chunks.max_depth[chunk])
+                .max()
+                .unwrap_or_default()
+        })
+    }
+
+    // The samples drawn within SAMPLE_HIT_DISTANCE of x
+    // Zoomed out, samples drawn in the same pixel as the nearest one are indistinguishable, so
+    // they're grouped together
+    fn samples_at(&self, timeline_view: &TimelineView, x: f64) -> Option<SampleGroup> {
+        let profile: Ref<Rc<Profile>> = self.profile.get().unwrap().borrow();
+        let tid = self.tid.get();
+        let samples = profile.thread_samples_in_range(
+            tid,
+            timeline_view.widget_to_time_point(x - SAMPLE_HIT_DISTANCE - 1.0),
+            timeline_view.widget_to_time_point(x + SAMPLE_HIT_DISTANCE + 1.0),
+        );
+
+        // Hit testing uses the same mapping the bars are drawn with
+        let sample_x =
+            |sample: usize| timeline_view.time_to_widget_point(profile.samples.time[sample]);
+        let nearest = samples
+            .clone()
+            .min_by(|&a, &b| (sample_x(a) - x).abs().total_cmp(&(sample_x(b) - x).abs()))?;
+        if (sample_x(nearest) - x).abs() > SAMPLE_HIT_DISTANCE {
+            return None;
+        }
+
+        let pixel = sample_x(nearest).floor();
+        let first = samples
+            .clone()
+            .find(|&sample| sample_x(sample).floor() == pixel)
+            .unwrap();
+        let last = samples
+            .rev()
+            .find(|&sample| sample_x(sample).floor() == pixel)
+            .unwrap();
+        SampleGroup::new(
+            &profile,
+            tid,
+            profile.samples.time[first],
+            profile.samples.time[last],
+        )
+    }
+
+    // Nested blocks for the functions on the thread's stack over time, outermost at the top
+    fn snapshot_flame_chart(
+        &self,
+        this: &TimelineRow,
+        timeline_view: &TimelineView,
+        snapshot: &Snapshot,
+    ) {
+        let profile: Ref<Rc<Profile>> = self.profile.get().unwrap().borrow();
+        let display_time_range = timeline_view.display_time_range();
+
+        // Only one sample per pixel is needed to draw every visible span
+        let display_duration = display_time_range.end() - display_time_range.start();
+        let min_interval = display_duration / timeline_view.width().max(1) as u64;
+
+        let search_matches = timeline_view.search_matches();
+        let spans = thread_spans(
+            &profile,
+            this.tid(),
+            display_time_range.start(),
+            display_time_range.end(),
+            min_interval,
+        );
+        for span in spans {
+            if span.depth >= MAX_SPAN_DEPTH {
+                continue;
+            }
+
+            let x = timeline_view.time_to_widget_point(span.start) as f32;
+            let width = (timeline_view.time_to_widget_point(span.end) as f32 - x).max(1.0);
+            let y = span.depth as f32 * SPAN_HEIGHT;
+            let rect = Rect::new(x, y, width, SPAN_HEIGHT - 1.0);
+            let name = profile.strings.get(span.function);
+            let category = Category::of(name, profile.strings.get(span.module));
+            let mut color = category_color(category);
+            if let Some(search_matches) = &search_matches {
+                if !search_matches.matches_function((span.function, span.module)) {
+                    color.set_alpha(0.3);
+                }
+            }
+            append_frame(this, snapshot, name, &color, &rect);
+        }
+    }
+
+    // A bar per pixel column, spanning the depths of the stacks sampled during it
+    // Columns are summarized from the thread's SamplePyramid, so drawing a row costs about the same
+    // however many samples are on screen
+    fn snapshot_samples(
+        &self,
+        this: &TimelineRow,
+        timeline_view: &TimelineView,
+        snapshot: &Snapshot,
+    ) {
+        let profile: Ref<Rc<Profile>> = self.profile.get().unwrap().borrow();
+        let display_time_range = timeline_view.display_time_range();
+        let (start, end) = (display_time_range.start(), display_time_range.end());
+        let column_count = timeline_view.width().max(0) as usize;
+
+        // Zoomed in past the pyramid's finest buckets, there are few enough samples on screen to
+        // summarize directly, and bars are widened to stay visible between sparse samples
+        let (columns, bar_width) = match timeline_view
+            .sample_pyramid(this.tid())
+            .and_then(|sample_pyramid| sample_pyramid.columns(start, end, column_count))
+        {
+            Some(columns) => (columns, 1.0),
+            None => (
+                sample_columns(&profile, this.tid(), start, end, column_count),
+                2.0,
+            ),
+        };
+
+        // While searching, columns whose deepest stack has a matching function on-stack are marked
+        // along the top of the row, and the rest are faded out
+        let search_matches = timeline_view.search_matches();
+        let match_color = this
+            .style_context()
+            .lookup_color("accent_color")
+            .unwrap_or(RGBA::new(0.21, 0.52, 0.89, 1.0));
+
+        let height = this.height() as f32;
+        for (x, column) in columns.into_iter().enumerate() {
+            if column.count == 0 {
+                continue;
+            }
+            let x = x as f32 - (bar_width - 1.0) / 2.0;
+
+            // Bars are colored by the category of the function the deepest stack was sampled in
+            let category = match column.deepest_stack {
+                NO_STACK => Category::Unknown,
+                stack => Category::of_frame(&profile, profile.stacks.frame[stack as usize]),
+            };
+            let matched = search_matches
+                .as_ref()
+                .map(|search_matches| search_matches.matches_stack(column.deepest_stack));
+            let mut color = category_color(category);
+            color.set_alpha(match matched {
+                Some(false) => 0.2,
+                _ => 0.7,
+            });
+
+            let top = height - (column.max_depth as f32).min(height);
+            let bottom = height - (column.min_depth as f32).min(height);
+            snapshot.append_color(
+                &color,
+                &Rect::new(x, top, bar_width, (bottom - top).max(1.0)),
+            );
+            if matched == Some(true) {
+                snapshot.append_color(&match_color, &Rect::new(x, 0.0, bar_width, 3.0));
+            }
+        }
+    }
 }
 
 #[object_subclass]
@@ -48,6 +406,20 @@ impl ObjectImpl for TimelineRowPrivate {
         self.parent_constructed(this);
 
         this.set_hexpand(true);
+
+        let select_controller = GestureClick::new();
+        select_controller.set_button(1);
+        select_controller.connect_pressed(toggle_thread_selected);
+        select_controller.connect_released(inspect_clicked_samples);
+        this.add_controller(&select_controller);
+
+        let menu_controller = GestureClick::new();
+        menu_controller.set_button(3);
+        menu_controller.connect_pressed(popup_thread_menu);
+        this.add_controller(&menu_controller);
+
+        this.set_has_tooltip(true);
+        this.connect_query_tooltip(describe_hovered_samples);
     }
 
     fn properties() -> &'static [ParamSpec] {
@@ -62,9 +434,17 @@ impl ObjectImpl for TimelineRowPrivate {
 }
 
 impl WidgetImpl for TimelineRowPrivate {
-    fn measure(&self, _: &Self::Type, orientation: Orientation, _: i32) -> (i32, i32, i32, i32) {
+    fn measure(&self, this: &Self::Type, orientation: Orientation, _: i32) -> (i32, i32, i32, i32) {
+        let flame_chart = this
+            .parent()
+            .and_then(|parent| parent.downcast::<TimelineView>().ok())
+            .map_or(false, |timeline_view| timeline_view.flame_chart());
         match orientation {
             Orientation::Horizontal => (0, 0, -1, -1),
+            Orientation::Vertical if flame_chart => {
+                let height = (self.max_depth().min(MAX_SPAN_DEPTH) as f32 * SPAN_HEIGHT) as i32;
+                (height, height, -1, -1)
+            }
             Orientation::Vertical => (30, 30, -1, -1),
             _ => unreachable!(),
         }
@@ -74,24 +454,50 @@ impl WidgetImpl for TimelineRowPrivate {
         self.parent_snapshot(this, snapshot);
 
         let timeline_view = this.parent().unwrap().downcast::<TimelineView>().unwrap();
+        if timeline_view.is_thread_selected(this.tid()) {
+            let mut selected_color = this
+                .style_context()
+                .lookup_color("accent_bg_color")
+                .unwrap_or(RGBA::new(0.21, 0.52, 0.89, 1.0));
+            selected_color.set_alpha(0.15);
+            snapshot.append_color(
+                &selected_color,
+                &Rect::new(0.0, 0.0, this.width() as f32, this.height() as f32),
+            );
+        }
 
-        let samples: Ref<Vec<Sample>> = self.samples.get().unwrap().borrow();
-        let samples = samples.iter().filter(|sample| {
-            timeline_view
-                .display_time_range()
-                .contains(sample.timestamp)
-        });
-
-        let mut color = this.style_context().lookup_color("blue_3").unwrap();
-        color.set_alpha(0.7);
+        if timeline_view.flame_chart() {
+            self.snapshot_flame_chart(this, &timeline_view, snapshot);
+        } else {
+            self.snapshot_samples(this, &timeline_view, snapshot);
+        }
 
-        for sample in samples {
-            let x = timeline_view.time_to_widget_point(sample.timestamp);
-            let height = (sample.callchain.len() as f32).min(this.height() as f32);
-            snapshot.append_color(
-                &color,
-                &Rect::new(x as f32 - 0.5, this.height() as f32 - height, 2.0, height),
+        // The inspected samples are outlined, so it's clear which samples the inspector shows
+        if let Some(samples) = timeline_view
+            .inspected_samples()
+            .filter(|samples| samples.tid == this.tid())
+        {
+            let profile: Ref<Rc<Profile>> = self.profile.get().unwrap().borrow();
+            let start_x = timeline_view.time_to_widget_point(samples.start_time(&profile)) as f32;
+            let end_x = timeline_view.time_to_widget_point(samples.end_time(&profile)) as f32;
+            let inspected_color = this
+                .style_context()
+                .lookup_color("accent_color")
+                .unwrap_or(RGBA::new(0.21, 0.52, 0.89, 1.0));
+            snapshot.append_border(
+                &RoundedRect::from_rect(
+                    Rect::new(
+                        start_x - 1.5,
+                        0.0,
+                        end_x - start_x + 4.0,
+                        this.height() as f32,
+                    ),
+                    0.0,
+                ),
+                &[1.0; 4],
+                &[inspected_color; 4],
             );
         }
+        append_row_header(this, snapshot, &self.header.borrow());
     }
 }
@@ -1,19 +1,20 @@
 use crate::timeline_range::TimelineRangePrivatePropertiesExt;
 use crate::timeline_view::{TimelineView, TimelineViewPrivatePropertiesExt};
 use glib::subclass::prelude::{ObjectImpl, ObjectImplExt, ObjectSubclass};
-use glib::{object_subclass, Cast, Object, Properties};
-use gtk::gdk::RGBA;
+use glib::{object_subclass, Cast, DateTime, Object, Properties};
 use gtk::graphene::Rect;
 use gtk::subclass::prelude::{WidgetClassSubclassExt, WidgetImpl, WidgetImplExt};
-use gtk::traits::WidgetExt;
-use gtk::{
-    Accessible, Buildable, ConstraintTarget, Orientation, Overflow, Scrollable, Snapshot, Widget,
-};
+use gtk::traits::{StyleContextExt, WidgetExt};
+use gtk::{Accessible, Buildable, ConstraintTarget, Orientation, Overflow, Snapshot, Widget};
+
+// Minimum pixels between labelled ticks
+const MIN_LABEL_SPACING: f64 = 100.0;
+const MIN_TIME_OF_DAY_LABEL_SPACING: f64 = 160.0;
 
 glib::wrapper! {
     pub struct TimelineTicker(ObjectSubclass<TimelineTickerPrivate>)
     @extends Widget,
-    @implements Scrollable, Accessible, Buildable, ConstraintTarget;
+    @implements Accessible, Buildable, ConstraintTarget;
 }
 
 impl TimelineTicker {
@@ -22,6 +23,64 @@ impl TimelineTicker {
     }
 }
 
+// Format a time in nanoseconds in the largest unit it fits in, precisely enough to tell apart
+// times resolution nanoseconds apart
+pub fn format_time(time: u64, resolution: u64) -> String {
+    let (unit, unit_name) = match time.max(resolution) {
+        0..=999 => (1, "ns"),
+        1_000..=999_999 => (1_000, "µs"),
+        1_000_000..=999_999_999 => (1_000_000, "ms"),
+        _ => (1_000_000_000, "s"),
+    };
+    let decimals = decimals(unit, resolution);
+    format!("{:.*}{unit_name}", decimals, time as f64 / unit as f64)
+}
+
+// Format nanoseconds since the Unix epoch as the local time of day
+pub fn format_time_of_day(time: i128, resolution: u64) -> String {
+    let seconds = time.div_euclid(1_000_000_000) as i64;
+    let nanoseconds = time.rem_euclid(1_000_000_000) as u64;
+    let time_of_day = DateTime::from_unix_local(seconds)
+        .and_then(|date_time| date_time.format("%H:%M:%S"))
+        .map(|time_of_day| time_of_day.to_string())
+        .unwrap_or_default();
+
+    // Truncated rather than rounded, so a fraction never rounds up into the next second
+    match decimals(1_000_000_000, resolution) {
+        0 => time_of_day,
+        decimals => {
+            let fraction = nanoseconds / 10u64.pow(9 - decimals as u32);
+            format!("{time_of_day}.{fraction:0decimals$}")
+        }
+    }
+}
+
+// How many decimal places of unit are needed to show differences of resolution
+fn decimals(unit: u64, resolution: u64) -> usize {
+    let mut decimals = 0;
+    let mut step = unit;
+    while step > resolution.max(1) && decimals < 9 {
+        step /= 10;
+        decimals += 1;
+    }
+    decimals
+}
+
+// The smallest 1, 2, or 5 × 10^n nanosecond interval at least min_interval long,
+// and how many minor ticks it's split into
+fn nice_interval(min_interval: f64) -> (u64, u64) {
+    let mut magnitude: u64 = 1;
+    loop {
+        for (multiple, subdivisions) in [(1, 5), (2, 4), (5, 5)] {
+            let interval = magnitude * multiple;
+            if interval as f64 >= min_interval || magnitude >= 1_000_000_000_000_000 {
+                return (interval, subdivisions);
+            }
+        }
+        magnitude *= 10;
+    }
+}
+
 // ------------------------------------------------------------------------------
 
 #[derive(Properties, Default)]
@@ -61,50 +120,65 @@ impl WidgetImpl for TimelineTickerPrivate {
         self.parent_snapshot(this, snapshot);
 
         let timeline_view = this.parent().unwrap().downcast::<TimelineView>().unwrap();
-        let display_duration = timeline_view.display_time_range().duration();
+        let display_time_range = timeline_view.display_time_range();
+        let width = this.width() as f64;
+        if display_time_range.duration() == 0 || width <= 0.0 {
+            return;
+        }
+
+        // Labelled ticks are at least a label's width apart
+        let min_label_spacing = if timeline_view.time_of_day() {
+            MIN_TIME_OF_DAY_LABEL_SPACING
+        } else {
+            MIN_LABEL_SPACING
+        };
+        let nanoseconds_per_pixel = display_time_range.duration() as f64 / width;
+        let (major_interval, subdivisions) =
+            nice_interval(nanoseconds_per_pixel * min_label_spacing);
+        let minor_interval = (major_interval / subdivisions).max(1);
+
+        let major_color = this.style_context().color();
+        let mut minor_color = major_color;
+        minor_color.set_alpha(major_color.alpha() * 0.5);
 
-        let mut p = 5;
-        while p < (this.width() - 5) {
-            let is_longer_tick = p % 55 == 0;
+        // Ticks fall on round numbers of whatever times are labelled with
+        let label_offset = timeline_view.time_label_offset();
+        let (major_interval, minor_interval) = (major_interval as i128, minor_interval as i128);
+        let first_tick = display_time_range.start() as i128 + label_offset;
+        let mut tick =
+            first_tick + (minor_interval - first_tick.rem_euclid(minor_interval)) % minor_interval;
+        while tick - label_offset <= display_time_range.end() as i128 {
+            let time = (tick - label_offset) as u64;
+            let x = timeline_view.time_to_widget_point(time);
+            let is_major_tick = tick.rem_euclid(major_interval) == 0;
 
             snapshot.append_color(
-                &if is_longer_tick {
-                    RGBA::BLACK
+                if is_major_tick {
+                    &major_color
                 } else {
-                    RGBA::new(0.0, 0.0, 0.0, 0.5)
+                    &minor_color
                 },
                 &Rect::new(
-                    p as f32,
+                    x.round() as f32,
                     20.0,
                     1.0,
-                    if is_longer_tick { 20.0 } else { 10.0 },
+                    if is_major_tick { 20.0 } else { 10.0 },
                 ),
             );
 
-            if is_longer_tick {
-                let timestamp = timeline_view.widget_to_time_point(p as f64)
-                    - timeline_view.profile_time_range().start();
-                let timestamp = match display_duration {
-                    0..=999 => format!("{timestamp}ms"),
-                    1000..=59999 => format!("{:.0}s", (timestamp as f64 / 1000.0).round()),
-                    60000..=3599999 => format!("{:.0}m", (timestamp as f64 / 60000.0).round()),
-                    _ => format!("{:.0}h", (timestamp as f64 / 3600000.0).round()),
-                };
-                let timestamp = this.create_pango_layout(Some(&timestamp));
+            if is_major_tick {
+                let label = timeline_view.format_time_label(time, major_interval as u64);
+                let label = this.create_pango_layout(Some(&label));
+                let label_width = label.pixel_size().0 as f64;
                 snapshot.render_layout(
                     &this.style_context(),
-                    p as f64 - (timestamp.pixel_size().0 as f64 / 2.0)
-                        + if timestamp.pixel_size().0 % 2 == 0 {
-                            0.0
-                        } else {
-                            1.0
-                        },
+                    (x - label_width / 2.0).round(),
                     2.0,
-                    &timestamp,
+                    &label,
                 );
             }
 
-            p += 10;
+            tick += minor_interval;
         }
     }
 }
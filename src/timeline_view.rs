@@ -1,23 +1,49 @@
-use crate::perf_data_parser::Profile;
+use crate::analysis::{
+    apply_stack_transforms, filter_threads, sort_threads, thread_summaries, CpuSamples,
+    SampleGroup, SamplePyramid, SearchMatches, StackTransform, ThreadOrder, ThreadSummary,
+};
+use crate::cpu_lane::CpuLane;
+use crate::cpu_usage_graph::CpuUsageGraph;
+use crate::process_row::ProcessRow;
+use crate::timeline_minimap::TimelineMinimap;
 use crate::timeline_range::{TimelineRange, TimelineRangePrivatePropertiesExt};
 use crate::timeline_row::TimelineRow;
-use crate::timeline_ticker::TimelineTicker;
+use crate::timeline_ticker::{format_time, format_time_of_day, TimelineTicker};
+use crate::wtf_profile::{merge_markers, Marker, Profile, UNKNOWN_PID};
 use adw::StyleManager;
-use glib::once_cell::sync::OnceCell;
+use glib::once_cell::sync::{Lazy, OnceCell};
 use glib::subclass::prelude::{
-    DerivedObjectProperties, ObjectImpl, ObjectImplExt, ObjectSubclass, ObjectSubclassIsExt,
+    DerivedObjectProperties, ObjectImpl, ObjectImplExt, ObjectSubclass, ObjectSubclassExt,
+    ObjectSubclassIsExt,
+};
+use glib::{
+    clone, object_subclass, BoxedAnyObject, Cast, IsA, ObjectExt, ParamSpec, ParamSpecOverride,
+    Properties, SignalHandlerId, StaticType, ToValue, Value,
 };
-use glib::{object_subclass, Cast, Object, ObjectExt, ParamSpec, Properties, StaticType, Value};
-use gtk::gdk::RGBA;
+use gtk::gdk::{Key, ModifierType, RGBA};
 use gtk::graphene::Rect;
-use gtk::subclass::prelude::{WidgetClassSubclassExt, WidgetImpl, WidgetImplExt};
-use gtk::traits::{EventControllerExt, GestureDragExt, OrientableExt, WidgetExt};
+use gtk::gsk::RoundedRect;
+use gtk::subclass::prelude::{ScrollableImpl, WidgetClassSubclassExt, WidgetImpl, WidgetImplExt};
+use gtk::traits::{
+    AdjustmentExt, ButtonExt, EventControllerExt, GestureDragExt, StyleContextExt, WidgetExt,
+};
 use gtk::{
-    Accessible, BoxLayout, Buildable, ConstraintTarget, GestureDrag, Orientation, Overflow,
-    Scrollable, Snapshot, Widget,
+    Accessible, Adjustment, Allocation, Buildable, Button, ConstraintTarget, EventControllerKey,
+    EventControllerMotion, EventControllerScroll, EventControllerScrollFlags, GestureClick,
+    GestureDrag, Inhibit, Orientation, Overflow, PickFlags, Scrollable, ScrollablePolicy, Snapshot,
+    Widget,
 };
-use itertools::Itertools;
-use std::cell::RefCell;
+use std::cell::{Cell, RefCell};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::rc::Rc;
+
+const ROW_SPACING: i32 = 6;
+// Zooming in stops at a microsecond across the whole timeline
+const MIN_DISPLAY_DURATION: u64 = 1000;
+// How much one step of Ctrl+scroll zooms by
+const ZOOM_STEP: f64 = 1.25;
+// How close to a selection edge, in pixels, a drag moves that edge instead of making a new selection
+const HANDLE_GRAB_DISTANCE: f64 = 6.0;
 
 glib::wrapper! {
     pub struct TimelineView(ObjectSubclass<TimelineViewPrivate>)
@@ -26,55 +52,376 @@ glib::wrapper! {
 }
 
 impl TimelineView {
-    pub fn new(profile: Profile) -> Self {
-        let start = profile
-            .samples
-            .iter()
-            .map(|sample| sample.timestamp)
-            .min()
-            .unwrap();
-        let end = profile
-            .samples
-            .iter()
-            .map(|sample| sample.timestamp)
-            .max()
-            .unwrap();
-        let profile_time_range = TimelineRange::new(start, end);
-
-        let this: Self = Object::new(&[
-            ("profile-time-range", &profile_time_range),
-            ("display-time-range", &profile_time_range),
-        ])
-        .unwrap();
-
-        for (_, samples) in &profile
-            .samples
-            .into_iter()
-            .into_group_map_by(|sample| sample.tid)
+    // The profile with every stack transform applied, which all views display
+    pub fn profile_data(&self) -> Option<Rc<Profile>> {
+        let imp = self.imp();
+        if let Some(transformed_profile) = &*imp.transformed_profile.borrow() {
+            return Some(transformed_profile.clone());
+        }
+        let profile = imp.profile.borrow();
+        profile
+            .as_ref()
+            .map(|profile| profile.borrow::<Rc<Profile>>().clone())
+    }
+
+    pub fn transforms(&self) -> Vec<StackTransform> {
+        self.imp().transforms.borrow().clone()
+    }
+
+    pub fn push_transform(&self, transform: StackTransform) {
+        self.imp().transforms.borrow_mut().push(transform);
+        self.apply_transforms();
+    }
+
+    // Undo every transform after the first count
+    pub fn truncate_transforms(&self, count: usize) {
+        self.imp().transforms.borrow_mut().truncate(count);
+        self.apply_transforms();
+    }
+
+    fn apply_transforms(&self) {
+        let imp = self.imp();
+        let transformed_profile = {
+            let profile = imp.profile.borrow();
+            let transforms = imp.transforms.borrow();
+            match &*profile {
+                Some(profile) if !transforms.is_empty() => {
+                    let profile = profile.borrow::<Rc<Profile>>();
+                    Some(Rc::new(apply_stack_transforms(&profile, &transforms)))
+                }
+                _ => None,
+            }
+        };
+        imp.transformed_profile.replace(transformed_profile);
+        imp.cpu_samples.replace(None);
+        imp.sample_pyramids.borrow_mut().clear();
+        imp.thread_summaries.replace(None);
+        imp.filtered_profile.replace(None);
+        self.inspect_samples(None);
+
+        // Stacks are renumbered by transforms, so matches need to be found again
+        let _ = self.update_search_matches();
+        imp.replace_rows(self.profile_data());
+        imp.transform_count
+            .set(imp.transforms.borrow().len() as u32);
+        self.notify("transform-count");
+    }
+
+    // The profile analysis views aggregate: the displayed profile, limited to the selected threads if any
+    pub fn analysis_profile(&self) -> Option<Rc<Profile>> {
+        let imp = self.imp();
+        if imp.selected_threads.borrow().is_empty() {
+            return self.profile_data();
+        }
+        if imp.filtered_profile.borrow().is_none() {
+            let profile = self.profile_data()?;
+            let filtered_profile = filter_threads(&profile, &imp.selected_threads.borrow());
+            imp.filtered_profile
+                .replace(Some(Rc::new(filtered_profile)));
+        }
+        imp.filtered_profile.borrow().clone()
+    }
+
+    pub fn is_thread_selected(&self, tid: u32) -> bool {
+        self.imp().selected_threads.borrow().contains(&tid)
+    }
+
+    pub fn toggle_thread_selected(&self, tid: u32) {
+        let imp = self.imp();
         {
-            let timeline_row = TimelineRow::new(samples.clone());
-            timeline_row.set_parent(&this);
+            let mut selected_threads = imp.selected_threads.borrow_mut();
+            if !selected_threads.remove(&tid) {
+                selected_threads.insert(tid);
+            }
+        }
+        self.selected_threads_changed();
+    }
+
+    pub fn clear_thread_selection(&self) {
+        self.imp().selected_threads.borrow_mut().clear();
+        self.selected_threads_changed();
+    }
+
+    fn selected_threads_changed(&self) {
+        let imp = self.imp();
+        imp.filtered_profile.replace(None);
+        imp.selected_thread_count
+            .set(imp.selected_threads.borrow().len() as u32);
+        imp.for_each_row(|row| row.queue_draw());
+        self.notify("selected-thread-count");
+    }
+
+    pub fn is_thread_pinned(&self, tid: u32) -> bool {
+        self.imp().pinned_threads.borrow().contains(&tid)
+    }
+
+    // Pinned threads are listed first, in the order they were pinned
+    pub fn toggle_thread_pinned(&self, tid: u32) {
+        let imp = self.imp();
+        {
+            let mut pinned_threads = imp.pinned_threads.borrow_mut();
+            match pinned_threads.iter().position(|&pinned| pinned == tid) {
+                Some(index) => {
+                    pinned_threads.remove(index);
+                }
+                None => pinned_threads.push(tid),
+            }
+        }
+        imp.replace_rows(self.profile_data());
+    }
+
+    pub fn hide_thread(&self, tid: u32) {
+        let imp = self.imp();
+        imp.hidden_threads.borrow_mut().insert(tid);
+        imp.replace_rows(self.profile_data());
+    }
+
+    pub fn hidden_thread_count(&self) -> usize {
+        self.imp().hidden_threads.borrow().len()
+    }
+
+    pub fn show_hidden_threads(&self) {
+        let imp = self.imp();
+        imp.hidden_threads.borrow_mut().clear();
+        imp.replace_rows(self.profile_data());
+    }
+
+    pub fn toggle_process_collapsed(&self, pid: u32) {
+        let imp = self.imp();
+        {
+            let mut collapsed_processes = imp.collapsed_processes.borrow_mut();
+            if !collapsed_processes.remove(&pid) {
+                collapsed_processes.insert(pid);
+            }
+        }
+        imp.replace_rows(self.profile_data());
+    }
+
+    // Samples of the displayed profile shown in the inspector, if any
+    pub fn inspected_samples(&self) -> Option<SampleGroup> {
+        self.imp().inspected_samples.borrow().clone()
+    }
+
+    pub fn inspect_samples(&self, samples: Option<SampleGroup>) {
+        let imp = self.imp();
+        let sample_count = samples.as_ref().map_or(0, SampleGroup::sample_count);
+        imp.inspected_sample_count.set(sample_count as u32);
+        imp.inspected_samples.replace(samples);
+        imp.for_each_row(|row| row.queue_draw());
+        self.notify("inspected-sample-count");
+    }
+
+    // Markers are kept by start time, and notified whenever they change
+    pub fn markers(&self) -> Vec<Marker> {
+        self.imp().markers.borrow().clone()
+    }
+
+    pub fn add_markers<I: IntoIterator<Item = Marker>>(&self, markers: I) {
+        merge_markers(&mut self.imp().markers.borrow_mut(), markers);
+        self.imp().markers_changed();
+    }
+
+    // A marker spanning the selection, or None if nothing is selected
+    pub fn add_marker_from_selection(&self, name: &str) -> Option<Marker> {
+        let selected_time_range = self.selected_time_range()?;
+        let marker = Marker {
+            start: selected_time_range.start(),
+            duration: selected_time_range.duration(),
+            name: name.to_owned(),
+        };
+        self.add_markers([marker.clone()]);
+        Some(marker)
+    }
+
+    pub fn remove_marker(&self, index: usize) {
+        self.imp().markers.borrow_mut().remove(index);
+        self.imp().markers_changed();
+    }
+
+    // Select a marker's span and zoom to it with some room either side, or center on a marker
+    // at a single point in time without zooming
+    pub fn show_marker(&self, index: usize) {
+        let marker = match self.imp().markers.borrow().get(index) {
+            Some(marker) => marker.clone(),
+            None => return,
+        };
+        if marker.duration == 0 {
+            let duration = self.display_time_range().duration();
+            self.show_time_range(marker.start.saturating_sub(duration / 2), duration);
+            return;
+        }
+        self.select_between(marker.start, marker.end());
+        let margin = marker.duration / 10;
+        self.show_time_range(
+            marker.start.saturating_sub(margin),
+            marker.duration + 2 * margin,
+        );
+    }
+
+    // Every thread of the displayed profile, summarized on first use
+    fn thread_summaries(&self) -> Option<Rc<Vec<ThreadSummary>>> {
+        let imp = self.imp();
+        if imp.thread_summaries.borrow().is_none() {
+            let summaries = Rc::new(thread_summaries(&self.profile_data()?));
+            imp.thread_summaries.replace(Some(summaries));
+        }
+        imp.thread_summaries.borrow().clone()
+    }
+
+    // Summaries of a thread's samples in the displayed profile for drawing, built on first use
+    pub fn sample_pyramid(&self, tid: u32) -> Option<Rc<SamplePyramid>> {
+        let imp = self.imp();
+        if let Some(sample_pyramid) = imp.sample_pyramids.borrow().get(&tid) {
+            return Some(sample_pyramid.clone());
         }
+        let sample_pyramid = Rc::new(SamplePyramid::new(&self.profile_data()?, tid));
+        imp.sample_pyramids
+            .borrow_mut()
+            .insert(tid, sample_pyramid.clone());
+        Some(sample_pyramid)
+    }
+
+    // Samples of the displayed profile by CPU, grouped on first use
+    pub fn cpu_samples(&self) -> Option<Rc<CpuSamples>> {
+        let imp = self.imp();
+        if imp.cpu_samples.borrow().is_none() {
+            let cpu_samples = Rc::new(CpuSamples::new(&self.profile_data()?));
+            imp.cpu_samples.replace(Some(cpu_samples));
+        }
+        imp.cpu_samples.borrow().clone()
+    }
+
+    pub fn search_matches(&self) -> Option<Rc<SearchMatches>> {
+        self.imp().search_matches.borrow().clone()
+    }
+
+    // Search every view for functions, files, and modules matching query, or stop searching if it's empty
+    pub fn set_search(&self, query: &str, use_regex: bool) -> Result<(), String> {
+        let imp = self.imp();
+        *imp.search_query.borrow_mut() = query.to_owned();
+        imp.search_regex.set(use_regex);
+        let result = self.update_search_matches();
+
+        imp.for_each_row(|row| row.queue_draw());
+        self.notify("search-query");
+        result
+    }
 
-        this
+    fn update_search_matches(&self) -> Result<(), String> {
+        let imp = self.imp();
+        let query = imp.search_query.borrow();
+        let search_matches = match self.profile_data() {
+            Some(profile) if !query.is_empty() => {
+                match SearchMatches::new(&profile, &query, imp.search_regex.get()) {
+                    Ok(search_matches) => Some(Rc::new(search_matches)),
+                    Err(e) => {
+                        imp.search_matches.replace(None);
+                        return Err(e.to_string());
+                    }
+                }
+            }
+            _ => None,
+        };
+        imp.search_matches.replace(search_matches);
+        Ok(())
+    }
+
+    // The range analysis views aggregate over: the selection, or else the whole profile
+    pub fn analysis_time_range(&self) -> Option<(u64, u64)> {
+        match self.selected_time_range() {
+            Some(selected_time_range) => {
+                Some((selected_time_range.start(), selected_time_range.end()))
+            }
+            None => self.profile_data()?.time_range(),
+        }
+    }
+
+    // Wall clock time minus perf's clock time, if the profile recorded both
+    pub fn wall_clock_offset(&self) -> Option<i128> {
+        let profile = self.profile_data()?;
+        let wall_time = profile.metadata("clock-wall-time")?.parse::<i128>().ok()?;
+        let perf_time = profile.metadata("clock-perf-time")?.parse::<i128>().ok()?;
+        Some(wall_time - perf_time)
+    }
+
+    // Added to a time to get what it's labelled with: the wall clock time when showing the
+    // time of day, or else the time since the profile started
+    pub fn time_label_offset(&self) -> i128 {
+        match self.wall_clock_offset().filter(|_| self.time_of_day()) {
+            Some(wall_clock_offset) => wall_clock_offset,
+            None => -(self.profile_time_range().start() as i128),
+        }
+    }
+
+    pub fn format_time_label(&self, time: u64, resolution: u64) -> String {
+        let label_time = time as i128 + self.time_label_offset();
+        if self.time_of_day() && self.wall_clock_offset().is_some() {
+            format_time_of_day(label_time, resolution)
+        } else {
+            format_time(label_time.max(0) as u64, resolution)
+        }
+    }
+
+    // Zoom by factor (> 1 zooms out), keeping the time under widget_point where it is
+    pub fn zoom_around(&self, widget_point: f64, factor: f64) {
+        let anchor = self.widget_to_time_point(widget_point);
+        let duration = (self.display_time_range().duration() as f64 * factor).round() as u64;
+        let anchor_offset = widget_point / self.width().max(1) as f64 * duration as f64;
+        self.show_time_range(anchor.saturating_sub(anchor_offset as u64), duration);
+    }
+
+    pub fn zoom_to_selection(&self) {
+        if let Some(selected_time_range) = self.selected_time_range() {
+            self.show_time_range(selected_time_range.start(), selected_time_range.duration());
+        }
+    }
+
+    pub fn reset_zoom(&self) {
+        self.set_display_time_range(self.profile_time_range());
+    }
+
+    // Display duration from start, moved and resized to stay within the profile
+    pub fn show_time_range(&self, start: u64, duration: u64) {
+        let profile_time_range = self.profile_time_range();
+        // A profile with a single sample has no duration, but the display range needs one
+        let profile_duration = profile_time_range.duration().max(1);
+        let duration = duration.clamp(MIN_DISPLAY_DURATION.min(profile_duration), profile_duration);
+        let start = start
+            .min(profile_time_range.end().saturating_sub(duration))
+            .max(profile_time_range.start());
+        self.set_display_time_range(TimelineRange::new(start, start + duration));
+    }
+
+    // Select between two times, in either order, with the edge at moved_time the one arrow keys nudge
+    fn select_between(&self, anchor_time: u64, moved_time: u64) {
+        self.imp().active_edge.set(if moved_time < anchor_time {
+            SelectionEdge::Start
+        } else {
+            SelectionEdge::End
+        });
+        self.set_selected_time_range(Some(TimelineRange::new(
+            anchor_time.min(moved_time),
+            anchor_time.max(moved_time),
+        )));
+        self.imp().update_zoom_button();
+        self.queue_draw();
     }
 
     pub fn time_to_widget_point(&self, time_point: u64) -> f64 {
         let timeline_width = self.width() as f64;
         let display_time_range = self.imp().display_time_range.borrow();
         let display_start = display_time_range.start() as f64;
-        let display_end = display_time_range.end() as f64;
-        (((time_point as f64 - display_start) * timeline_width) / (display_end - display_start))
+        let display_duration = display_time_range.duration().max(1) as f64;
+        (((time_point as f64 - display_start) * timeline_width) / display_duration)
             .clamp(0.0, timeline_width)
     }
 
     pub fn widget_to_time_point(&self, widget_point: f64) -> u64 {
-        let timeline_width = self.width() as f64;
+        let timeline_width = self.width().max(1) as f64;
         let display_time_range = self.imp().display_time_range.borrow();
         let display_start = display_time_range.start() as f64;
-        let display_end = display_time_range.end() as f64;
-        ((((widget_point * (display_end - display_start)) / timeline_width) + display_start).round()
-            as u64)
+        let display_duration = display_time_range.duration().max(1) as f64;
+        ((((widget_point * display_duration) / timeline_width) + display_start).round() as u64)
             .clamp(display_time_range.start(), display_time_range.end())
     }
 }
@@ -83,45 +430,711 @@ impl TimelineView {
 
 #[derive(Properties, Default)]
 pub struct TimelineViewPrivate {
-    #[property(get, set, construct_only, builder(TimelineRange::static_type()))]
-    profile_time_range: OnceCell<TimelineRange>,
+    #[property(get, set = Self::set_profile, builder(BoxedAnyObject::static_type()))]
+    profile: RefCell<Option<BoxedAnyObject>>,
+    #[property(get, set, builder(TimelineRange::static_type()))]
+    profile_time_range: RefCell<TimelineRange>,
     #[property(get, set = Self::set_display_time_range, builder(TimelineRange::static_type()))]
     display_time_range: RefCell<TimelineRange>,
     #[property(get, set, builder(TimelineRange::static_type()))]
     selected_time_range: RefCell<Option<TimelineRange>>,
+
+    // Number of stack transforms applied to the profile, notified whenever they change
+    #[property(get)]
+    transform_count: Cell<u32>,
+    transforms: RefCell<Vec<StackTransform>>,
+    transformed_profile: RefCell<Option<Rc<Profile>>>,
+
+    // The current search, notified whenever its matches change
+    #[property(get)]
+    search_query: RefCell<String>,
+    search_regex: Cell<bool>,
+    search_matches: RefCell<Option<Rc<SearchMatches>>>,
+
+    // Whether rows draw each thread's stacks over time, instead of one bar per sample
+    #[property(get, set = Self::set_flame_chart)]
+    flame_chart: Cell<bool>,
+    // Whether rows show what ran on each CPU, instead of each thread's samples
+    #[property(get, set = Self::set_cpu_lanes)]
+    cpu_lanes: Cell<bool>,
+    cpu_samples: RefCell<Option<Rc<CpuSamples>>>,
+    // By tid, kept across rows being replaced
+    sample_pyramids: RefCell<HashMap<u32, Rc<SamplePyramid>>>,
+    // Threads the analysis views are limited to, or every thread if none are selected
+    #[property(get)]
+    selected_thread_count: Cell<u32>,
+    selected_threads: RefCell<BTreeSet<u32>>,
+    filtered_profile: RefCell<Option<Rc<Profile>>>,
+    // How thread rows are arranged: an index into ThreadOrder::ALL, and whether threads are listed
+    // under their process
+    #[property(get, set = Self::set_thread_order)]
+    thread_order: Cell<u32>,
+    #[property(get, set = Self::set_group_by_process)]
+    group_by_process: Cell<bool>,
+    pinned_threads: RefCell<Vec<u32>>,
+    hidden_threads: RefCell<HashSet<u32>>,
+    collapsed_processes: RefCell<HashSet<u32>>,
+    thread_summaries: RefCell<Option<Rc<Vec<ThreadSummary>>>>,
+    // Samples clicked on to be inspected, cleared whenever stacks are renumbered
+    #[property(get)]
+    inspected_sample_count: Cell<u32>,
+    inspected_samples: RefCell<Option<SampleGroup>>,
+    // Named points and spans of time, loaded with the profile or placed since
+    #[property(get)]
+    marker_count: Cell<u32>,
+    markers: RefCell<Vec<Marker>>,
+    // Whether times are labelled with the time of day, for profiles that recorded the wall clock
+    #[property(get, set = Self::set_time_of_day)]
+    time_of_day: Cell<bool>,
+
+    // Scrollable: left/right moves display_time_range within profile_time_range, up/down moves the rows
+    hadjustment: RefCell<Option<(Adjustment, SignalHandlerId)>>,
+    vadjustment: RefCell<Option<(Adjustment, SignalHandlerId)>>,
+    hscroll_policy: Cell<Option<ScrollablePolicy>>,
+    vscroll_policy: Cell<Option<ScrollablePolicy>>,
+    // Set while display_time_range is copied to hadjustment, so it isn't copied back
+    updating_hadjustment: Cell<bool>,
+
+    ticker: OnceCell<TimelineTicker>,
+    minimap: OnceCell<TimelineMinimap>,
+    // Where the rows start, below the ticker and minimap, as of the last allocation
+    rows_top: Cell<i32>,
+    zoom_button: OnceCell<Button>,
+    // On-screen bounds of the selection the zoom button is shown over, None while it's hidden
+    zoom_button_bounds: Cell<Option<(f64, f64)>>,
+    pointer_x: Cell<Option<f64>>,
+    selecting: Cell<bool>,
+    // While dragging a selection edge, the time of the other edge
+    drag_anchor: Cell<Option<u64>>,
+    active_edge: Cell<SelectionEdge>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Default)]
+enum SelectionEdge {
+    Start,
+    #[default]
+    End,
 }
 
 impl TimelineViewPrivate {
+    pub fn set_profile(&self, value: Option<BoxedAnyObject>) {
+        let this = self.instance();
+
+        // Transforms only apply to the profile they were made for
+        self.transforms.borrow_mut().clear();
+        self.transformed_profile.replace(None);
+        self.cpu_samples.replace(None);
+        self.sample_pyramids.borrow_mut().clear();
+        self.transform_count.set(0);
+
+        // As do the threads picked out of it
+        self.thread_summaries.replace(None);
+        self.filtered_profile.replace(None);
+        self.selected_threads.borrow_mut().clear();
+        self.selected_thread_count.set(0);
+        self.pinned_threads.borrow_mut().clear();
+        self.hidden_threads.borrow_mut().clear();
+        self.collapsed_processes.borrow_mut().clear();
+        self.inspected_sample_count.set(0);
+        self.inspected_samples.replace(None);
+
+        let profile = value
+            .as_ref()
+            .map(|profile| profile.borrow::<Rc<Profile>>().clone());
+        self.markers.replace(
+            profile
+                .as_ref()
+                .map(|profile| profile.markers.clone())
+                .unwrap_or_default(),
+        );
+        self.marker_count.set(self.markers.borrow().len() as u32);
+        if let Some(profile) = &profile {
+            let (start, end) = profile.time_range().unwrap_or_default();
+            let profile_time_range = TimelineRange::new(start, end);
+            this.set_profile_time_range(profile_time_range.clone());
+            this.set_display_time_range(profile_time_range);
+        }
+        // Selections are times in the old profile
+        this.set_selected_time_range(None);
+        *self.profile.borrow_mut() = value;
+
+        let _ = this.update_search_matches();
+        self.replace_rows(profile);
+    }
+
+    fn replace_rows(&self, profile: Option<Rc<Profile>>) {
+        let this = self.instance();
+
+        // Remove the previous profile's rows, keeping the ticker
+        let mut child = this.first_child();
+        while let Some(widget) = child {
+            child = widget.next_sibling();
+            if is_row(&widget) {
+                widget.unparent();
+            }
+        }
+
+        if let Some(profile) = profile {
+            // Without CPUs there's no usage to graph or lanes to show, only threads
+            let knows_cpus = profile.knows_cpus();
+            if knows_cpus {
+                CpuUsageGraph::new().set_parent(&this);
+            }
+            if self.cpu_lanes.get() && knows_cpus {
+                let cpu_samples = this.cpu_samples().unwrap();
+                for cpu_index in 0..cpu_samples.cpus.len() {
+                    let cpu_lane =
+                        CpuLane::new(profile.clone(), cpu_samples.clone(), cpu_index as u32);
+                    cpu_lane.set_parent(&this);
+                }
+            } else {
+                self.add_thread_rows(&profile);
+            }
+        }
+        this.queue_draw();
+    }
+
+    // Pinned threads first, then every other thread that isn't hidden, sorted and optionally
+    // grouped under a header for their process
+    fn add_thread_rows(&self, profile: &Rc<Profile>) {
+        let this = self.instance();
+        let mut threads = this.thread_summaries().unwrap().to_vec();
+        let order = ThreadOrder::ALL[self.thread_order.get() as usize % ThreadOrder::ALL.len()];
+        sort_threads(&mut threads, order, &profile.strings);
+
+        let total_weight = threads
+            .iter()
+            .map(|thread| thread.weight)
+            .sum::<u64>()
+            .max(1);
+        let share = |weight: u64| weight as f64 * 100.0 / total_weight as f64;
+        let thread_name = |thread: &ThreadSummary| match profile.strings.get(thread.name) {
+            "" => "Unnamed Thread",
+            name => name,
+        };
+
+        let pinned_threads = self.pinned_threads.borrow();
+        let hidden_threads = self.hidden_threads.borrow();
+        let add_row = |thread: &ThreadSummary| {
+            let mut header = format!(
+                "{} · {} · {:.1}%",
+                thread_name(thread),
+                thread.tid,
+                share(thread.weight)
+            );
+            if pinned_threads.contains(&thread.tid) {
+                header.push_str(" · Pinned");
+            }
+            TimelineRow::new(profile.clone(), thread.tid, &header).set_parent(&this);
+        };
+
+        for tid in pinned_threads.iter() {
+            if let Some(thread) = threads.iter().find(|thread| thread.tid == *tid) {
+                if !hidden_threads.contains(tid) {
+                    add_row(thread);
+                }
+            }
+        }
+        let unpinned_threads = threads.iter().filter(|thread| {
+            !pinned_threads.contains(&thread.tid) && !hidden_threads.contains(&thread.tid)
+        });
+
+        if !self.group_by_process.get() {
+            unpinned_threads.for_each(add_row);
+            return;
+        }
+
+        // Processes are listed in the order of their first thread, threads from unknown
+        // processes aren't grouped and keep their own place
+        let mut processes: Vec<(u32, Vec<&ThreadSummary>)> = Vec::new();
+        for thread in unpinned_threads {
+            let process =
+                (processes.iter_mut()).find(|(pid, _)| *pid == thread.pid && *pid != UNKNOWN_PID);
+            match process {
+                Some((_, process_threads)) => process_threads.push(thread),
+                None => processes.push((thread.pid, vec![thread])),
+            }
+        }
+        for (pid, process_threads) in processes {
+            if pid == UNKNOWN_PID {
+                process_threads.into_iter().for_each(add_row);
+                continue;
+            }
+            // Named after its main thread, whose tid is the pid
+            let main_thread = threads
+                .iter()
+                .find(|thread| thread.tid == pid)
+                .unwrap_or(process_threads[0]);
+            let weight = process_threads.iter().map(|thread| thread.weight).sum();
+            let header = format!(
+                "{} · {pid} · {} threads · {:.1}%",
+                thread_name(main_thread),
+                process_threads.len(),
+                share(weight)
+            );
+            let collapsed = self.collapsed_processes.borrow().contains(&pid);
+            ProcessRow::new(pid, &header, collapsed).set_parent(&this);
+            if !collapsed {
+                process_threads.into_iter().for_each(add_row);
+            }
+        }
+    }
+
     pub fn set_display_time_range(&self, value: TimelineRange) {
         *self.display_time_range.borrow_mut() = value;
-        *self.selected_time_range.borrow_mut() = None;
+        self.update_hadjustment();
+
+        self.for_each_row(|row| row.queue_draw());
+        if let Some(ticker) = self.ticker.get() {
+            ticker.queue_draw();
+        }
+        if let Some(minimap) = self.minimap.get() {
+            minimap.queue_draw();
+        }
+        self.update_zoom_button();
+        self.instance().queue_draw();
+    }
+
+    pub fn set_flame_chart(&self, value: bool) {
+        self.flame_chart.set(value);
+
+        // Rows are as tall as their deepest stack in flame chart mode
+        self.for_each_row(|row| row.queue_resize());
+        self.instance().queue_draw();
+    }
+
+    pub fn set_cpu_lanes(&self, value: bool) {
+        if self.cpu_lanes.replace(value) != value {
+            self.replace_rows(self.instance().profile_data());
+        }
+    }
+
+    pub fn set_thread_order(&self, value: u32) {
+        if self.thread_order.replace(value) != value {
+            self.replace_rows(self.instance().profile_data());
+        }
+    }
+
+    pub fn set_group_by_process(&self, value: bool) {
+        if self.group_by_process.replace(value) != value {
+            self.replace_rows(self.instance().profile_data());
+        }
+    }
+
+    pub fn set_time_of_day(&self, value: bool) {
+        self.time_of_day.set(value);
+
+        if let Some(ticker) = self.ticker.get() {
+            ticker.queue_draw();
+        }
+        self.instance().queue_draw();
+    }
+
+    fn markers_changed(&self) {
+        let this = self.instance();
+        self.marker_count.set(self.markers.borrow().len() as u32);
+        this.notify("marker-count");
+        this.queue_draw();
+    }
+
+    fn for_each_row<F: Fn(&Widget)>(&self, f: F) {
+        let mut child = self.instance().first_child();
+        while let Some(widget) = child {
+            child = widget.next_sibling();
+            if is_row(&widget) {
+                f(&widget);
+            }
+        }
+    }
+
+    // The ticker and minimap stay at the top, with the rows scrolling beneath them
+    fn rows_top(&self) -> i32 {
+        self.rows_top.get()
+    }
+
+    // Total height of the rows, laid out one after another
+    fn rows_height(&self, width: i32) -> i32 {
+        let mut height = 0;
+        let mut row_count = 0;
+        self.for_each_row(|row| {
+            height += row.measure(Orientation::Vertical, width).1;
+            row_count += 1;
+        });
+        height + ROW_SPACING * (row_count - 1).max(0)
+    }
+
+    fn set_hadjustment(&self, value: Option<Adjustment>) {
+        let this = self.instance();
+        if let Some((hadjustment, handler)) = self.hadjustment.take() {
+            hadjustment.disconnect(handler);
+        }
+
+        let hadjustment = value.map(|hadjustment| {
+            let handler =
+                hadjustment.connect_value_changed(clone!(@weak this => move |hadjustment| {
+                    if !this.imp().updating_hadjustment.get() {
+                        let start = this.profile_time_range().start()
+                            + hadjustment.value().round() as u64;
+                        this.show_time_range(start, this.display_time_range().duration());
+                    }
+                }));
+            (hadjustment, handler)
+        });
+        self.hadjustment.replace(hadjustment);
+        self.update_hadjustment();
+    }
+
+    fn update_hadjustment(&self) {
+        let hadjustment = self.hadjustment.borrow();
+        if let Some((hadjustment, _)) = &*hadjustment {
+            let profile_time_range = self.profile_time_range.borrow();
+            let display_time_range = self.display_time_range.borrow();
+            let page_size = display_time_range.duration() as f64;
+
+            self.updating_hadjustment.set(true);
+            hadjustment.configure(
+                display_time_range
+                    .start()
+                    .saturating_sub(profile_time_range.start()) as f64,
+                0.0,
+                profile_time_range.duration() as f64,
+                page_size / 10.0,
+                page_size * 0.9,
+                page_size,
+            );
+            self.updating_hadjustment.set(false);
+        }
+    }
+
+    fn set_vadjustment(&self, value: Option<Adjustment>) {
+        let this = self.instance();
+        if let Some((vadjustment, handler)) = self.vadjustment.take() {
+            vadjustment.disconnect(handler);
+        }
+
+        let vadjustment = value.map(|vadjustment| {
+            let handler = vadjustment
+                .connect_value_changed(clone!(@weak this => move |_| this.queue_allocate()));
+            (vadjustment, handler)
+        });
+        self.vadjustment.replace(vadjustment);
+        this.queue_allocate();
+    }
+
+    // Grab handles on both selection edges, with the start and end times beside them and the
+    // duration along the bottom
+    fn snapshot_selection_handles(
+        &self,
+        this: &TimelineView,
+        snapshot: &Snapshot,
+        selected_time_range: &TimelineRange,
+    ) {
+        let handle_color = this
+            .style_context()
+            .lookup_color("accent_color")
+            .unwrap_or(RGBA::new(0.21, 0.52, 0.89, 1.0));
+        let label_background = this
+            .style_context()
+            .lookup_color("view_bg_color")
+            .unwrap_or(RGBA::WHITE);
+        let height = this.height() as f32;
+        let rows_top = self.rows_top() as f32;
+        let grip_top = rows_top + ((height - rows_top) / 2.0 - 12.0).max(0.0);
+
+        // Precise to a pixel
+        let resolution = (this.display_time_range().duration() / this.width().max(1) as u64).max(1);
+        let draw_label = |text: &str, x: f32, y: f32, align: f32| {
+            let layout = this.create_pango_layout(Some(text));
+            let (width, label_height) = layout.pixel_size();
+            let x = (x - width as f32 * align).clamp(0.0, (this.width() - width).max(0) as f32);
+            snapshot.append_color(
+                &label_background,
+                &Rect::new(x - 3.0, y, width as f32 + 6.0, label_height as f32),
+            );
+            snapshot.render_layout(&this.style_context(), x as f64, y as f64, &layout);
+        };
+
+        let selection_start = this.time_to_widget_point(selected_time_range.start()) as f32;
+        let selection_end = this.time_to_widget_point(selected_time_range.end()) as f32;
+        for (x, time, align) in [
+            (selection_start, selected_time_range.start(), 1.0),
+            (selection_end, selected_time_range.end(), 0.0),
+        ] {
+            snapshot.append_color(&handle_color, &Rect::new(x - 1.0, 0.0, 2.0, height));
+            let grip = RoundedRect::from_rect(Rect::new(x - 3.0, grip_top, 6.0, 24.0), 3.0);
+            snapshot.push_rounded_clip(&grip);
+            snapshot.append_color(&handle_color, grip.bounds());
+            snapshot.pop();
+
+            // Start times go left of the start edge and end times right of the end edge
+            let time = this.format_time_label(time, resolution);
+            let offset = if align == 1.0 { -6.0 } else { 6.0 };
+            draw_label(&time, x + offset, rows_top + 2.0, align);
+        }
+
+        let duration = format_time(selected_time_range.duration(), resolution);
+        draw_label(
+            &duration,
+            (selection_start + selection_end) / 2.0,
+            height - 24.0,
+            0.5,
+        );
+    }
+
+    // Lines at markers and shading over the spans of longer ones, labelled at the top of the rows
+    fn snapshot_markers(&self, this: &TimelineView, snapshot: &Snapshot) {
+        let marker_color = marker_color(this);
+        let label_background = this
+            .style_context()
+            .lookup_color("view_bg_color")
+            .unwrap_or(RGBA::WHITE);
+        let mut span_color = marker_color;
+        span_color.set_alpha(0.12);
+        let (height, rows_top) = (this.height() as f32, self.rows_top() as f32);
+        let display_time_range = this.display_time_range();
+
+        for marker in self.markers.borrow().iter() {
+            if marker.end() < display_time_range.start() || marker.start > display_time_range.end()
+            {
+                continue;
+            }
+            let start = this.time_to_widget_point(marker.start) as f32;
+            let end = this.time_to_widget_point(marker.end()) as f32;
+            if marker.duration > 0 {
+                snapshot.append_color(
+                    &span_color,
+                    &Rect::new(start, rows_top, end - start, height - rows_top),
+                );
+                snapshot.append_color(
+                    &marker_color,
+                    &Rect::new(end - 0.5, rows_top, 1.0, height - rows_top),
+                );
+            }
+            snapshot.append_color(
+                &marker_color,
+                &Rect::new(start - 0.5, rows_top, 1.0, height - rows_top),
+            );
+
+            let layout = this.create_pango_layout(Some(&marker.name));
+            let (label_width, label_height) = layout.pixel_size();
+            let x = start.max(0.0) + 3.0;
+            snapshot.append_color(
+                &label_background,
+                &Rect::new(
+                    x - 3.0,
+                    rows_top,
+                    label_width as f32 + 6.0,
+                    label_height as f32,
+                ),
+            );
+            snapshot.append_color(
+                &marker_color,
+                &Rect::new(x - 3.0, rows_top, 2.0, label_height as f32),
+            );
+            snapshot.render_layout(&this.style_context(), x as f64, rows_top as f64, &layout);
+        }
+    }
+
+    // The selection edge within grabbing distance of a point, if any
+    fn edge_at(&self, widget_point: f64) -> Option<SelectionEdge> {
+        let this = self.instance();
+        let selected_time_range = this.selected_time_range()?;
+        let selection_start = this.time_to_widget_point(selected_time_range.start());
+        let selection_end = this.time_to_widget_point(selected_time_range.end());
+        if (widget_point - selection_end).abs() <= HANDLE_GRAB_DISTANCE {
+            Some(SelectionEdge::End)
+        } else if (widget_point - selection_start).abs() <= HANDLE_GRAB_DISTANCE {
+            Some(SelectionEdge::Start)
+        } else {
+            None
+        }
+    }
+
+    // The zoom button is shown while hovering over the selection, but not while making it
+    fn update_zoom_button(&self) {
+        let zoom_button = match self.zoom_button.get() {
+            Some(zoom_button) => zoom_button,
+            None => return,
+        };
+        let this = self.instance();
+        let hovered_selection = match (this.selected_time_range(), self.pointer_x.get()) {
+            (Some(selected_time_range), Some(pointer_x)) if !self.selecting.get() => {
+                let selection_start = this.time_to_widget_point(selected_time_range.start());
+                let selection_end = this.time_to_widget_point(selected_time_range.end());
+                let hovering = selected_time_range.duration() > 0
+                    && (selection_start..=selection_end).contains(&pointer_x);
+                hovering.then_some((selection_start, selection_end))
+            }
+            _ => None,
+        };
+        // Laying out the button lays out every row, so only do it when the button moves
+        if self.zoom_button_bounds.replace(hovered_selection) != hovered_selection {
+            zoom_button.set_visible(hovered_selection.is_some());
+            this.queue_allocate();
+        }
     }
 }
 
-fn clear_selection(gesture: &GestureDrag, _: f64, _: f64) {
-    let timeline = gesture.widget().downcast::<TimelineView>().unwrap();
-    timeline.set_selected_time_range(None);
+pub fn marker_color(widget: &impl IsA<Widget>) -> RGBA {
+    widget
+        .style_context()
+        .lookup_color("purple_3")
+        .unwrap_or(RGBA::new(0.57, 0.25, 0.67, 1.0))
+}
 
-    timeline.queue_draw();
+// Rows are every child but the ticker, minimap and zoom button, scrolling together beneath the
+// ticker and minimap
+fn is_row(widget: &Widget) -> bool {
+    widget.is::<TimelineRow>()
+        || widget.is::<ProcessRow>()
+        || widget.is::<CpuLane>()
+        || widget.is::<CpuUsageGraph>()
 }
 
-fn update_selection(gesture: &GestureDrag, _: f64, _: f64) {
-    let drag_offset = gesture.offset().unwrap().0;
-    let drag_point = gesture.start_point().unwrap().0;
-    let (drag_start, drag_end) = if drag_offset < 0.0 {
-        (drag_point + drag_offset, drag_point)
-    } else {
-        (drag_point, drag_point + drag_offset)
+// Dragging from a selection edge moves just that edge, and dragging anywhere else starts a new selection.
+// The old selection stays until the new one replaces it, so it doesn't flash away on every press.
+fn begin_selection(gesture: &GestureDrag, x: f64, _: f64) {
+    let timeline = gesture.widget().downcast::<TimelineView>().unwrap();
+    let imp = timeline.imp();
+    timeline.grab_focus();
+
+    let drag_anchor = match (imp.edge_at(x), timeline.selected_time_range()) {
+        (Some(SelectionEdge::Start), Some(selected_time_range)) => Some(selected_time_range.end()),
+        (Some(SelectionEdge::End), Some(selected_time_range)) => Some(selected_time_range.start()),
+        _ => None,
     };
+    imp.drag_anchor.set(drag_anchor);
+}
+
+// Nothing changes until the pointer moves past the drag threshold, so clicks stay clicks
+fn update_selection(gesture: &GestureDrag, offset_x: f64, offset_y: f64) {
+    let (start_x, start_y) = gesture.start_point().unwrap();
 
     let timeline = gesture.widget().downcast::<TimelineView>().unwrap();
+    let imp = timeline.imp();
+    if !imp.selecting.get() {
+        let moved_past_threshold = timeline.drag_check_threshold(
+            start_x as i32,
+            start_y as i32,
+            (start_x + offset_x) as i32,
+            (start_y + offset_y) as i32,
+        );
+        if !moved_past_threshold {
+            return;
+        }
+        imp.selecting.set(true);
+        imp.update_zoom_button();
+    }
 
-    timeline.set_selected_time_range(Some(TimelineRange::new(
-        timeline.widget_to_time_point(drag_start),
-        timeline.widget_to_time_point(drag_end),
-    )));
+    let anchor_time = imp
+        .drag_anchor
+        .get()
+        .unwrap_or_else(|| timeline.widget_to_time_point(start_x));
+    timeline.select_between(
+        anchor_time,
+        timeline.widget_to_time_point(start_x + offset_x),
+    );
+}
 
-    timeline.queue_draw();
+// Clicking without dragging clears the selection, unless the click was on a thread row, which
+// inspects the samples under it instead
+fn finish_selection(gesture: &GestureDrag, _: f64, _: f64) {
+    let timeline = gesture.widget().downcast::<TimelineView>().unwrap();
+    let imp = timeline.imp();
+    let (x, y) = gesture.start_point().unwrap();
+    let clicked_row = timeline
+        .pick(x, y, PickFlags::DEFAULT)
+        .and_then(|widget| widget.ancestor(TimelineRow::static_type()));
+    if !imp.selecting.get() && imp.drag_anchor.get().is_none() && clicked_row.is_none() {
+        timeline.set_selected_time_range(None);
+        timeline.queue_draw();
+    }
+    imp.selecting.set(false);
+    imp.update_zoom_button();
+}
+
+fn track_pointer(controller: &EventControllerMotion, x: f64, _: f64) {
+    let timeline = controller.widget().downcast::<TimelineView>().unwrap();
+    let imp = timeline.imp();
+    imp.pointer_x.set(Some(x));
+    imp.update_zoom_button();
+
+    if !imp.selecting.get() {
+        let over_edge = imp.edge_at(x).is_some();
+        timeline.set_cursor_from_name(over_edge.then_some("col-resize"));
+    }
+}
+
+fn forget_pointer(controller: &EventControllerMotion) {
+    let timeline = controller.widget().downcast::<TimelineView>().unwrap();
+    timeline.imp().pointer_x.set(None);
+    timeline.imp().update_zoom_button();
+}
+
+// Ctrl+scroll zooms around the pointer, plain scrolling is left to the ScrolledWindow
+fn zoom(controller: &EventControllerScroll, _: f64, dy: f64) -> Inhibit {
+    if !controller
+        .current_event_state()
+        .contains(ModifierType::CONTROL_MASK)
+    {
+        return Inhibit(false);
+    }
+
+    let timeline = controller.widget().downcast::<TimelineView>().unwrap();
+    let pointer_x = timeline
+        .imp()
+        .pointer_x
+        .get()
+        .unwrap_or(timeline.width() as f64 / 2.0);
+    timeline.zoom_around(pointer_x, ZOOM_STEP.powf(dy));
+    Inhibit(true)
+}
+
+// Left/Right nudge the selection edge last moved by a pixel's worth of time, or ten with Ctrl
+fn nudge_selection(
+    controller: &EventControllerKey,
+    key: Key,
+    _: u32,
+    modifiers: ModifierType,
+) -> Inhibit {
+    let direction = match key {
+        Key::Left => -1.0,
+        Key::Right => 1.0,
+        _ => return Inhibit(false),
+    };
+    let timeline = controller.widget().downcast::<TimelineView>().unwrap();
+    let selected_time_range = match timeline.selected_time_range() {
+        Some(selected_time_range) => selected_time_range,
+        None => return Inhibit(false),
+    };
+
+    let pixels = if modifiers.contains(ModifierType::CONTROL_MASK) {
+        10.0
+    } else {
+        1.0
+    };
+    let display_duration = timeline.display_time_range().duration() as f64;
+    let step = direction * pixels * display_duration / timeline.width().max(1) as f64;
+    let (anchor_time, moved_time) = match timeline.imp().active_edge.get() {
+        SelectionEdge::Start => (selected_time_range.end(), selected_time_range.start()),
+        SelectionEdge::End => (selected_time_range.start(), selected_time_range.end()),
+    };
+    let profile_time_range = timeline.profile_time_range();
+    let moved_time = (moved_time as f64 + step.abs().max(1.0).copysign(step))
+        .round()
+        .clamp(
+            profile_time_range.start() as f64,
+            profile_time_range.end() as f64,
+        );
+    timeline.select_between(anchor_time, moved_time as u64);
+    Inhibit(true)
+}
+
+fn reset_zoom(gesture: &GestureClick, n_press: i32, _: f64, _: f64) {
+    if n_press == 2 {
+        let timeline = gesture.widget().downcast::<TimelineView>().unwrap();
+        timeline.reset_zoom();
+    }
 }
 
 #[object_subclass]
@@ -129,10 +1142,36 @@ impl ObjectSubclass for TimelineViewPrivate {
     const NAME: &'static str = "WtfTimelineView";
     type Type = TimelineView;
     type ParentType = Widget;
+    type Interfaces = (Scrollable,);
 
     fn class_init(klass: &mut Self::Class) {
         klass.set_css_name("timeline-view");
-        klass.set_layout_manager_type::<BoxLayout>();
+
+        // Thread and process actions, for the menus and headers of rows
+        let thread_actions: [(&str, fn(&TimelineView, u32)); 4] = [
+            (
+                "timeline.select-thread",
+                TimelineView::toggle_thread_selected,
+            ),
+            ("timeline.pin-thread", TimelineView::toggle_thread_pinned),
+            ("timeline.hide-thread", TimelineView::hide_thread),
+            (
+                "timeline.collapse-process",
+                TimelineView::toggle_process_collapsed,
+            ),
+        ];
+        for (action, activate) in thread_actions {
+            klass.install_action(action, Some("u"), move |this, _, parameter| {
+                let id = parameter.and_then(|p| p.get::<u32>()).unwrap();
+                activate(this, id);
+            });
+        }
+        klass.install_action("timeline.clear-thread-selection", None, |this, _, _| {
+            this.clear_thread_selection();
+        });
+        klass.install_action("timeline.show-hidden-threads", None, |this, _, _| {
+            this.show_hidden_threads();
+        });
     }
 }
 
@@ -140,25 +1179,62 @@ impl ObjectImpl for TimelineViewPrivate {
     fn constructed(&self, this: &Self::Type) {
         self.parent_constructed(this);
 
-        let layout_manager = this
-            .layout_manager()
-            .unwrap()
-            .downcast::<gtk::BoxLayout>()
-            .unwrap();
-        layout_manager.set_orientation(Orientation::Vertical);
-        layout_manager.set_spacing(6);
-
         this.set_hexpand(true);
         this.set_vexpand(true);
         this.set_overflow(Overflow::Hidden);
 
         let selection_controller = GestureDrag::new();
-        selection_controller.connect_drag_begin(clear_selection);
+        selection_controller.connect_drag_begin(begin_selection);
         selection_controller.connect_drag_update(update_selection);
+        selection_controller.connect_drag_end(finish_selection);
         this.add_controller(&selection_controller);
 
+        let motion_controller = EventControllerMotion::new();
+        motion_controller.connect_motion(track_pointer);
+        motion_controller.connect_leave(forget_pointer);
+        this.add_controller(&motion_controller);
+
+        let zoom_controller = EventControllerScroll::new(EventControllerScrollFlags::VERTICAL);
+        zoom_controller.connect_scroll(zoom);
+        this.add_controller(&zoom_controller);
+
+        this.set_focusable(true);
+        let key_controller = EventControllerKey::new();
+        key_controller.connect_key_pressed(nudge_selection);
+        this.add_controller(&key_controller);
+
+        let reset_zoom_controller = GestureClick::new();
+        reset_zoom_controller.connect_pressed(reset_zoom);
+        this.add_controller(&reset_zoom_controller);
+
         let timeline_ticker = TimelineTicker::new();
         timeline_ticker.set_parent(this);
+        self.ticker.set(timeline_ticker).unwrap();
+
+        // The minimap shows the whole profile, and marks the selection
+        let timeline_minimap = TimelineMinimap::new();
+        timeline_minimap.set_parent(this);
+        for property in [
+            "profile",
+            "selected-time-range",
+            "transform-count",
+            "marker-count",
+        ] {
+            this.connect_notify_local(
+                Some(property),
+                clone!(@weak timeline_minimap => move |_, _| timeline_minimap.queue_draw()),
+            );
+        }
+        self.minimap.set(timeline_minimap).unwrap();
+
+        let zoom_button = Button::from_icon_name("zoom-in-symbolic");
+        zoom_button.set_tooltip_text(Some("Zoom to Selection"));
+        zoom_button.add_css_class("osd");
+        zoom_button.add_css_class("circular");
+        zoom_button.set_visible(false);
+        zoom_button.connect_clicked(clone!(@weak this => move |_| this.zoom_to_selection()));
+        zoom_button.set_parent(this);
+        self.zoom_button.set(zoom_button).unwrap();
     }
 
     fn dispose(&self, this: &Self::Type) {
@@ -168,19 +1244,168 @@ impl ObjectImpl for TimelineViewPrivate {
     }
 
     fn properties() -> &'static [ParamSpec] {
-        Self::derived_properties()
+        static PROPERTIES: Lazy<Vec<ParamSpec>> = Lazy::new(|| {
+            let mut properties = TimelineViewPrivate::derived_properties().to_vec();
+            for name in [
+                "hadjustment",
+                "vadjustment",
+                "hscroll-policy",
+                "vscroll-policy",
+            ] {
+                properties.push(ParamSpecOverride::for_interface::<Scrollable>(name));
+            }
+            properties
+        });
+        PROPERTIES.as_ref()
     }
     fn set_property(&self, this: &Self::Type, id: usize, value: &Value, pspec: &ParamSpec) {
-        Self::derived_set_property(self, this, id, value, pspec).unwrap();
+        match pspec.name() {
+            "hadjustment" => self.set_hadjustment(value.get().unwrap()),
+            "vadjustment" => self.set_vadjustment(value.get().unwrap()),
+            "hscroll-policy" => self.hscroll_policy.set(Some(value.get().unwrap())),
+            "vscroll-policy" => self.vscroll_policy.set(Some(value.get().unwrap())),
+            _ => Self::derived_set_property(self, this, id, value, pspec).unwrap(),
+        }
     }
     fn property(&self, this: &Self::Type, id: usize, pspec: &ParamSpec) -> Value {
-        Self::derived_property(self, this, id, pspec).unwrap()
+        let adjustment = |adjustment: &RefCell<Option<(Adjustment, SignalHandlerId)>>| {
+            adjustment
+                .borrow()
+                .as_ref()
+                .map(|(adjustment, _)| adjustment.clone())
+                .to_value()
+        };
+        match pspec.name() {
+            "hadjustment" => adjustment(&self.hadjustment),
+            "vadjustment" => adjustment(&self.vadjustment),
+            "hscroll-policy" => self
+                .hscroll_policy
+                .get()
+                .unwrap_or(ScrollablePolicy::Minimum)
+                .to_value(),
+            "vscroll-policy" => self
+                .vscroll_policy
+                .get()
+                .unwrap_or(ScrollablePolicy::Minimum)
+                .to_value(),
+            _ => Self::derived_property(self, this, id, pspec).unwrap(),
+        }
     }
 }
 
 impl WidgetImpl for TimelineViewPrivate {
+    fn measure(
+        &self,
+        _: &Self::Type,
+        orientation: Orientation,
+        for_size: i32,
+    ) -> (i32, i32, i32, i32) {
+        match orientation {
+            Orientation::Horizontal => (0, 0, -1, -1),
+            Orientation::Vertical => {
+                let header_height = self
+                    .ticker
+                    .get()
+                    .unwrap()
+                    .measure(Orientation::Vertical, for_size)
+                    .1
+                    + self
+                        .minimap
+                        .get()
+                        .unwrap()
+                        .measure(Orientation::Vertical, for_size)
+                        .1;
+                let rows_height = self.rows_height(for_size);
+                (
+                    header_height,
+                    header_height + ROW_SPACING + rows_height,
+                    -1,
+                    -1,
+                )
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    fn size_allocate(&self, this: &Self::Type, width: i32, height: i32, _: i32) {
+        // The ticker and minimap stay at the top, with the rows scrolling beneath them
+        let ticker = self.ticker.get().unwrap();
+        let ticker_height = ticker.measure(Orientation::Vertical, width).1;
+        ticker.size_allocate(&Allocation::new(0, 0, width, ticker_height), -1);
+        let minimap = self.minimap.get().unwrap();
+        let minimap_height = minimap.measure(Orientation::Vertical, width).1;
+        minimap.size_allocate(
+            &Allocation::new(0, ticker_height, width, minimap_height),
+            -1,
+        );
+
+        let rows_top = ticker_height + minimap_height + ROW_SPACING;
+        self.rows_top.set(rows_top);
+        let page_size = (height - rows_top).max(0) as f64;
+        let rows_height = self.rows_height(width) as f64;
+        let mut scroll_offset = 0.0;
+        if let Some((vadjustment, _)) = &*self.vadjustment.borrow() {
+            scroll_offset = vadjustment
+                .value()
+                .clamp(0.0, (rows_height - page_size).max(0.0));
+            vadjustment.configure(
+                scroll_offset,
+                0.0,
+                rows_height.max(page_size),
+                30.0,
+                page_size * 0.9,
+                page_size,
+            );
+        }
+
+        let mut y = rows_top - scroll_offset.round() as i32;
+        self.for_each_row(|row| {
+            let row_height = row.measure(Orientation::Vertical, width).1;
+            row.size_allocate(&Allocation::new(0, y, width, row_height), -1);
+            y += row_height + ROW_SPACING;
+        });
+
+        // Centered over the selection, just below the ticker
+        let zoom_button = self.zoom_button.get().unwrap();
+        let (button_width, button_height) = (
+            zoom_button.measure(Orientation::Horizontal, -1).1,
+            zoom_button.measure(Orientation::Vertical, -1).1,
+        );
+        let selection_center = match this.selected_time_range() {
+            Some(selected_time_range) => {
+                (this.time_to_widget_point(selected_time_range.start())
+                    + this.time_to_widget_point(selected_time_range.end()))
+                    / 2.0
+            }
+            None => width as f64 / 2.0,
+        };
+        let button_x =
+            (selection_center as i32 - button_width / 2).clamp(0, (width - button_width).max(0));
+        zoom_button.size_allocate(
+            &Allocation::new(
+                button_x,
+                rows_top + ROW_SPACING,
+                button_width,
+                button_height,
+            ),
+            -1,
+        );
+    }
+
     fn snapshot(&self, this: &Self::Type, snapshot: &Snapshot) {
-        self.parent_snapshot(this, snapshot);
+        // Rows scrolled up are hidden beneath the ticker and minimap
+        let ticker = self.ticker.get().unwrap();
+        let rows_top = self.rows_top() as f32;
+        snapshot.push_clip(&Rect::new(
+            0.0,
+            rows_top,
+            this.width() as f32,
+            (this.height() as f32 - rows_top).max(0.0),
+        ));
+        self.for_each_row(|row| this.snapshot_child(row, snapshot));
+        self.snapshot_markers(this, snapshot);
+        snapshot.pop();
+        this.snapshot_child(ticker, snapshot);
 
         if let Some(selected_time_range) = this.selected_time_range() {
             let selection_start = this.time_to_widget_point(selected_time_range.start()) as f32;
@@ -201,6 +1426,14 @@ impl WidgetImpl for TimelineViewPrivate {
                     this.height() as f32,
                 ),
             );
+            self.snapshot_selection_handles(this, snapshot, &selected_time_range);
         }
+
+        // Over the selection, which is marked on it in its own scale
+        this.snapshot_child(self.minimap.get().unwrap(), snapshot);
+
+        this.snapshot_child(self.zoom_button.get().unwrap(), snapshot);
     }
 }
+
+impl ScrollableImpl for TimelineViewPrivate {}
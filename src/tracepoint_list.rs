@@ -0,0 +1,224 @@
+use crate::timeline_range::TimelineRangePrivatePropertiesExt;
+use crate::timeline_view::{TimelineView, TimelineViewPrivatePropertiesExt};
+use crate::wtf_profile::Profile;
+use adw::subclass::prelude::BinImpl;
+use adw::traits::BinExt;
+use adw::Bin;
+use glib::once_cell::sync::OnceCell;
+use glib::subclass::prelude::{
+    DerivedObjectProperties, ObjectImpl, ObjectImplExt, ObjectSubclass, ObjectSubclassExt,
+    ObjectSubclassIsExt,
+};
+use glib::{
+    clone, idle_add_local_once, object_subclass, ObjectExt, ParamSpec, Properties, StaticType,
+    Value,
+};
+use gtk::pango::EllipsizeMode;
+use gtk::subclass::prelude::{WidgetClassSubclassExt, WidgetImpl};
+use gtk::traits::{BoxExt, WidgetExt};
+use gtk::{
+    Accessible, Buildable, ConstraintTarget, Label, ListBox, Orientation, ScrolledWindow,
+    SelectionMode, Widget,
+};
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+// Rows are built eagerly, so busy tracepoints like sched:sched_switch are cut off
+const MAX_ROWS: usize = 500;
+
+glib::wrapper! {
+    pub struct TracepointList(ObjectSubclass<TracepointListPrivate>)
+    @extends Bin, Widget,
+    @implements Accessible, Buildable, ConstraintTarget;
+}
+
+impl TracepointList {
+    // Selection changes arrive on every drag update, so coalesce them into one rebuild
+    fn queue_rebuild(&self) {
+        if !self.imp().rebuild_queued.replace(true) {
+            idle_add_local_once(clone!(@weak self as this => move || {
+                this.imp().rebuild_queued.set(false);
+                this.rebuild();
+            }));
+        }
+    }
+
+    fn rebuild(&self) {
+        let imp = self.imp();
+        let list = imp.list.get().unwrap();
+        while let Some(row) = list.first_child() {
+            list.remove(&row);
+        }
+
+        // Transforms and thread filters leave tracepoints as they are, so they come from the original
+        let timeline = match self.timeline() {
+            Some(timeline) => timeline,
+            None => return,
+        };
+        let profile = match timeline.profile() {
+            Some(profile) => profile.borrow::<Rc<Profile>>().clone(),
+            None => return,
+        };
+        let tracepoints = &profile.tracepoints;
+        self.set_visible(!tracepoints.is_empty());
+        let (start, end) = match timeline.analysis_time_range() {
+            Some(time_range) => time_range,
+            None => return,
+        };
+
+        let filter_threads = timeline.selected_thread_count() > 0;
+        let events = tracepoints
+            .in_range(start, end)
+            .filter(|&event| !filter_threads || timeline.is_thread_selected(tracepoints.tid[event]))
+            .collect::<Vec<_>>();
+
+        imp.subtitle.get().unwrap().set_label(&match events.len() {
+            1 => "1 event".to_owned(),
+            count if count > MAX_ROWS => format!("{count} events, showing the first {MAX_ROWS}"),
+            count => format!("{count} events"),
+        });
+        for &event in events.iter().take(MAX_ROWS) {
+            list.append(&new_row(&timeline, &profile, event));
+        }
+    }
+}
+
+fn new_row(timeline: &TimelineView, profile: &Profile, event: usize) -> gtk::Box {
+    let tracepoints = &profile.tracepoints;
+
+    let name = Label::new(Some(profile.strings.get(tracepoints.name[event])));
+    name.set_xalign(0.0);
+    name.set_hexpand(true);
+    name.set_ellipsize(EllipsizeMode::End);
+
+    let tid = tracepoints.tid[event];
+    let thread = match profile.thread_name(tid) {
+        Some(thread_name) => format!("{thread_name} ({tid})"),
+        None => format!("Thread {tid}"),
+    };
+    let thread = Label::new(Some(&thread));
+    thread.add_css_class("dim-label");
+
+    // Precise to a pixel at the current zoom
+    let resolution =
+        (timeline.display_time_range().duration() / timeline.width().max(1) as u64).max(1);
+    let time = timeline.format_time_label(tracepoints.time[event], resolution);
+    let time = Label::new(Some(&time));
+    time.add_css_class("numeric");
+    time.add_css_class("dim-label");
+
+    let header = gtk::Box::new(Orientation::Horizontal, 6);
+    header.append(&name);
+    header.append(&thread);
+    header.append(&time);
+
+    let fields = tracepoints.fields(event).map(|field| {
+        let field_name = profile.strings.get(tracepoints.field_name[field]);
+        let value = profile.strings.get(tracepoints.field_value[field]);
+        format!("{field_name}={value}")
+    });
+    let fields = fields.collect::<Vec<_>>();
+    let fields_label = Label::new(Some(&fields.join(" ")));
+    fields_label.set_xalign(0.0);
+    fields_label.set_ellipsize(EllipsizeMode::End);
+    fields_label.set_tooltip_text(Some(&fields.join("\n")));
+    fields_label.add_css_class("caption");
+    fields_label.add_css_class("monospace");
+
+    let row = gtk::Box::new(Orientation::Vertical, 2);
+    row.append(&header);
+    row.append(&fields_label);
+    row
+}
+
+// ------------------------------------------------------------------------------
+
+#[derive(Properties, Default)]
+pub struct TracepointListPrivate {
+    #[property(get, set = Self::set_timeline, builder(TimelineView::static_type()))]
+    timeline: RefCell<Option<TimelineView>>,
+
+    subtitle: OnceCell<Label>,
+    list: OnceCell<ListBox>,
+    rebuild_queued: Cell<bool>,
+}
+
+impl TracepointListPrivate {
+    pub fn set_timeline(&self, value: Option<TimelineView>) {
+        let this = self.instance();
+        if let Some(timeline) = &value {
+            for property in [
+                "profile",
+                "selected-time-range",
+                "selected-thread-count",
+                "time-of-day",
+            ] {
+                timeline.connect_notify_local(
+                    Some(property),
+                    clone!(@weak this => move |_, _| this.queue_rebuild()),
+                );
+            }
+        }
+
+        *self.timeline.borrow_mut() = value;
+        this.queue_rebuild();
+    }
+}
+
+#[object_subclass]
+impl ObjectSubclass for TracepointListPrivate {
+    const NAME: &'static str = "WtfTracepointList";
+    type Type = TracepointList;
+    type ParentType = Bin;
+
+    fn class_init(klass: &mut Self::Class) {
+        klass.set_css_name("tracepoint-list");
+    }
+}
+
+impl ObjectImpl for TracepointListPrivate {
+    fn constructed(&self, this: &Self::Type) {
+        self.parent_constructed(this);
+
+        let title = Label::new(Some("Tracepoints"));
+        title.set_xalign(0.0);
+        title.add_css_class("heading");
+
+        let subtitle = Label::new(None);
+        subtitle.set_xalign(0.0);
+        subtitle.set_ellipsize(EllipsizeMode::End);
+        subtitle.add_css_class("dim-label");
+
+        let list = ListBox::new();
+        list.set_selection_mode(SelectionMode::None);
+        list.add_css_class("boxed-list");
+
+        let scrolled_window = ScrolledWindow::new();
+        scrolled_window.set_child(Some(&list));
+        scrolled_window.set_min_content_height(160);
+
+        let content = gtk::Box::new(Orientation::Vertical, 6);
+        content.append(&title);
+        content.append(&subtitle);
+        content.append(&scrolled_window);
+        this.set_child(Some(&content));
+        // Only profiles with tracepoints show the list
+        this.set_visible(false);
+
+        self.subtitle.set(subtitle).unwrap();
+        self.list.set(list).unwrap();
+    }
+
+    fn properties() -> &'static [ParamSpec] {
+        Self::derived_properties()
+    }
+    fn set_property(&self, this: &Self::Type, id: usize, value: &Value, pspec: &ParamSpec) {
+        Self::derived_set_property(self, this, id, value, pspec).unwrap();
+    }
+    fn property(&self, this: &Self::Type, id: usize, pspec: &ParamSpec) -> Value {
+        Self::derived_property(self, this, id, pspec).unwrap()
+    }
+}
+
+impl WidgetImpl for TracepointListPrivate {}
+impl BinImpl for TracepointListPrivate {}
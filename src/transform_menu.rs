@@ -0,0 +1,91 @@
+use crate::analysis::{crate_of, module_file_name, Function};
+use crate::wtf_profile::{Profile, EMPTY_STRING};
+use gio::{Menu, MenuItem};
+use glib::{IsA, ToVariant, Variant};
+use gtk::gdk::Rectangle;
+use gtk::traits::{PopoverExt, WidgetExt};
+use gtk::{PopoverMenu, Widget};
+
+// Open a menu of the stack transforms that can be applied to a function at x, y of widget
+// The menu's actions are handled by the ProfilePage containing widget
+// path is the function's callers from the outermost one, if it's known
+pub fn popup_transform_menu(
+    widget: &impl IsA<Widget>,
+    x: f64,
+    y: f64,
+    profile: &Profile,
+    function: Function,
+    path: Option<Vec<Function>>,
+) {
+    let menu = Menu::new();
+    let name = profile.strings.get(function.0);
+
+    let focus_section = Menu::new();
+    focus_section.append_item(&new_item(
+        "Focus on Function",
+        "profile.focus-function",
+        function.to_variant(),
+    ));
+    if let Some(path) = path {
+        focus_section.append_item(&new_item(
+            "Focus on Subtree",
+            "profile.focus-subtree",
+            path.to_variant(),
+        ));
+    }
+    menu.append_section(None, &focus_section);
+
+    let remove_section = Menu::new();
+    remove_section.append_item(&new_item(
+        "Merge Function into Callers",
+        "profile.merge-function",
+        function.to_variant(),
+    ));
+    remove_section.append_item(&new_item(
+        "Drop Samples with Function",
+        "profile.drop-function",
+        function.to_variant(),
+    ));
+    menu.append_section(None, &remove_section);
+
+    let collapse_section = Menu::new();
+    collapse_section.append_item(&new_item(
+        "Collapse Recursion",
+        "profile.collapse-recursion",
+        function.to_variant(),
+    ));
+    collapse_section.append_item(&new_item(
+        "Collapse Direct Recursion",
+        "profile.collapse-direct-recursion",
+        function.to_variant(),
+    ));
+    if function.1 != EMPTY_STRING {
+        let module = module_file_name(profile.strings.get(function.1));
+        collapse_section.append_item(&new_item(
+            &format!("Collapse {module}"),
+            "profile.collapse-module",
+            function.1.to_variant(),
+        ));
+    }
+    if let Some(crate_name) = crate_of(name) {
+        collapse_section.append_item(&new_item(
+            &format!("Collapse {crate_name} Crate"),
+            "profile.collapse-crate",
+            crate_name.to_variant(),
+        ));
+    }
+    menu.append_section(None, &collapse_section);
+
+    let popover = PopoverMenu::from_model(Some(&menu));
+    popover.set_parent(widget);
+    popover.set_has_arrow(false);
+    popover.set_pointing_to(Some(&Rectangle::new(x as i32, y as i32, 1, 1)));
+    popover.connect_closed(|popover| popover.unparent());
+    popover.popup();
+}
+
+fn new_item(label: &str, action: &str, target: Variant) -> MenuItem {
+    let item = MenuItem::new(Some(label), None);
+    item.set_action_and_target_value(Some(action), Some(&target));
+    item
+}
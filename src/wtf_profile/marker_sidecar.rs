@@ -0,0 +1,37 @@
+use super::Marker;
+use serde::Deserialize;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Error as IOError, ErrorKind};
+use std::path::Path;
+
+// One JSON object per line, e.g. {"timestamp": 123456789, "name": "GC started", "duration": 5000}
+// Times are nanoseconds from CLOCK_MONOTONIC, which perf uses for sample times when recording
+// with `perf record -k CLOCK_MONOTONIC`, and duration can be left out for a single point in time
+#[derive(Deserialize)]
+struct SidecarMarker {
+    timestamp: u64,
+    name: String,
+    #[serde(default)]
+    duration: u64,
+}
+
+pub fn read_marker_sidecar<P: AsRef<Path>>(path: P) -> Result<Vec<Marker>, IOError> {
+    let reader = BufReader::new(File::open(path)?);
+    let mut markers = Vec::new();
+    for (line_number, line) in reader.lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let marker = serde_json::from_str::<SidecarMarker>(&line).map_err(|error| {
+            let error_message = format!("Invalid marker on line {}: {error}", line_number + 1);
+            IOError::new(ErrorKind::InvalidData, error_message)
+        })?;
+        markers.push(Marker {
+            start: marker.timestamp,
+            duration: marker.duration,
+            name: marker.name,
+        });
+    }
+    Ok(markers)
+}
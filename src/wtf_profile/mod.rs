@@ -0,0 +1,744 @@
+mod marker_sidecar;
+mod wtf_reader;
+mod wtf_writer;
+
+pub use marker_sidecar::read_marker_sidecar;
+pub use wtf_reader::read_wtf_profile;
+pub use wtf_writer::write_wtf_profile;
+
+use memmap2::Mmap;
+use std::collections::HashMap;
+use std::io::{Error as IOError, Write};
+use std::mem;
+use std::ops::{Deref, Range};
+use std::slice;
+use std::sync::Arc;
+
+pub type StringId = u32;
+pub type FrameId = u32;
+pub type StackId = u32;
+
+// Used in place of a StackId for stacks with no parent, and samples with an empty callchain
+pub const NO_STACK: StackId = u32::MAX;
+// String 0 is always the empty string, used for missing names, modules, and files
+pub const EMPTY_STRING: StringId = 0;
+// Pid of threads that only appear in samples, whose process was never recorded
+pub const UNKNOWN_PID: u32 = u32::MAX;
+// Cpu of samples and tracepoints recorded without their CPU (perf record --sample-cpu)
+pub const UNKNOWN_CPU: u32 = u32::MAX;
+
+pub const SAMPLES_PER_CHUNK: usize = 256;
+
+const WTF_MAGIC: &[u8; 8] = b"WTFPROF\0";
+const WTF_VERSION: u32 = 2;
+
+// Every column of the profile is stored as its own section
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+enum SectionKind {
+    Metadata = 1,
+    StringOffsets,
+    StringBytes,
+    FrameAddress,
+    FrameFunction,
+    FrameModule,
+    FrameFile,
+    FrameLine,
+    StackParent,
+    StackFrame,
+    StackDepth,
+    SampleTime,
+    SampleTid,
+    SampleCpu,
+    SampleStack,
+    SampleWeight,
+    ThreadTid,
+    ThreadPid,
+    ThreadName,
+    ThreadFirstChunk,
+    ThreadChunkCount,
+    ChunkTid,
+    ChunkStartTime,
+    ChunkEndTime,
+    ChunkFirstSample,
+    ChunkSampleCount,
+    ChunkMaxDepth,
+    MarkerStart,
+    MarkerDuration,
+    MarkerNameOffsets,
+    MarkerNameBytes,
+    TracepointTime,
+    TracepointTid,
+    TracepointCpu,
+    TracepointName,
+    TracepointFirstField,
+    TracepointFieldName,
+    TracepointFieldValue,
+}
+
+// In-memory form of a .wtf profile, see wtf_profile_format.txt for the on-disk layout
+// Columns are either owned (when converting a profile) or borrowed from a memory-mapped .wtf file
+pub struct Profile {
+    pub metadata: Vec<(StringId, StringId)>,
+    pub strings: StringTable,
+    pub frames: FrameTable,
+    pub stacks: StackTable,
+    pub samples: SampleTable,
+    pub chunks: ChunkTable,
+    pub threads: ThreadTable,
+    // Sorted by start time
+    pub markers: Vec<Marker>,
+    pub tracepoints: TracepointTable,
+}
+
+impl Profile {
+    pub fn metadata(&self, key: &str) -> Option<&str> {
+        self.metadata
+            .iter()
+            .find(|(k, _)| self.strings.get(*k) == key)
+            .map(|(_, v)| self.strings.get(*v))
+    }
+
+    // Whether every sample knows the CPU it was taken on
+    pub fn knows_cpus(&self) -> bool {
+        !self.samples.cpu.contains(&UNKNOWN_CPU)
+    }
+
+    pub fn thread_index(&self, tid: u32) -> Option<usize> {
+        self.threads.tid.binary_search(&tid).ok()
+    }
+
+    pub fn thread_name(&self, tid: u32) -> Option<&str> {
+        self.thread_index(tid)
+            .map(|i| self.strings.get(self.threads.name[i]))
+            .filter(|name| !name.is_empty())
+    }
+
+    // Range of indices into chunks, for the chunks of a thread overlapping start..=end
+    pub fn thread_chunks_in_range(&self, tid: u32, start: u64, end: u64) -> Range<usize> {
+        let thread = match self.thread_index(tid) {
+            Some(thread) => thread,
+            None => return 0..0,
+        };
+        let first_chunk = self.threads.first_chunk[thread] as usize;
+        let chunk_count = self.threads.chunk_count[thread] as usize;
+        let chunks = first_chunk..first_chunk + chunk_count;
+
+        let first = chunks.start
+            + self.chunks.end_time[chunks.clone()].partition_point(|&time| time < start);
+        let last =
+            chunks.start + self.chunks.start_time[chunks].partition_point(|&time| time <= end);
+        first..last.max(first)
+    }
+
+    // Range of indices into samples, for the samples of a thread with start <= time <= end
+    // Only the chunks at either end of the range are searched, the rest are never read
+    pub fn thread_samples_in_range(&self, tid: u32, start: u64, end: u64) -> Range<usize> {
+        let chunks = self.thread_chunks_in_range(tid, start, end);
+        if chunks.is_empty() {
+            return 0..0;
+        }
+
+        let first_chunk = self.chunks.samples(chunks.start);
+        let last_chunk = self.chunks.samples(chunks.end - 1);
+        let first = first_chunk.start
+            + self.samples.time[first_chunk].partition_point(|&time| time < start);
+        let last =
+            last_chunk.start + self.samples.time[last_chunk].partition_point(|&time| time <= end);
+        first..last.max(first)
+    }
+
+    // The first and last sample or tracepoint times in the profile
+    pub fn time_range(&self) -> Option<(u64, u64)> {
+        let starts = self
+            .chunks
+            .start_time
+            .iter()
+            .chain(self.tracepoints.time.first());
+        let ends = self
+            .chunks
+            .end_time
+            .iter()
+            .chain(self.tracepoints.time.last());
+        Some((*starts.min()?, *ends.max()?))
+    }
+
+    // Frames of a stack, from the leaf (innermost) frame to the root (outermost) frame
+    pub fn stack_frames(&self, mut stack: StackId) -> impl Iterator<Item = FrameId> + '_ {
+        std::iter::from_fn(move || {
+            if stack == NO_STACK {
+                return None;
+            }
+            let frame = self.stacks.frame[stack as usize];
+            stack = self.stacks.parent[stack as usize];
+            Some(frame)
+        })
+    }
+
+    pub fn stack_depth(&self, stack: StackId) -> usize {
+        match stack {
+            NO_STACK => 0,
+            stack => self.stacks.depth[stack as usize] as usize,
+        }
+    }
+
+    pub fn function_name(&self, frame: FrameId) -> &str {
+        self.strings.get(self.frames.function[frame as usize])
+    }
+}
+
+pub struct StringTable {
+    // offsets[i]..offsets[i + 1] is the byte range of string i
+    pub offsets: Column<u32>,
+    pub bytes: Column<u8>,
+}
+
+impl StringTable {
+    pub fn get(&self, id: StringId) -> &str {
+        let start = self.offsets[id as usize] as usize;
+        let end = self.offsets[id as usize + 1] as usize;
+        std::str::from_utf8(&self.bytes[start..end]).unwrap_or_default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.offsets.len().saturating_sub(1)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+pub struct FrameTable {
+    pub address: Column<u64>,
+    pub function: Column<StringId>,
+    pub module: Column<StringId>,
+    pub file: Column<StringId>,
+    pub line: Column<u32>,
+}
+
+impl FrameTable {
+    pub fn len(&self) -> usize {
+        self.address.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+// Prefix tree of stacks, where each stack is its leaf frame plus the stack of its caller
+pub struct StackTable {
+    pub parent: Column<StackId>,
+    pub frame: Column<FrameId>,
+    pub depth: Column<u32>,
+}
+
+impl StackTable {
+    pub fn len(&self) -> usize {
+        self.frame.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+pub struct SampleTable {
+    pub time: Column<u64>,
+    pub tid: Column<u32>,
+    pub cpu: Column<u32>,
+    pub stack: Column<StackId>,
+    pub weight: Column<u32>,
+}
+
+impl SampleTable {
+    pub fn len(&self) -> usize {
+        self.time.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+// Samples are sorted by thread and then time, and each thread's samples are split into chunks
+// A thread's chunks are chunks[first_chunk..first_chunk + chunk_count]
+pub struct ThreadTable {
+    pub tid: Column<u32>,
+    pub pid: Column<u32>,
+    pub name: Column<StringId>,
+    pub first_chunk: Column<u32>,
+    pub chunk_count: Column<u32>,
+}
+
+impl ThreadTable {
+    pub fn len(&self) -> usize {
+        self.tid.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+// Index over time-sorted runs of up to SAMPLES_PER_CHUNK samples from a single thread,
+// along with aggregates that let the timeline draw a zoomed out chunk without reading its samples
+pub struct ChunkTable {
+    pub tid: Column<u32>,
+    pub start_time: Column<u64>,
+    pub end_time: Column<u64>,
+    pub first_sample: Column<u32>,
+    pub sample_count: Column<u32>,
+    pub max_depth: Column<u32>,
+}
+
+impl ChunkTable {
+    pub fn len(&self) -> usize {
+        self.tid.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn samples(&self, chunk: usize) -> Range<usize> {
+        let first_sample = self.first_sample[chunk] as usize;
+        first_sample..first_sample + self.sample_count[chunk] as usize
+    }
+}
+
+// Events recorded by kernel tracepoints, e.g. sched:sched_switch, sorted by time
+// An event's fields are field_name/field_value[first_field[i]..first_field[i + 1]], with values
+// formatted as strings, since each tracepoint has its own field types
+pub struct TracepointTable {
+    pub time: Column<u64>,
+    pub tid: Column<u32>,
+    pub cpu: Column<u32>,
+    pub name: Column<StringId>,
+    pub first_field: Column<u32>,
+    pub field_name: Column<StringId>,
+    pub field_value: Column<StringId>,
+}
+
+impl TracepointTable {
+    pub fn len(&self) -> usize {
+        self.time.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn fields(&self, event: usize) -> Range<usize> {
+        self.first_field[event] as usize..self.first_field[event + 1] as usize
+    }
+
+    // Range of indices of the events with start <= time <= end
+    pub fn in_range(&self, start: u64, end: u64) -> Range<usize> {
+        let first = self.time.partition_point(|&time| time < start);
+        let last = self.time.partition_point(|&time| time <= end);
+        first..last.max(first)
+    }
+}
+
+// ------------------------------------------------------------------------------
+
+pub struct Column<T: ColumnValue> {
+    storage: ColumnStorage<T>,
+}
+
+enum ColumnStorage<T> {
+    Owned(Vec<T>),
+    Mapped {
+        mmap: Arc<Mmap>,
+        offset: usize,
+        len: usize,
+    },
+}
+
+impl<T: ColumnValue> Column<T> {
+    // Caller must ensure the range is in bounds, and aligned for T
+    fn mapped(mmap: Arc<Mmap>, offset: usize, len: usize) -> Self {
+        debug_assert!(offset + len * mem::size_of::<T>() <= mmap.len());
+        debug_assert_eq!((mmap.as_ptr() as usize + offset) % mem::align_of::<T>(), 0);
+        Self {
+            storage: ColumnStorage::Mapped { mmap, offset, len },
+        }
+    }
+}
+
+impl<T: ColumnValue> From<Vec<T>> for Column<T> {
+    fn from(values: Vec<T>) -> Self {
+        Self {
+            storage: ColumnStorage::Owned(values),
+        }
+    }
+}
+
+impl<T: ColumnValue> Deref for Column<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        match &self.storage {
+            ColumnStorage::Owned(values) => values,
+            // Safety: Bounds and alignment are checked by wtf_reader before creating the column,
+            // and ColumnValue is only implemented for integers, which are valid for any bit pattern
+            ColumnStorage::Mapped { mmap, offset, len } => unsafe {
+                slice::from_raw_parts(mmap.as_ptr().add(*offset) as *const T, *len)
+            },
+        }
+    }
+}
+
+// Plain little-endian integers that can be read directly out of a memory-mapped file
+pub trait ColumnValue: Copy + 'static {
+    fn write_le<W: Write>(self, writer: &mut W) -> Result<(), IOError>;
+    fn from_le_slice(bytes: &[u8]) -> Self;
+}
+
+macro_rules! impl_column_value {
+    ($($t:ty),*) => {$(
+        impl ColumnValue for $t {
+            fn write_le<W: Write>(self, writer: &mut W) -> Result<(), IOError> {
+                writer.write_all(&self.to_le_bytes())
+            }
+
+            fn from_le_slice(bytes: &[u8]) -> Self {
+                <$t>::from_le_bytes(bytes.try_into().unwrap())
+            }
+        }
+    )*};
+}
+
+impl_column_value!(u8, u16, u32, u64);
+
+// ------------------------------------------------------------------------------
+
+#[derive(PartialEq, Eq, Hash, Clone, Copy)]
+pub struct Frame {
+    pub address: u64,
+    pub function: StringId,
+    pub module: StringId,
+    pub file: StringId,
+    pub line: u32,
+}
+
+// A named point (duration 0) or span of time, placed by the user or logged by the profiled program
+#[derive(Clone, PartialEq, Eq)]
+pub struct Marker {
+    pub start: u64,
+    pub duration: u64,
+    pub name: String,
+}
+
+impl Marker {
+    pub fn end(&self) -> u64 {
+        self.start.saturating_add(self.duration)
+    }
+}
+
+// Add to markers sorted by start time, skipping any already there, so importing the same markers
+// twice is harmless
+pub fn merge_markers<I: IntoIterator<Item = Marker>>(markers: &mut Vec<Marker>, new_markers: I) {
+    for marker in new_markers {
+        if !markers.contains(&marker) {
+            markers.push(marker);
+        }
+    }
+    markers.sort_by_key(|marker| marker.start);
+}
+
+pub struct Sample {
+    pub time: u64,
+    pub tid: u32,
+    pub cpu: u32,
+    pub stack: StackId,
+    pub weight: u32,
+}
+
+pub struct Tracepoint {
+    pub time: u64,
+    pub tid: u32,
+    pub cpu: u32,
+    pub name: StringId,
+    pub fields: Vec<(StringId, StringId)>,
+}
+
+// Interns strings, frames, and stacks while a profile is being converted to the .wtf format
+pub struct ProfileBuilder {
+    metadata: Vec<(StringId, StringId)>,
+    string_ids: HashMap<String, StringId>,
+    string_offsets: Vec<u32>,
+    string_bytes: Vec<u8>,
+    frame_ids: HashMap<Frame, FrameId>,
+    frames: Vec<Frame>,
+    stack_ids: HashMap<(StackId, FrameId), StackId>,
+    stack_parents: Vec<StackId>,
+    stack_frames: Vec<FrameId>,
+    samples: Vec<Sample>,
+    tracepoints: Vec<Tracepoint>,
+    threads: HashMap<u32, (u32, StringId)>,
+    markers: Vec<Marker>,
+}
+
+impl ProfileBuilder {
+    pub fn new() -> Self {
+        let mut builder = Self {
+            metadata: Vec::new(),
+            string_ids: HashMap::new(),
+            string_offsets: vec![0],
+            string_bytes: Vec::new(),
+            frame_ids: HashMap::new(),
+            frames: Vec::new(),
+            stack_ids: HashMap::new(),
+            stack_parents: Vec::new(),
+            stack_frames: Vec::new(),
+            samples: Vec::new(),
+            tracepoints: Vec::new(),
+            threads: HashMap::new(),
+            markers: Vec::new(),
+        };
+        builder.intern_string("");
+        builder
+    }
+
+    // Start with the metadata, strings, frames, and stacks of a profile, copied as they are so that
+    // their ids stay the same, even where the profile has duplicates this builder wouldn't make.
+    // Its markers and tracepoints aren't tied to samples, so they're carried over too.
+    pub fn from_tables(profile: &Profile) -> Self {
+        let mut builder = Self::new();
+        builder.metadata = profile.metadata.clone();
+        builder.markers = profile.markers.clone();
+
+        let tracepoints = &profile.tracepoints;
+        for event in 0..tracepoints.len() {
+            let fields = tracepoints.fields(event).map(|field| {
+                (
+                    tracepoints.field_name[field],
+                    tracepoints.field_value[field],
+                )
+            });
+            builder.tracepoints.push(Tracepoint {
+                time: tracepoints.time[event],
+                tid: tracepoints.tid[event],
+                cpu: tracepoints.cpu[event],
+                name: tracepoints.name[event],
+                fields: fields.collect(),
+            });
+        }
+
+        builder.string_offsets = profile.strings.offsets.to_vec();
+        builder.string_bytes = profile.strings.bytes.to_vec();
+        builder.string_ids.clear();
+        for string in 0..profile.strings.len() as StringId {
+            let key = profile.strings.get(string).to_owned();
+            builder.string_ids.entry(key).or_insert(string);
+        }
+
+        let frames = &profile.frames;
+        for frame in 0..frames.len() {
+            builder.frames.push(Frame {
+                address: frames.address[frame],
+                function: frames.function[frame],
+                module: frames.module[frame],
+                file: frames.file[frame],
+                line: frames.line[frame],
+            });
+        }
+        for (frame, &frame_key) in builder.frames.iter().enumerate() {
+            builder
+                .frame_ids
+                .entry(frame_key)
+                .or_insert(frame as FrameId);
+        }
+
+        builder.stack_parents = profile.stacks.parent.to_vec();
+        builder.stack_frames = profile.stacks.frame.to_vec();
+        for stack in 0..profile.stacks.len() {
+            let key = (builder.stack_parents[stack], builder.stack_frames[stack]);
+            builder.stack_ids.entry(key).or_insert(stack as StackId);
+        }
+        builder
+    }
+
+    pub fn intern_string(&mut self, string: &str) -> StringId {
+        if let Some(&id) = self.string_ids.get(string) {
+            return id;
+        }
+        let id = self.string_offsets.len() as StringId - 1;
+        self.string_bytes.extend_from_slice(string.as_bytes());
+        self.string_offsets.push(self.string_bytes.len() as u32);
+        self.string_ids.insert(string.to_owned(), id);
+        id
+    }
+
+    pub fn intern_frame(&mut self, frame: Frame) -> FrameId {
+        let frames = &mut self.frames;
+        *self.frame_ids.entry(frame).or_insert_with(|| {
+            frames.push(frame);
+            frames.len() as FrameId - 1
+        })
+    }
+
+    pub fn intern_stack(&mut self, parent: StackId, frame: FrameId) -> StackId {
+        let (stack_parents, stack_frames) = (&mut self.stack_parents, &mut self.stack_frames);
+        *self.stack_ids.entry((parent, frame)).or_insert_with(|| {
+            stack_parents.push(parent);
+            stack_frames.push(frame);
+            stack_frames.len() as StackId - 1
+        })
+    }
+
+    // Intern a callchain ordered from the leaf frame to the root frame, as perf records them
+    pub fn intern_callchain<I>(&mut self, callchain: I) -> StackId
+    where
+        I: IntoIterator<Item = FrameId>,
+        I::IntoIter: DoubleEndedIterator,
+    {
+        callchain
+            .into_iter()
+            .rev()
+            .fold(NO_STACK, |parent, frame| self.intern_stack(parent, frame))
+    }
+
+    pub fn add_sample(&mut self, sample: Sample) {
+        self.samples.push(sample);
+    }
+
+    pub fn add_tracepoint(&mut self, tracepoint: Tracepoint) {
+        self.tracepoints.push(tracepoint);
+    }
+
+    pub fn set_thread(&mut self, tid: u32, pid: u32, name: StringId) {
+        self.threads.insert(tid, (pid, name));
+    }
+
+    pub fn add_metadata(&mut self, key: &str, value: &str) {
+        let key = self.intern_string(key);
+        let value = self.intern_string(value);
+        self.metadata.push((key, value));
+    }
+
+    pub fn finish(mut self) -> Profile {
+        // Threads that only appear in samples still get an (unnamed) entry
+        for sample in &self.samples {
+            self.threads
+                .entry(sample.tid)
+                .or_insert((UNKNOWN_PID, EMPTY_STRING));
+        }
+        let mut threads = self.threads.into_iter().collect::<Vec<_>>();
+        threads.sort_unstable_by_key(|(tid, _)| *tid);
+
+        // Group samples by thread, in time order, so that each thread's samples can be chunked
+        let mut samples = self.samples;
+        samples.sort_by_key(|sample| (sample.tid, sample.time));
+
+        // Parents always come before their children, so depths can be computed in one pass
+        let mut stack_depths = Vec::with_capacity(self.stack_parents.len());
+        for &parent in &self.stack_parents {
+            let parent_depth = stack_depths.get(parent as usize).copied().unwrap_or(0);
+            stack_depths.push(parent_depth + 1);
+        }
+        let depth_of = |stack: StackId| stack_depths.get(stack as usize).copied().unwrap_or(0);
+
+        let mut chunks = Vec::new();
+        let mut thread_chunks = Vec::with_capacity(threads.len());
+        for (tid, _) in &threads {
+            let first_chunk = chunks.len() as u32;
+            let first_sample = samples.partition_point(|sample| sample.tid < *tid);
+            let end_sample = samples.partition_point(|sample| sample.tid <= *tid);
+            for chunk_start in (first_sample..end_sample).step_by(SAMPLES_PER_CHUNK) {
+                let chunk_end = (chunk_start + SAMPLES_PER_CHUNK).min(end_sample);
+                let chunk_samples = &samples[chunk_start..chunk_end];
+                chunks.push(Chunk {
+                    tid: *tid,
+                    start_time: chunk_samples[0].time,
+                    end_time: chunk_samples[chunk_samples.len() - 1].time,
+                    first_sample: chunk_start as u32,
+                    sample_count: chunk_samples.len() as u32,
+                    max_depth: chunk_samples
+                        .iter()
+                        .map(|s| depth_of(s.stack))
+                        .max()
+                        .unwrap(),
+                });
+            }
+            thread_chunks.push((first_chunk, chunks.len() as u32 - first_chunk));
+        }
+
+        let mut tracepoints = self.tracepoints;
+        tracepoints.sort_by_key(|tracepoint| tracepoint.time);
+        let mut first_field = vec![0];
+        let mut fields = Vec::new();
+        for tracepoint in &tracepoints {
+            fields.extend_from_slice(&tracepoint.fields);
+            first_field.push(fields.len() as u32);
+        }
+
+        let frames = self.frames;
+        Profile {
+            metadata: self.metadata,
+            strings: StringTable {
+                offsets: self.string_offsets.into(),
+                bytes: self.string_bytes.into(),
+            },
+            frames: FrameTable {
+                address: column(&frames, |f| f.address),
+                function: column(&frames, |f| f.function),
+                module: column(&frames, |f| f.module),
+                file: column(&frames, |f| f.file),
+                line: column(&frames, |f| f.line),
+            },
+            stacks: StackTable {
+                parent: self.stack_parents.into(),
+                frame: self.stack_frames.into(),
+                depth: stack_depths.into(),
+            },
+            samples: SampleTable {
+                time: column(&samples, |s| s.time),
+                tid: column(&samples, |s| s.tid),
+                cpu: column(&samples, |s| s.cpu),
+                stack: column(&samples, |s| s.stack),
+                weight: column(&samples, |s| s.weight),
+            },
+            chunks: ChunkTable {
+                tid: column(&chunks, |c| c.tid),
+                start_time: column(&chunks, |c| c.start_time),
+                end_time: column(&chunks, |c| c.end_time),
+                first_sample: column(&chunks, |c| c.first_sample),
+                sample_count: column(&chunks, |c| c.sample_count),
+                max_depth: column(&chunks, |c| c.max_depth),
+            },
+            threads: ThreadTable {
+                tid: column(&threads, |(tid, _)| *tid),
+                pid: column(&threads, |(_, (pid, _))| *pid),
+                name: column(&threads, |(_, (_, name))| *name),
+                first_chunk: column(&thread_chunks, |(first, _)| *first),
+                chunk_count: column(&thread_chunks, |(_, count)| *count),
+            },
+            markers: self.markers,
+            tracepoints: TracepointTable {
+                time: column(&tracepoints, |t| t.time),
+                tid: column(&tracepoints, |t| t.tid),
+                cpu: column(&tracepoints, |t| t.cpu),
+                name: column(&tracepoints, |t| t.name),
+                first_field: first_field.into(),
+                field_name: column(&fields, |(name, _)| *name),
+                field_value: column(&fields, |(_, value)| *value),
+            },
+        }
+    }
+}
+
+struct Chunk {
+    tid: u32,
+    start_time: u64,
+    end_time: u64,
+    first_sample: u32,
+    sample_count: u32,
+    max_depth: u32,
+}
+
+fn column<T, V: ColumnValue, F: Fn(&T) -> V>(items: &[T], f: F) -> Column<V> {
+    items.iter().map(f).collect::<Vec<_>>().into()
+}
@@ -0,0 +1,380 @@
+use super::{
+    ChunkTable, Column, ColumnValue, FrameTable, Marker, Profile, SampleTable, SectionKind,
+    StackTable, StringTable, ThreadTable, TracepointTable, NO_STACK, WTF_MAGIC, WTF_VERSION,
+};
+use memmap2::Mmap;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{Error as IOError, ErrorKind};
+use std::mem;
+use std::path::Path;
+use std::sync::Arc;
+
+pub fn read_wtf_profile<P: AsRef<Path>>(path: P) -> Result<Profile, IOError> {
+    let file = File::open(path)?;
+    // Safety: .wtf files are never modified in place, only written whole
+    let mmap = Arc::new(unsafe { Mmap::map(&file)? });
+    let sections = SectionReader::new(mmap)?;
+
+    let metadata = sections.column::<u32>(SectionKind::Metadata)?;
+    let profile = Profile {
+        metadata: metadata.chunks_exact(2).map(|kv| (kv[0], kv[1])).collect(),
+        strings: StringTable {
+            offsets: sections.column(SectionKind::StringOffsets)?,
+            bytes: sections.column(SectionKind::StringBytes)?,
+        },
+        frames: FrameTable {
+            address: sections.column(SectionKind::FrameAddress)?,
+            function: sections.column(SectionKind::FrameFunction)?,
+            module: sections.column(SectionKind::FrameModule)?,
+            file: sections.column(SectionKind::FrameFile)?,
+            line: sections.column(SectionKind::FrameLine)?,
+        },
+        stacks: StackTable {
+            parent: sections.column(SectionKind::StackParent)?,
+            frame: sections.column(SectionKind::StackFrame)?,
+            depth: sections.column(SectionKind::StackDepth)?,
+        },
+        samples: SampleTable {
+            time: sections.column(SectionKind::SampleTime)?,
+            tid: sections.column(SectionKind::SampleTid)?,
+            cpu: sections.column(SectionKind::SampleCpu)?,
+            stack: sections.column(SectionKind::SampleStack)?,
+            weight: sections.column(SectionKind::SampleWeight)?,
+        },
+        chunks: ChunkTable {
+            tid: sections.column(SectionKind::ChunkTid)?,
+            start_time: sections.column(SectionKind::ChunkStartTime)?,
+            end_time: sections.column(SectionKind::ChunkEndTime)?,
+            first_sample: sections.column(SectionKind::ChunkFirstSample)?,
+            sample_count: sections.column(SectionKind::ChunkSampleCount)?,
+            max_depth: sections.column(SectionKind::ChunkMaxDepth)?,
+        },
+        threads: ThreadTable {
+            tid: sections.column(SectionKind::ThreadTid)?,
+            pid: sections.column(SectionKind::ThreadPid)?,
+            name: sections.column(SectionKind::ThreadName)?,
+            first_chunk: sections.column(SectionKind::ThreadFirstChunk)?,
+            chunk_count: sections.column(SectionKind::ThreadChunkCount)?,
+        },
+        markers: read_markers(&sections)?,
+        tracepoints: read_tracepoints(&sections)?,
+    };
+
+    validate_profile(&profile)?;
+    Ok(profile)
+}
+
+struct SectionReader {
+    mmap: Arc<Mmap>,
+    // Section kind -> (element size, offset, element count)
+    directory: HashMap<u32, (u32, u64, u64)>,
+}
+
+impl SectionReader {
+    fn new(mmap: Arc<Mmap>) -> Result<Self, IOError> {
+        if mmap.len() < 16 || &mmap[0..8] != WTF_MAGIC {
+            return Err(invalid_data("Not a .wtf profile"));
+        }
+        let version = u32::from_le_slice(&mmap[8..12]);
+        if version != WTF_VERSION {
+            let error_message =
+                format!("Unsupported .wtf version: Got {version}, expected {WTF_VERSION}");
+            return Err(IOError::new(ErrorKind::InvalidInput, error_message));
+        }
+
+        let section_count = u32::from_le_slice(&mmap[12..16]) as usize;
+        let directory_bytes = mmap
+            .get(16..16 + 24 * section_count)
+            .ok_or_else(|| invalid_data("Truncated .wtf section directory"))?;
+        let directory = directory_bytes
+            .chunks_exact(24)
+            .map(|entry| {
+                let kind = u32::from_le_slice(&entry[0..4]);
+                let element_size = u32::from_le_slice(&entry[4..8]);
+                let offset = u64::from_le_slice(&entry[8..16]);
+                let element_count = u64::from_le_slice(&entry[16..24]);
+                (kind, (element_size, offset, element_count))
+            })
+            .collect();
+
+        Ok(Self { mmap, directory })
+    }
+
+    fn contains(&self, kind: SectionKind) -> bool {
+        self.directory.contains_key(&(kind as u32))
+    }
+
+    fn column<T: ColumnValue>(&self, kind: SectionKind) -> Result<Column<T>, IOError> {
+        let &(element_size, offset, element_count) = self
+            .directory
+            .get(&(kind as u32))
+            .ok_or_else(|| invalid_data("Missing .wtf section"))?;
+        if element_size as usize != mem::size_of::<T>() {
+            return Err(invalid_data("Unexpected .wtf section element size"));
+        }
+
+        // Sizes come from the file, so they can't be trusted not to overflow
+        let offset = usize::try_from(offset).map_err(|_| invalid_data("Truncated .wtf section"))?;
+        let len =
+            usize::try_from(element_count).map_err(|_| invalid_data("Truncated .wtf section"))?;
+        let end = len
+            .checked_mul(mem::size_of::<T>())
+            .and_then(|size| size.checked_add(offset))
+            .ok_or_else(|| invalid_data("Truncated .wtf section"))?;
+        let bytes = self
+            .mmap
+            .get(offset..end)
+            .ok_or_else(|| invalid_data("Truncated .wtf section"))?;
+
+        // Columns can only be borrowed from the file when the in-memory layout matches
+        let is_aligned = (bytes.as_ptr() as usize) % mem::align_of::<T>() == 0;
+        if cfg!(target_endian = "little") && is_aligned {
+            Ok(Column::mapped(self.mmap.clone(), offset, len))
+        } else {
+            let values = bytes
+                .chunks_exact(mem::size_of::<T>())
+                .map(T::from_le_slice)
+                .collect::<Vec<_>>();
+            Ok(values.into())
+        }
+    }
+}
+
+// Markers were added after the other tables, so profiles without them have no marker sections
+fn read_markers(sections: &SectionReader) -> Result<Vec<Marker>, IOError> {
+    if !sections.contains(SectionKind::MarkerStart) {
+        return Ok(Vec::new());
+    }
+    let start = sections.column::<u64>(SectionKind::MarkerStart)?;
+    let duration = sections.column::<u64>(SectionKind::MarkerDuration)?;
+    let name_offsets = sections.column::<u32>(SectionKind::MarkerNameOffsets)?;
+    let name_bytes = sections.column::<u8>(SectionKind::MarkerNameBytes)?;
+    if duration.len() != start.len()
+        || name_offsets.len() != start.len() + 1
+        || name_offsets.windows(2).any(|w| w[0] > w[1])
+        || name_offsets.last().map(|&end| end as usize) != Some(name_bytes.len())
+    {
+        return Err(invalid_data("Invalid .wtf marker table"));
+    }
+
+    let mut markers = (0..start.len())
+        .map(|i| {
+            let name = &name_bytes[name_offsets[i] as usize..name_offsets[i + 1] as usize];
+            let name = String::from_utf8(name.to_vec())
+                .map_err(|_| invalid_data("Invalid .wtf marker name"))?;
+            Ok(Marker {
+                start: start[i],
+                duration: duration[i],
+                name,
+            })
+        })
+        .collect::<Result<Vec<_>, IOError>>()?;
+    markers.sort_by_key(|marker| marker.start);
+    Ok(markers)
+}
+
+// Tracepoint sections are optional like the marker sections, so files written before they were
+// added are still version 2. They're checked along with the rest of the profile.
+fn read_tracepoints(sections: &SectionReader) -> Result<TracepointTable, IOError> {
+    if !sections.contains(SectionKind::TracepointTime) {
+        return Ok(TracepointTable {
+            time: Vec::new().into(),
+            tid: Vec::new().into(),
+            cpu: Vec::new().into(),
+            name: Vec::new().into(),
+            first_field: vec![0].into(),
+            field_name: Vec::new().into(),
+            field_value: Vec::new().into(),
+        });
+    }
+    Ok(TracepointTable {
+        time: sections.column(SectionKind::TracepointTime)?,
+        tid: sections.column(SectionKind::TracepointTid)?,
+        cpu: sections.column(SectionKind::TracepointCpu)?,
+        name: sections.column(SectionKind::TracepointName)?,
+        first_field: sections.column(SectionKind::TracepointFirstField)?,
+        field_name: sections.column(SectionKind::TracepointFieldName)?,
+        field_value: sections.column(SectionKind::TracepointFieldValue)?,
+    })
+}
+
+// Check every index in the profile, so that the rest of the program can index columns freely
+fn validate_profile(profile: &Profile) -> Result<(), IOError> {
+    let offsets = &profile.strings.offsets;
+    let string_bytes = profile.strings.bytes.len() as u32;
+    if offsets.first() != Some(&0)
+        || offsets.last() != Some(&string_bytes)
+        || offsets.windows(2).any(|w| w[0] > w[1])
+    {
+        return Err(invalid_data("Invalid .wtf string table"));
+    }
+
+    let string_count = profile.strings.len() as u32;
+    let is_string = |id: &u32| *id < string_count;
+    let frames = &profile.frames;
+    let frame_count = frames.len();
+    let frame_lengths = [
+        frames.function.len(),
+        frames.module.len(),
+        frames.file.len(),
+    ];
+    if frame_lengths
+        .iter()
+        .chain([&frames.line.len()])
+        .any(|&len| len != frame_count)
+        || !frames.function.iter().all(is_string)
+        || !frames.module.iter().all(is_string)
+        || !frames.file.iter().all(is_string)
+    {
+        return Err(invalid_data("Invalid .wtf frame table"));
+    }
+
+    let stacks = &profile.stacks;
+    let stack_count = stacks.len() as u32;
+    // Parents always come before their children, which also rules out cycles
+    let parents_valid =
+        (stacks.parent.iter().enumerate()).all(|(i, &p)| p == NO_STACK || (p as usize) < i);
+    if stacks.parent.len() != stacks.len()
+        || stacks.depth.len() != stacks.len()
+        || !stacks.frame.iter().all(|&f| (f as usize) < frame_count)
+        || !parents_valid
+    {
+        return Err(invalid_data("Invalid .wtf stack table"));
+    }
+    // Depths are one more than their parent's, as stack_depth and chunk max depths rely on them
+    let depths_valid = (0..stacks.len()).all(|i| {
+        let parent_depth = match stacks.parent[i] {
+            NO_STACK => 0,
+            parent => stacks.depth[parent as usize],
+        };
+        parent_depth.checked_add(1) == Some(stacks.depth[i])
+    });
+    if !depths_valid {
+        return Err(invalid_data("Invalid .wtf stack table"));
+    }
+
+    let samples = &profile.samples;
+    let sample_count = samples.len();
+    let sample_lengths = [samples.tid.len(), samples.cpu.len(), samples.stack.len()];
+    if sample_lengths
+        .iter()
+        .chain([&samples.weight.len()])
+        .any(|&len| len != sample_count)
+        || !samples
+            .stack
+            .iter()
+            .all(|&s| s == NO_STACK || s < stack_count)
+    {
+        return Err(invalid_data("Invalid .wtf sample table"));
+    }
+    // Range lookups binary search each thread's samples by time
+    let samples_sorted = (1..sample_count)
+        .all(|i| (samples.tid[i - 1], samples.time[i - 1]) <= (samples.tid[i], samples.time[i]));
+    if !samples_sorted {
+        return Err(invalid_data("Unsorted .wtf sample table"));
+    }
+
+    let chunks = &profile.chunks;
+    let chunk_count = chunks.len();
+    let chunk_lengths = [
+        chunks.start_time.len(),
+        chunks.end_time.len(),
+        chunks.first_sample.len(),
+    ];
+    if chunk_lengths
+        .iter()
+        .chain([&chunks.sample_count.len(), &chunks.max_depth.len()])
+        .any(|&len| len != chunk_count)
+        || !(0..chunk_count).all(|chunk| chunks.samples(chunk).end <= sample_count)
+    {
+        return Err(invalid_data("Invalid .wtf chunk table"));
+    }
+    // Chunks split the samples in order, and their aggregates stand in for their samples when
+    // zoomed out, so they must agree
+    let mut next_sample = 0;
+    for chunk in 0..chunk_count {
+        let chunk_samples = chunks.samples(chunk);
+        let chunk_valid = chunk_samples.start == next_sample
+            && !chunk_samples.is_empty()
+            && samples.tid[chunk_samples.clone()]
+                .iter()
+                .all(|&tid| tid == chunks.tid[chunk])
+            && samples.time[chunk_samples.start] == chunks.start_time[chunk]
+            && samples.time[chunk_samples.end - 1] == chunks.end_time[chunk]
+            && samples.stack[chunk_samples.clone()]
+                .iter()
+                .map(|&s| {
+                    if s == NO_STACK {
+                        0
+                    } else {
+                        stacks.depth[s as usize]
+                    }
+                })
+                .max()
+                == Some(chunks.max_depth[chunk]);
+        if !chunk_valid {
+            return Err(invalid_data("Invalid .wtf chunk table"));
+        }
+        next_sample = chunk_samples.end;
+    }
+    if next_sample != sample_count {
+        return Err(invalid_data("Invalid .wtf chunk table"));
+    }
+
+    let threads = &profile.threads;
+    let thread_lengths = [
+        threads.pid.len(),
+        threads.name.len(),
+        threads.first_chunk.len(),
+    ];
+    if thread_lengths.iter().chain([&threads.chunk_count.len()]).any(|&len| len != threads.len())
+        // Threads are looked up by binary search
+        || !threads.tid.windows(2).all(|w| w[0] < w[1])
+        || !(0..threads.len()).all(|thread| {
+            let first_chunk = threads.first_chunk[thread] as usize;
+            let end_chunk = first_chunk + threads.chunk_count[thread] as usize;
+            end_chunk <= chunk_count
+                && chunks.tid[first_chunk..end_chunk]
+                    .iter()
+                    .all(|&tid| tid == threads.tid[thread])
+        })
+        || !threads.name.iter().all(is_string)
+        || !profile
+            .metadata
+            .iter()
+            .all(|(k, v)| is_string(k) && is_string(v))
+    {
+        return Err(invalid_data("Invalid .wtf thread table or metadata"));
+    }
+
+    let tracepoints = &profile.tracepoints;
+    let tracepoint_lengths = [
+        tracepoints.tid.len(),
+        tracepoints.cpu.len(),
+        tracepoints.name.len(),
+    ];
+    let first_field = &tracepoints.first_field;
+    let field_count = tracepoints.field_name.len();
+    if tracepoint_lengths
+        .iter()
+        .any(|&len| len != tracepoints.len())
+        || first_field.len() != tracepoints.len() + 1
+        || first_field.first() != Some(&0)
+        || first_field.last().map(|&end| end as usize) != Some(field_count)
+        || first_field.windows(2).any(|w| w[0] > w[1])
+        || tracepoints.field_value.len() != field_count
+        || tracepoints.time.windows(2).any(|w| w[0] > w[1])
+        || !tracepoints.name.iter().all(is_string)
+        || !tracepoints.field_name.iter().all(is_string)
+        || !tracepoints.field_value.iter().all(is_string)
+    {
+        return Err(invalid_data("Invalid .wtf tracepoint table"));
+    }
+
+    Ok(())
+}
+
+fn invalid_data(error: &str) -> IOError {
+    IOError::new(ErrorKind::InvalidData, error)
+}
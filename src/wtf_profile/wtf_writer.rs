@@ -0,0 +1,163 @@
+use super::{ColumnValue, Marker, Profile, SectionKind, WTF_MAGIC, WTF_VERSION};
+use std::fs::{self, File};
+use std::io::{BufWriter, Error as IOError, Write};
+use std::mem;
+use std::ops::Deref;
+use std::path::Path;
+
+// The given markers are written in place of the profile's own, which may have been edited since
+// the profile was loaded
+pub fn write_wtf_profile<P: AsRef<Path>>(
+    profile: &Profile,
+    markers: &[Marker],
+    path: P,
+) -> Result<(), IOError> {
+    let metadata = profile
+        .metadata
+        .iter()
+        .flat_map(|&(key, value)| [key, value])
+        .collect::<Vec<_>>();
+
+    let marker_start = markers.iter().map(|m| m.start).collect::<Vec<_>>();
+    let marker_duration = markers.iter().map(|m| m.duration).collect::<Vec<_>>();
+    let mut marker_name_offsets = vec![0];
+    let mut marker_name_bytes = Vec::new();
+    for marker in markers {
+        marker_name_bytes.extend_from_slice(marker.name.as_bytes());
+        marker_name_offsets.push(marker_name_bytes.len() as u32);
+    }
+
+    let mut sections = SectionWriter::default();
+    sections.add(SectionKind::Metadata, &metadata);
+    sections.add(SectionKind::StringOffsets, &profile.strings.offsets);
+    sections.add(SectionKind::StringBytes, &profile.strings.bytes);
+    sections.add(SectionKind::FrameAddress, &profile.frames.address);
+    sections.add(SectionKind::FrameFunction, &profile.frames.function);
+    sections.add(SectionKind::FrameModule, &profile.frames.module);
+    sections.add(SectionKind::FrameFile, &profile.frames.file);
+    sections.add(SectionKind::FrameLine, &profile.frames.line);
+    sections.add(SectionKind::StackParent, &profile.stacks.parent);
+    sections.add(SectionKind::StackFrame, &profile.stacks.frame);
+    sections.add(SectionKind::StackDepth, &profile.stacks.depth);
+    sections.add(SectionKind::SampleTime, &profile.samples.time);
+    sections.add(SectionKind::SampleTid, &profile.samples.tid);
+    sections.add(SectionKind::SampleCpu, &profile.samples.cpu);
+    sections.add(SectionKind::SampleStack, &profile.samples.stack);
+    sections.add(SectionKind::SampleWeight, &profile.samples.weight);
+    sections.add(SectionKind::ThreadTid, &profile.threads.tid);
+    sections.add(SectionKind::ThreadPid, &profile.threads.pid);
+    sections.add(SectionKind::ThreadName, &profile.threads.name);
+    sections.add(SectionKind::ThreadFirstChunk, &profile.threads.first_chunk);
+    sections.add(SectionKind::ThreadChunkCount, &profile.threads.chunk_count);
+    sections.add(SectionKind::ChunkTid, &profile.chunks.tid);
+    sections.add(SectionKind::ChunkStartTime, &profile.chunks.start_time);
+    sections.add(SectionKind::ChunkEndTime, &profile.chunks.end_time);
+    sections.add(SectionKind::ChunkFirstSample, &profile.chunks.first_sample);
+    sections.add(SectionKind::ChunkSampleCount, &profile.chunks.sample_count);
+    sections.add(SectionKind::ChunkMaxDepth, &profile.chunks.max_depth);
+    sections.add(SectionKind::MarkerStart, &marker_start);
+    sections.add(SectionKind::MarkerDuration, &marker_duration);
+    sections.add(SectionKind::MarkerNameOffsets, &marker_name_offsets);
+    sections.add(SectionKind::MarkerNameBytes, &marker_name_bytes);
+    sections.add(SectionKind::TracepointTime, &profile.tracepoints.time);
+    sections.add(SectionKind::TracepointTid, &profile.tracepoints.tid);
+    sections.add(SectionKind::TracepointCpu, &profile.tracepoints.cpu);
+    sections.add(SectionKind::TracepointName, &profile.tracepoints.name);
+    sections.add(
+        SectionKind::TracepointFirstField,
+        &profile.tracepoints.first_field,
+    );
+    sections.add(
+        SectionKind::TracepointFieldName,
+        &profile.tracepoints.field_name,
+    );
+    sections.add(
+        SectionKind::TracepointFieldValue,
+        &profile.tracepoints.field_value,
+    );
+
+    // The profile may be memory-mapped from the file being replaced, so that file is never
+    // truncated, a new one is renamed over it instead
+    let path = path.as_ref();
+    let mut temp_path = path.as_os_str().to_owned();
+    temp_path.push(".tmp");
+    let mut file = BufWriter::new(File::create(&temp_path)?);
+    sections.write(&mut file)?;
+    file.flush()?;
+    drop(file);
+    fs::rename(&temp_path, path)
+}
+
+#[derive(Default)]
+struct SectionWriter<'a> {
+    sections: Vec<Section<'a>>,
+}
+
+struct Section<'a> {
+    kind: SectionKind,
+    element_size: u32,
+    element_count: u64,
+    write_elements: WriteElements<'a>,
+}
+
+type WriteElements<'a> = Box<dyn Fn(&mut dyn Write) -> Result<(), IOError> + 'a>;
+
+impl<'a> SectionWriter<'a> {
+    // Accepts either a Vec or a Column
+    fn add<T: ColumnValue, C: Deref<Target = [T]>>(&mut self, kind: SectionKind, values: &'a C) {
+        let values: &'a [T] = values;
+        self.sections.push(Section {
+            kind,
+            element_size: mem::size_of::<T>() as u32,
+            element_count: values.len() as u64,
+            write_elements: Box::new(move |mut writer| {
+                values
+                    .iter()
+                    .try_for_each(|value| value.write_le(&mut writer))
+            }),
+        });
+    }
+
+    fn write<W: Write>(&self, writer: &mut W) -> Result<(), IOError> {
+        // Header + section directory
+        let directory_size = 16 + 24 * self.sections.len() as u64;
+        writer.write_all(WTF_MAGIC)?;
+        writer.write_all(&WTF_VERSION.to_le_bytes())?;
+        writer.write_all(&(self.sections.len() as u32).to_le_bytes())?;
+
+        // Sections are 8 byte aligned so that every column can be used straight from a memory map
+        let mut offset = align(directory_size);
+        for section in &self.sections {
+            writer.write_all(&(section.kind as u32).to_le_bytes())?;
+            writer.write_all(&section.element_size.to_le_bytes())?;
+            writer.write_all(&offset.to_le_bytes())?;
+            writer.write_all(&section.element_count.to_le_bytes())?;
+            offset = align(offset + section.byte_size());
+        }
+
+        let mut position = directory_size;
+        for section in &self.sections {
+            write_padding(writer, &mut position)?;
+            (section.write_elements)(writer)?;
+            position += section.byte_size();
+        }
+        write_padding(writer, &mut position)
+    }
+}
+
+impl Section<'_> {
+    fn byte_size(&self) -> u64 {
+        self.element_size as u64 * self.element_count
+    }
+}
+
+fn align(offset: u64) -> u64 {
+    (offset + 7) & !7
+}
+
+fn write_padding<W: Write>(writer: &mut W, position: &mut u64) -> Result<(), IOError> {
+    let padding = align(*position) - *position;
+    writer.write_all(&[0; 8][..padding as usize])?;
+    *position += padding;
+    Ok(())
+}